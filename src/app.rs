@@ -1,23 +1,178 @@
+use crate::auth;
+use crate::config::Config;
+use crate::errors;
 use crate::handlers;
+use crate::idempotency;
+use crate::live;
+use crate::ratelimit;
 use crate::state::AppState;
 use axum::{
-    routing::{get, post},
     Router,
+    http::{HeaderName, HeaderValue, Method, header},
+    middleware,
+    routing::{get, post, put},
 };
+use tower_http::LatencyUnit;
+use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
+use tower_http::compression::CompressionLayer;
+use tower_http::compression::predicate::{DefaultPredicate, Predicate, SizeAbove};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
+use tracing::{Level, warn};
+
+/// `/export.toml` and `/export.yaml`, for builds with the `toml` and `yaml`
+/// features.
+fn export_routes() -> Router<AppState> {
+    let routes = Router::new();
+    #[cfg(feature = "toml")]
+    let routes = routes.route("/export.toml", get(crate::export::export_toml));
+    #[cfg(feature = "yaml")]
+    let routes = routes.route("/export.yaml", get(crate::export::export_yaml));
+    routes
+}
 
 pub fn router(state: AppState) -> Router {
-    let api_v1 = Router::new()
+    let limited = || middleware::from_fn_with_state(state.clone(), ratelimit::limit_clicks);
+    let authed = || middleware::from_fn_with_state(state.clone(), auth::require_token);
+    let mut api_v1 = Router::new()
         .route("/today", get(handlers::get_today))
+        .route("/ws", get(live::get_ws))
         .route("/stats", get(handlers::get_stats))
-        .route("/click", post(handlers::click));
+        .route("/stats/stream", get(live::stats_stream))
+        .route("/chart.svg", get(handlers::get_chart_svg))
+        .route("/status", get(handlers::get_status))
+        .route("/config", get(handlers::get_config))
+        .route("/whoami", get(handlers::whoami))
+        .route("/openapi.json", get(handlers::openapi))
+        .route("/setup/status", get(handlers::setup_status))
+        .route("/setup", post(handlers::setup))
+        .route("/datastamp", get(handlers::get_datastamp))
+        .route("/maintenance/message", post(handlers::set_maintenance))
+        .route(
+            "/target",
+            get(handlers::get_target).put(handlers::set_target),
+        )
+        .route("/click", post(handlers::click).layer(limited()))
+        .route("/click/preview", post(handlers::preview_click))
+        .route("/click/:date", post(handlers::click_on).layer(limited()))
+        .route("/undo", post(handlers::undo))
+        .route("/week", get(handlers::get_week))
+        .route("/week/:label", get(handlers::get_week_by_label))
+        .route("/compare/week", get(handlers::compare_week))
+        .route("/milestone", get(handlers::milestone))
+        .route("/histogram", get(handlers::histogram))
+        .route("/range", get(handlers::get_range))
+        .route("/heatmap", get(handlers::get_heatmap))
+        .route("/days", get(handlers::list_days))
+        .route("/history", get(handlers::get_history))
+        .route("/import", post(handlers::import))
+        .route("/export.csv", get(handlers::export_csv))
+        .route("/import.csv", post(handlers::import_csv))
+        .route("/sync", post(handlers::sync))
+        .route("/reset", post(handlers::reset_all))
+        .route("/week/:label/note", put(handlers::put_week_note))
+        .route(
+            "/day/:date",
+            get(handlers::get_day)
+                .put(handlers::set_day)
+                .delete(handlers::delete_day),
+        )
+        .route("/day/:date/tags", post(handlers::set_day_tags))
+        .merge(export_routes())
+        .route_layer(authed());
+    if let Some(cors) = cors_layer(&state.config) {
+        api_v1 = api_v1.layer(cors);
+    }
 
     Router::new()
         .route("/", get(handlers::index))
-        .route("/click/add", post(handlers::click_add))
-        .route("/click/sub", post(handlers::click_sub))
+        .route(
+            "/click/add",
+            post(handlers::click_add).layer(limited()).layer(authed()),
+        )
+        .route(
+            "/click/sub",
+            post(handlers::click_sub).layer(limited()).layer(authed()),
+        )
+        .route("/grafana/", get(handlers::grafana_health))
+        .route("/grafana/search", post(handlers::grafana_search))
+        .route("/grafana/query", post(handlers::grafana_query))
         .nest("/api/v1", api_v1.clone())
-        .route("/api/today", get(handlers::get_today))
-        .route("/api/stats", get(handlers::get_stats))
-        .route("/api/click", post(handlers::click))
+        .nest("/api", api_v1)
+        .layer(trace_layer(Level::INFO))
+        // Added after the layer above so probes and scrapes get their own,
+        // quieter one.
+        .route(
+            "/metrics",
+            get(handlers::metrics).layer(trace_layer(Level::DEBUG)),
+        )
+        .route(
+            "/healthz",
+            get(handlers::healthz).layer(trace_layer(Level::DEBUG)),
+        )
+        .layer(middleware::from_fn(errors::negotiate_errors))
+        .layer(compression_layer())
         .with_state(state)
 }
+
+/// Logs one line per request with the method, path, status and latency at
+/// `level`. Server errors are logged at `ERROR` whatever the level.
+fn trace_layer(
+    level: Level,
+) -> TraceLayer<SharedClassifier<ServerErrorsAsFailures>, DefaultMakeSpan, (), DefaultOnResponse> {
+    TraceLayer::new_for_http()
+        .make_span_with(DefaultMakeSpan::new().level(level))
+        .on_request(())
+        .on_response(
+            DefaultOnResponse::new()
+                .level(level)
+                .latency_unit(LatencyUnit::Millis),
+        )
+}
+
+/// Responses below this many bytes go out as they are; compressing them
+/// barely saves anything.
+const MIN_COMPRESS_BYTES: u16 = 1024;
+
+/// gzip or deflate, whichever the client's `Accept-Encoding` prefers. The
+/// default predicate already leaves images and `text/event-stream` alone, so
+/// SSE events aren't held back in a compressor buffer.
+fn compression_layer() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new()
+        .compress_when(DefaultPredicate::new().and(SizeAbove::new(MIN_COMPRESS_BYTES)))
+}
+
+/// CORS for the API routes, from `APP_CORS_ORIGINS`. Origins that aren't
+/// valid header values are skipped with a warning.
+fn cors_layer(config: &Config) -> Option<CorsLayer> {
+    if config.cors_origins.is_empty() {
+        return None;
+    }
+    let origin = if config.cors_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::from(Any)
+    } else {
+        let origins: Vec<HeaderValue> = config
+            .cors_origins
+            .iter()
+            .filter_map(|origin| {
+                HeaderValue::from_str(origin)
+                    .inspect_err(|_| warn!("ignoring invalid CORS origin {origin:?}"))
+                    .ok()
+            })
+            .collect();
+        AllowOrigin::list(origins)
+    };
+    Some(
+        CorsLayer::new()
+            .allow_origin(origin)
+            .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+            .allow_headers([
+                header::CONTENT_TYPE,
+                header::ACCEPT,
+                header::IF_NONE_MATCH,
+                header::AUTHORIZATION,
+                HeaderName::from_static(idempotency::HEADER),
+            ])
+            .expose_headers([header::ETAG, header::RETRY_AFTER]),
+    )
+}
@@ -0,0 +1,56 @@
+//! Optional bearer-token check for writes (`APP_API_TOKEN`). Layered over the
+//! whole API, where any request that isn't a `GET`, `HEAD` or `OPTIONS` needs
+//! `Authorization: Bearer <token>`, so new write endpoints are covered
+//! without being listed here; outside the API it guards the form buttons.
+//! Reads, the page itself and `POST`s that only compute an answer stay
+//! public.
+
+use crate::errors::AppError;
+use crate::state::AppState;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// API `POST` routes that don't change anything, as seen inside the nested
+/// router.
+const READ_ONLY_POSTS: &[&str] = &["/click/preview"];
+
+pub async fn require_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(token) = state.config.api_token.as_deref() else {
+        return next.run(request).await;
+    };
+    let is_write = !matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) && !READ_ONLY_POSTS.contains(&request.uri().path());
+    if !is_write || bearer_matches(request.headers(), token) {
+        return next.run(request).await;
+    }
+
+    let mut response = AppError::unauthorized("a valid bearer token is required").into_response();
+    response
+        .headers_mut()
+        .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    response
+}
+
+fn bearer_matches(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|presented| constant_time_eq(presented.trim().as_bytes(), token.as_bytes()))
+}
+
+/// Compares without stopping at the first differing byte, so response
+/// timing doesn't reveal how much of a guess was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
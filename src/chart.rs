@@ -0,0 +1,315 @@
+//! Server-side rendering of the dashboard line chart. Mirrors the
+//! `renderLineChart` function in the page script so exported SVGs match what
+//! the browser draws.
+
+use crate::models::StatsResponse;
+use std::fmt::Write;
+
+const WIDTH: f64 = 600.0;
+const HEIGHT: f64 = 260.0;
+const PADDING_X: f64 = 44.0;
+const PADDING_Y: f64 = 34.0;
+const TOP: f64 = 24.0;
+const TICKS: usize = 4;
+
+const STYLE: &str = "text{font-family:\"Space Grotesk\",\"Trebuchet MS\",sans-serif}\
+.chart-line{fill:none;stroke:#ff6b4a;stroke-width:3}\
+.chart-point{fill:white;stroke:#ff6b4a;stroke-width:2}\
+.chart-grid{stroke:rgba(47,72,88,0.12)}\
+.chart-axis{stroke:rgba(47,72,88,0.25);stroke-dasharray:4 6}\
+.chart-label{fill:#7a746d;font-size:11px}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartView {
+    Daily,
+    Weekly,
+    Average,
+}
+
+impl ChartView {
+    pub fn parse(value: Option<&str>) -> Option<Self> {
+        match value.map(str::trim) {
+            None | Some("") | Some("daily") => Some(Self::Daily),
+            Some("weekly") => Some(Self::Weekly),
+            Some("average") => Some(Self::Average),
+            Some(_) => None,
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Daily => "Last 7 days",
+            Self::Weekly => "Weekly totals",
+            Self::Average => "Weekly averages",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartPoint {
+    pub label: String,
+    pub value: f64,
+}
+
+/// Picks the series the dashboard plots for `view`.
+pub fn chart_points(stats: &StatsResponse, view: ChartView) -> Vec<ChartPoint> {
+    match view {
+        ChartView::Daily => stats
+            .last_7_days
+            .iter()
+            .map(|day| ChartPoint {
+                label: day.date.get(5..).unwrap_or(&day.date).to_string(),
+                value: day.net as f64,
+            })
+            .collect(),
+        ChartView::Weekly => stats
+            .weekly_totals
+            .iter()
+            .map(|week| ChartPoint {
+                label: week.week.clone(),
+                value: week.net as f64,
+            })
+            .collect(),
+        ChartView::Average => stats
+            .weekly_averages
+            .iter()
+            .map(|week| ChartPoint {
+                label: week.week.clone(),
+                value: week.avg_net,
+            })
+            .collect(),
+    }
+}
+
+/// Renders a standalone SVG document for `points`.
+pub fn render_svg(title: &str, points: &[ChartPoint]) -> String {
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {WIDTH} {HEIGHT}\" \
+         width=\"{WIDTH}\" height=\"{HEIGHT}\" role=\"img\">"
+    );
+    let _ = write!(svg, "<title>{}</title>", escape(title));
+    let _ = write!(svg, "<style>{STYLE}</style>");
+    let _ = write!(
+        svg,
+        "<rect width=\"100%\" height=\"100%\" fill=\"white\" />"
+    );
+    svg.push_str(&render_body(points));
+    svg.push_str("</svg>");
+    svg
+}
+
+fn render_body(points: &[ChartPoint]) -> String {
+    if points.is_empty() {
+        return "<text class=\"chart-label\" x=\"50%\" y=\"50%\" text-anchor=\"middle\">\
+                No data yet</text>"
+            .to_string();
+    }
+
+    let scale = Scale::fit(points);
+    let mut out = String::new();
+
+    for i in 0..=TICKS {
+        let value = scale.min + (scale.range() * i as f64) / TICKS as f64;
+        let y = scale.y(value);
+        let _ = write!(
+            out,
+            "<line class=\"chart-grid\" x1=\"{PADDING_X}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" />",
+            WIDTH - PADDING_X
+        );
+        let _ = write!(
+            out,
+            "<text class=\"chart-label\" x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+            PADDING_X - 10.0,
+            y + 4.0,
+            format_axis_value(value)
+        );
+    }
+
+    let zero = scale.y(0.0);
+    let _ = write!(
+        out,
+        "<line class=\"chart-axis\" x1=\"{PADDING_X}\" y1=\"{zero}\" x2=\"{}\" y2=\"{zero}\" />",
+        WIDTH - PADDING_X
+    );
+
+    let _ = write!(
+        out,
+        "<path class=\"chart-line\" d=\"{}\" />",
+        line_path(points)
+    );
+
+    for (index, point) in points.iter().enumerate() {
+        let _ = write!(
+            out,
+            "<circle class=\"chart-point\" cx=\"{}\" cy=\"{}\" r=\"4\" />",
+            scale.x(index),
+            scale.y(point.value)
+        );
+    }
+
+    let label_every = if points.len() > 8 { 2 } else { 1 };
+    for (index, point) in points.iter().enumerate() {
+        if index % label_every != 0 {
+            continue;
+        }
+        let _ = write!(
+            out,
+            "<text class=\"chart-label\" x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            scale.x(index),
+            HEIGHT - PADDING_Y + 18.0,
+            escape(&point.label)
+        );
+    }
+
+    out
+}
+
+/// The `d` attribute of the chart line, with coordinates rounded to two
+/// decimals like the browser version.
+pub fn line_path(points: &[ChartPoint]) -> String {
+    let scale = Scale::fit(points);
+    points
+        .iter()
+        .enumerate()
+        .map(|(index, point)| {
+            let command = if index == 0 { 'M' } else { 'L' };
+            format!(
+                "{command} {:.2} {:.2}",
+                scale.x(index),
+                scale.y(point.value)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Maps values onto the SVG canvas. The vertical range always includes zero
+/// and is widened to `[-1, 1]` when every value is the same.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scale {
+    min: f64,
+    max: f64,
+    x_step: f64,
+}
+
+impl Scale {
+    fn fit(points: &[ChartPoint]) -> Self {
+        let mut min = points.iter().map(|p| p.value).fold(0.0_f64, f64::min);
+        let mut max = points.iter().map(|p| p.value).fold(0.0_f64, f64::max);
+        if min == max {
+            min -= 1.0;
+            max += 1.0;
+        }
+        let x_step = if points.len() > 1 {
+            (WIDTH - PADDING_X * 2.0) / (points.len() - 1) as f64
+        } else {
+            0.0
+        };
+        Self { min, max, x_step }
+    }
+
+    fn range(&self) -> f64 {
+        self.max - self.min
+    }
+
+    fn x(&self, index: usize) -> f64 {
+        PADDING_X + index as f64 * self.x_step
+    }
+
+    fn y(&self, value: f64) -> f64 {
+        let scale_y = (HEIGHT - TOP - PADDING_Y) / self.range();
+        HEIGHT - PADDING_Y - (value - self.min) * scale_y
+    }
+}
+
+fn format_axis_value(value: f64) -> String {
+    // Same rounding as JavaScript's Math.round (halves round up).
+    let rounded = (value * 10.0 + 0.5).floor() / 10.0;
+    if rounded.fract() == 0.0 {
+        format!("{}", rounded as i64)
+    } else {
+        format!("{rounded:.1}")
+    }
+}
+
+pub(crate) fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(values: &[f64]) -> Vec<ChartPoint> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| ChartPoint {
+                label: format!("p{index}"),
+                value: *value,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn path_spans_the_plot_area() {
+        let path = line_path(&points(&[0.0, 10.0]));
+        assert_eq!(path, "M 44.00 226.00 L 556.00 24.00");
+    }
+
+    #[test]
+    fn scale_always_includes_zero() {
+        let path = line_path(&points(&[5.0, 10.0, 5.0]));
+        // min is pulled down to 0, so 5 sits halfway up the plot.
+        assert_eq!(path, "M 44.00 125.00 L 300.00 24.00 L 556.00 125.00");
+
+        let negative = line_path(&points(&[-4.0, 0.0]));
+        assert_eq!(negative, "M 44.00 226.00 L 556.00 24.00");
+    }
+
+    #[test]
+    fn flat_series_is_centered() {
+        let path = line_path(&points(&[0.0, 0.0, 0.0]));
+        assert_eq!(path, "M 44.00 125.00 L 300.00 125.00 L 556.00 125.00");
+    }
+
+    #[test]
+    fn axis_values_use_one_decimal_at_most() {
+        assert_eq!(format_axis_value(2.0), "2");
+        assert_eq!(format_axis_value(0.25), "0.3");
+        assert_eq!(format_axis_value(-0.25), "-0.2");
+        assert_eq!(format_axis_value(1.04), "1");
+    }
+
+    #[test]
+    fn svg_is_standalone_and_labels_every_other_point_when_crowded() {
+        let svg = render_svg("Weekly <totals>", &points(&[1.0; 9]));
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>"));
+        assert!(svg.contains("<title>Weekly &lt;totals&gt;</title>"));
+        assert_eq!(svg.matches("<circle").count(), 9);
+        assert!(svg.contains(">p0</text>"));
+        assert!(!svg.contains(">p1</text>"));
+        assert!(svg.contains(">p8</text>"));
+    }
+
+    #[test]
+    fn empty_series_renders_placeholder() {
+        let svg = render_svg("Empty", &[]);
+        assert!(svg.contains("No data yet"));
+        assert!(!svg.contains("<path"));
+    }
+
+    #[test]
+    fn view_parsing_defaults_to_daily() {
+        assert_eq!(ChartView::parse(None), Some(ChartView::Daily));
+        assert_eq!(ChartView::parse(Some("average")), Some(ChartView::Average));
+        assert_eq!(ChartView::parse(Some("monthly")), None);
+    }
+}
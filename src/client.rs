@@ -0,0 +1,53 @@
+//! What the server can tell about the client behind a request.
+
+use axum::http::HeaderMap;
+use std::net::{IpAddr, SocketAddr};
+
+/// The client's address. Behind a trusted reverse proxy this is the first
+/// entry of `X-Forwarded-For`; otherwise it is the TCP peer, since anyone can
+/// send that header. `peer` is `None` when the connection info isn't
+/// available (for example in in-process tests).
+pub fn client_ip(
+    headers: &HeaderMap,
+    peer: Option<SocketAddr>,
+    trust_proxy: bool,
+) -> Option<IpAddr> {
+    if trust_proxy {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .and_then(|first| first.trim().parse().ok());
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+    peer.map(|addr| addr.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn forwarded_header_is_only_used_behind_a_trusted_proxy() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("203.0.113.7, 10.0.0.2"),
+        );
+        let peer: SocketAddr = "10.0.0.2:51000".parse().unwrap();
+
+        assert_eq!(
+            client_ip(&headers, Some(peer), true),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        assert_eq!(client_ip(&headers, Some(peer), false), Some(peer.ip()));
+        assert_eq!(
+            client_ip(&HeaderMap::new(), Some(peer), true),
+            Some(peer.ip())
+        );
+        assert_eq!(client_ip(&HeaderMap::new(), None, false), None);
+    }
+}
@@ -0,0 +1,101 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+
+/// Source of the current time. Handlers read the time through `AppState` so
+/// tests can pin it with [`FixedClock`].
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// The zone day keys are computed in: an IANA zone from `APP_TIMEZONE`, or
+/// the server's local zone (`TZ`) when that is unset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AccountingZone {
+    #[default]
+    Local,
+    Named(Tz),
+}
+
+impl AccountingZone {
+    /// Parses an IANA name such as `America/New_York`.
+    pub fn parse(value: &str) -> Option<Self> {
+        value.trim().parse::<Tz>().ok().map(Self::Named)
+    }
+
+    /// `instant` as a wall-clock time in this zone.
+    pub fn at(self, instant: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self {
+            Self::Local => instant.with_timezone(&Local).fixed_offset(),
+            Self::Named(tz) => instant.with_timezone(&tz).fixed_offset(),
+        }
+    }
+
+    /// The instant a wall-clock time in this zone refers to, taking the
+    /// earlier one when a DST change repeats it. `None` if a DST change
+    /// skips it.
+    pub fn resolve(self, local: NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        match self {
+            Self::Local => local
+                .and_local_timezone(Local)
+                .earliest()
+                .map(|time| time.fixed_offset()),
+            Self::Named(tz) => local
+                .and_local_timezone(tz)
+                .earliest()
+                .map(|time| time.fixed_offset()),
+        }
+    }
+
+    /// The IANA name, or `TZ` for the local zone if it is set.
+    pub fn name(self) -> Option<String> {
+        match self {
+            Self::Local => std::env::var("TZ").ok().filter(|tz| !tz.is_empty()),
+            Self::Named(tz) => Some(tz.name().to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone};
+
+    #[test]
+    fn day_key_follows_the_named_zone_around_midnight() {
+        // 03:30 UTC is still the previous evening in New York.
+        let instant = Utc.with_ymd_and_hms(2026, 1, 6, 3, 30, 0).unwrap();
+        let utc = AccountingZone::parse("UTC").unwrap();
+        let new_york = AccountingZone::parse("America/New_York").unwrap();
+        assert_eq!(utc.at(instant).date_naive().to_string(), "2026-01-06");
+        assert_eq!(new_york.at(instant).date_naive().to_string(), "2026-01-05");
+        assert_eq!(new_york.at(instant).offset().to_string(), "-05:00");
+
+        let midnight = NaiveDate::from_ymd_opt(2026, 1, 6)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        assert_eq!(
+            new_york.resolve(midnight).unwrap().with_timezone(&Utc),
+            Utc.with_ymd_and_hms(2026, 1, 6, 5, 0, 0).unwrap()
+        );
+        assert!(AccountingZone::parse("Mars/Olympus").is_none());
+    }
+}
@@ -0,0 +1,365 @@
+use crate::clock::AccountingZone;
+use crate::models::{CountMode, SetupRequest, WeekStart};
+use chrono::FixedOffset;
+use std::env;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::warn;
+
+const DEFAULT_MAX_STATS_POINTS: usize = 400;
+const DEFAULT_EVENT_LOG_COMPACT_SECS: u64 = 300;
+const DEFAULT_PORT: u16 = 8080;
+
+/// Runtime settings read from the environment at startup.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// Skip loading web fonts from Google so the page works on air-gapped
+    /// networks (`APP_OFFLINE=1`).
+    pub offline: bool,
+    /// Treat Saturdays and Sundays as neutral days for streaks
+    /// (`APP_STREAK_SKIP_WEEKENDS=1`).
+    pub streak_skip_weekends: bool,
+    /// Require an explicit confirmation for subtract clicks
+    /// (`APP_CONFIRM_SUB=1`).
+    pub confirm_sub: bool,
+    /// Units automatically added to each new day (`APP_AUTO_ADD_DAILY`).
+    /// See [`Config::auto_add_steps`] for the stored amount.
+    pub auto_add_daily: Option<u64>,
+    /// Hours after midnight at which a new day starts, for night owls
+    /// (`APP_DAY_OFFSET_HOURS`, 0-23).
+    pub day_offset_hours: u32,
+    /// Text for the add button (`APP_ADD_LABEL`).
+    pub add_label: Option<String>,
+    /// Text for the subtract button (`APP_SUB_LABEL`).
+    pub sub_label: Option<String>,
+    /// Flush every write to disk before acknowledging it (`APP_SYNC_WRITES=1`).
+    pub sync_writes: bool,
+    /// Take the client address from `X-Forwarded-For`, for deployments
+    /// behind a reverse proxy (`APP_TRUST_PROXY=1`).
+    pub trust_proxy: bool,
+    /// Stored steps per unit, for fractional counting (`APP_UNIT_SCALE`).
+    pub unit_scale: Option<u64>,
+    /// Zone that decides which day a click counts towards (`APP_TIMEZONE`,
+    /// an IANA name). Defaults to the server's local zone.
+    pub timezone: AccountingZone,
+    /// Fixed UTC offset used only when formatting times for clients
+    /// (`APP_DISPLAY_TIMEZONE`, e.g. `+09:00`). Day keys ignore it.
+    pub display_timezone: Option<FixedOffset>,
+    /// Page title, from the first-run setup.
+    pub title: Option<String>,
+    /// Net to aim for each day, in units (`APP_DAILY_TARGET`, or the
+    /// first-run setup). Can be changed live through `PUT /api/target`.
+    pub daily_goal: Option<u64>,
+    /// Upper bound on the points `/api/stats` returns per series
+    /// (`APP_MAX_STATS_POINTS`).
+    pub max_stats_points: Option<usize>,
+    /// Banner shown on the page at startup (`APP_MAINTENANCE_MSG`). Can be
+    /// changed live through `POST /api/maintenance/message`.
+    pub maintenance_message: Option<String>,
+    /// Reject writes with `503` while a maintenance message is set
+    /// (`APP_MAINTENANCE_READ_ONLY=1`).
+    pub maintenance_read_only: bool,
+    /// Coalesce writes and flush at most this often, in milliseconds
+    /// (`APP_PERSIST_DEBOUNCE_MS`). Unset or `0` writes on every change.
+    pub persist_debounce_ms: Option<u64>,
+    /// Clicks per minute allowed from one client address
+    /// (`APP_CLICK_RATE_LIMIT`). Unset means unlimited.
+    pub click_rate_limit: Option<u32>,
+    /// Append clicks to a log next to the data file instead of rewriting
+    /// the file on each one (`APP_EVENT_LOG=1`).
+    pub event_log: bool,
+    /// Seconds between folding the event log back into the data file
+    /// (`APP_EVENT_LOG_COMPACT_SECS`, default 300).
+    pub event_log_compact_secs: Option<u64>,
+    /// Origins allowed to call `/api` from a browser (`APP_CORS_ORIGINS`,
+    /// comma-separated, or `*` for any). Empty means no CORS headers.
+    pub cors_origins: Vec<String>,
+    /// Bearer token required on every write (`APP_API_TOKEN`). Unset leaves
+    /// writes open.
+    pub api_token: Option<String>,
+    /// HTML file to serve instead of the built-in page (`APP_UI_TEMPLATE`).
+    pub ui_template: Option<PathBuf>,
+    /// `APP_COUNT_MODE=single` keeps one running value per day that `sub`
+    /// clicks decrease, floored at zero, instead of two growing buckets.
+    pub count_mode: CountMode,
+    /// `APP_WEEK_START=sunday` starts weekly buckets on Sunday instead of
+    /// Monday.
+    pub week_start: WeekStart,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Self {
+            offline: env_flag("APP_OFFLINE"),
+            streak_skip_weekends: env_flag("APP_STREAK_SKIP_WEEKENDS"),
+            confirm_sub: env_flag("APP_CONFIRM_SUB"),
+            auto_add_daily: env::var("APP_AUTO_ADD_DAILY")
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .filter(|amount| *amount > 0),
+            day_offset_hours: env::var("APP_DAY_OFFSET_HOURS")
+                .ok()
+                .and_then(|value| value.trim().parse::<u32>().ok())
+                .filter(|hours| *hours < 24)
+                .unwrap_or(0),
+            add_label: env_label("APP_ADD_LABEL"),
+            sub_label: env_label("APP_SUB_LABEL"),
+            sync_writes: env_flag("APP_SYNC_WRITES"),
+            trust_proxy: env_flag("APP_TRUST_PROXY"),
+            unit_scale: env::var("APP_UNIT_SCALE")
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .filter(|scale| *scale > 0),
+            timezone: env_timezone("APP_TIMEZONE"),
+            display_timezone: env::var("APP_DISPLAY_TIMEZONE")
+                .ok()
+                .and_then(|value| parse_utc_offset(&value)),
+            title: None,
+            daily_goal: env::var("APP_DAILY_TARGET")
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok()),
+            max_stats_points: env::var("APP_MAX_STATS_POINTS")
+                .ok()
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .filter(|max| *max > 0),
+            maintenance_message: env_label("APP_MAINTENANCE_MSG"),
+            maintenance_read_only: env_flag("APP_MAINTENANCE_READ_ONLY"),
+            persist_debounce_ms: env::var("APP_PERSIST_DEBOUNCE_MS")
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .filter(|ms| *ms > 0),
+            click_rate_limit: env::var("APP_CLICK_RATE_LIMIT")
+                .ok()
+                .and_then(|value| value.trim().parse::<u32>().ok())
+                .filter(|limit| *limit > 0),
+            event_log: env_flag("APP_EVENT_LOG"),
+            event_log_compact_secs: env::var("APP_EVENT_LOG_COMPACT_SECS")
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .filter(|secs| *secs > 0),
+            cors_origins: env::var("APP_CORS_ORIGINS")
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(|origin| origin.trim().trim_end_matches('/').to_string())
+                        .filter(|origin| !origin.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            api_token: env_label("APP_API_TOKEN"),
+            ui_template: env_label("APP_UI_TEMPLATE").map(PathBuf::from),
+            count_mode: env_count_mode("APP_COUNT_MODE"),
+            week_start: env_week_start("APP_WEEK_START"),
+        }
+    }
+
+    /// Fills in settings from `config.toml` that the environment left unset.
+    pub fn apply_setup(&mut self, setup: &SetupRequest) {
+        if self.title.is_none() {
+            self.title = setup.title.clone();
+        }
+        if self.display_timezone.is_none() {
+            self.display_timezone = setup.timezone.as_deref().and_then(parse_utc_offset);
+        }
+        if self.daily_goal.is_none() {
+            self.daily_goal = setup.daily_goal;
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or("Daily Click Counter")
+    }
+
+    pub fn add_label(&self) -> &str {
+        self.add_label.as_deref().unwrap_or("Add +1")
+    }
+
+    pub fn sub_label(&self) -> &str {
+        self.sub_label.as_deref().unwrap_or("Subtract -1")
+    }
+
+    pub fn unit_scale(&self) -> u64 {
+        self.unit_scale.unwrap_or(1)
+    }
+
+    /// `APP_AUTO_ADD_DAILY` in stored steps, scaled like the daily goal.
+    pub fn auto_add_steps(&self) -> Option<u64> {
+        self.auto_add_daily
+            .map(|amount| amount.saturating_mul(self.unit_scale()))
+    }
+
+    pub fn max_stats_points(&self) -> usize {
+        self.max_stats_points.unwrap_or(DEFAULT_MAX_STATS_POINTS)
+    }
+
+    /// `None` means write-through.
+    pub fn persist_debounce(&self) -> Option<Duration> {
+        self.persist_debounce_ms.map(Duration::from_millis)
+    }
+
+    /// How often the event log is compacted; `None` when it is off.
+    pub fn event_log_compaction(&self) -> Option<Duration> {
+        self.event_log.then(|| {
+            Duration::from_secs(
+                self.event_log_compact_secs
+                    .unwrap_or(DEFAULT_EVENT_LOG_COMPACT_SECS),
+            )
+        })
+    }
+}
+
+fn env_count_mode(name: &str) -> CountMode {
+    match env_label(name).as_deref() {
+        None | Some("split") => CountMode::Split,
+        Some("single") => CountMode::Single,
+        Some(value) => {
+            warn!("{name}={value} is not split or single; using split");
+            CountMode::Split
+        }
+    }
+}
+
+fn env_week_start(name: &str) -> WeekStart {
+    match env_label(name)
+        .map(|value| value.to_ascii_lowercase())
+        .as_deref()
+    {
+        None | Some("monday") => WeekStart::Monday,
+        Some("sunday") => WeekStart::Sunday,
+        Some(value) => {
+            warn!("{name}={value} is not monday or sunday; using monday");
+            WeekStart::Monday
+        }
+    }
+}
+
+/// An unparseable zone falls back to the local zone with a warning rather
+/// than refusing to start.
+fn env_timezone(name: &str) -> AccountingZone {
+    let Some(value) = env_label(name) else {
+        return AccountingZone::Local;
+    };
+    AccountingZone::parse(&value).unwrap_or_else(|| {
+        warn!("{name}={value} is not a known IANA timezone; using the server's local zone");
+        AccountingZone::Local
+    })
+}
+
+fn env_label(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn env_flag(name: &str) -> bool {
+    env::var(name)
+        .map(|value| {
+            matches!(
+                value.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            )
+        })
+        .unwrap_or(false)
+}
+
+/// Parses `UTC`, `Z`, `+HH`, `+HH:MM` or `-HH:MM` into a fixed offset.
+pub fn parse_utc_offset(value: &str) -> Option<FixedOffset> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("utc") || value == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+    let (sign, rest) = match value.as_bytes().first()? {
+        b'+' => (1, &value[1..]),
+        b'-' => (-1, &value[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Where to listen: `APP_BIND_ADDR` (or `HOST`) and `PORT`, defaulting to
+/// every interface on port 8080. Unlike most settings, an unparseable host is
+/// an error rather than a fallback, so the server never ends up reachable on
+/// more interfaces than intended.
+pub fn resolve_listen_addr() -> Result<SocketAddr, std::io::Error> {
+    let host = env::var("APP_BIND_ADDR").or_else(|_| env::var("HOST")).ok();
+    listen_addr_from(host.as_deref(), env::var("PORT").ok().as_deref())
+}
+
+fn listen_addr_from(host: Option<&str>, port: Option<&str>) -> Result<SocketAddr, std::io::Error> {
+    let ip = match host.map(str::trim).filter(|host| !host.is_empty()) {
+        None => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        Some(host) => {
+            let bare = host
+                .strip_prefix('[')
+                .and_then(|host| host.strip_suffix(']'))
+                .unwrap_or(host);
+            bare.parse().map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("bind address {host} is not an IP address like 127.0.0.1 or ::1"),
+                )
+            })?
+        }
+    };
+    let port = port
+        .and_then(|value| value.trim().parse::<u16>().ok())
+        .unwrap_or(DEFAULT_PORT);
+    Ok(SocketAddr::new(ip, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listen_addr_combines_host_and_port() {
+        let addr = |host, port| listen_addr_from(host, port).map(|addr| addr.to_string());
+        assert_eq!(addr(None, None).unwrap(), "0.0.0.0:8080");
+        assert_eq!(addr(Some(" "), Some("3000")).unwrap(), "0.0.0.0:3000");
+        assert_eq!(
+            addr(Some("127.0.0.1"), Some("9000")).unwrap(),
+            "127.0.0.1:9000"
+        );
+        assert_eq!(addr(Some("::1"), None).unwrap(), "[::1]:8080");
+        assert_eq!(addr(Some("[::]"), None).unwrap(), "[::]:8080");
+        assert!(addr(Some("localhost"), None).is_err());
+        assert!(addr(Some("10.0.0.256"), None).is_err());
+    }
+
+    #[test]
+    fn auto_add_is_scaled_to_steps() {
+        let config = Config {
+            auto_add_daily: Some(3),
+            unit_scale: Some(2),
+            ..Config::default()
+        };
+        assert_eq!(config.auto_add_steps(), Some(6));
+        let config = Config {
+            auto_add_daily: Some(3),
+            ..Config::default()
+        };
+        assert_eq!(config.auto_add_steps(), Some(3));
+        assert_eq!(Config::default().auto_add_steps(), None);
+    }
+
+    #[test]
+    fn utc_offsets_parse() {
+        assert_eq!(parse_utc_offset("UTC"), FixedOffset::east_opt(0));
+        assert_eq!(parse_utc_offset("+09:00"), FixedOffset::east_opt(9 * 3600));
+        assert_eq!(
+            parse_utc_offset("-05:30"),
+            FixedOffset::west_opt(5 * 3600 + 1800)
+        );
+        assert_eq!(parse_utc_offset("+2"), FixedOffset::east_opt(2 * 3600));
+        assert_eq!(parse_utc_offset("Europe/Paris"), None);
+        assert_eq!(parse_utc_offset("+15:00"), None);
+    }
+}
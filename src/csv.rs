@@ -0,0 +1,187 @@
+//! CSV export and import of the daily counts, one `date,add,sub,net` row per
+//! day. Values are plain numbers and dates, so no quoting is needed as long
+//! as the formatted date never contains the delimiter.
+
+use crate::errors::AppError;
+use crate::models::{AppData, DayCounts};
+use chrono::NaiveDate;
+
+/// Delimiter and date format shared by export and import, so a file written
+/// with one set of options reads back with the same options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    pub date_format: String,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            date_format: "%Y-%m-%d".to_string(),
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn parse(delimiter: Option<&str>, date_format: Option<&str>) -> Result<Self, AppError> {
+        let mut options = Self::default();
+
+        if let Some(value) = delimiter.filter(|value| !value.is_empty()) {
+            let mut chars = value.chars();
+            let (Some(delimiter), None) = (chars.next(), chars.next()) else {
+                return Err(AppError::bad_request(
+                    "delimiter must be a single character",
+                ));
+            };
+            if delimiter == '"' || delimiter == '\n' || delimiter == '\r' {
+                return Err(AppError::bad_request(
+                    "delimiter must not be a quote or newline",
+                ));
+            }
+            options.delimiter = delimiter;
+        }
+
+        if let Some(format) = date_format.filter(|value| !value.trim().is_empty()) {
+            options.date_format = format.trim().to_string();
+        }
+
+        // A format is only usable if a date survives a round trip through it,
+        // which also rejects invalid specifiers and formats missing a field.
+        let probe = NaiveDate::from_ymd_opt(2026, 12, 31).unwrap_or_default();
+        let mut formatted = String::new();
+        if std::fmt::write(
+            &mut formatted,
+            format_args!("{}", probe.format(&options.date_format)),
+        )
+        .is_err()
+            || NaiveDate::parse_from_str(&formatted, &options.date_format).ok() != Some(probe)
+        {
+            return Err(AppError::bad_request(
+                "date_format must be a chrono format that includes year, month and day",
+            ));
+        }
+        if formatted.contains(options.delimiter) {
+            return Err(AppError::bad_request(
+                "date_format must not produce the delimiter",
+            ));
+        }
+
+        Ok(options)
+    }
+
+    fn header(&self) -> String {
+        let d = self.delimiter;
+        format!("date{d}add{d}sub{d}net")
+    }
+}
+
+/// Renders every stored day, oldest first. Empty data yields just the header.
+pub fn write_csv(data: &AppData, options: &CsvOptions) -> String {
+    let d = options.delimiter;
+    let mut out = options.header();
+    out.push('\n');
+    for (key, counts) in &data.days {
+        let Ok(date) = NaiveDate::parse_from_str(key, "%Y-%m-%d") else {
+            continue;
+        };
+        let net = counts.net();
+        out.push_str(&format!(
+            "{}{d}{}{d}{}{d}{net}\n",
+            date.format(&options.date_format),
+            counts.add,
+            counts.sub
+        ));
+    }
+    out
+}
+
+/// Parses rows written by [`write_csv`]. The header row is optional and the
+/// `net` column, if present, is ignored since it is derived.
+pub fn parse_csv(body: &str, options: &CsvOptions) -> Result<AppData, AppError> {
+    let mut data = AppData::default();
+    for (index, line) in body.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (index == 0 && line == options.header()) {
+            continue;
+        }
+        let row = index + 1;
+        let fields: Vec<&str> = line.split(options.delimiter).map(str::trim).collect();
+        if fields.len() != 3 && fields.len() != 4 {
+            return Err(AppError::bad_request(format!(
+                "row {row}: expected date, add, sub and optionally net"
+            )));
+        }
+        let date = NaiveDate::parse_from_str(fields[0], &options.date_format).map_err(|_| {
+            AppError::bad_request(format!(
+                "row {row}: date '{}' does not match '{}'",
+                fields[0], options.date_format
+            ))
+        })?;
+        let count = |value: &str| {
+            value
+                .parse::<u64>()
+                .map_err(|_| AppError::bad_request(format!("row {row}: '{value}' is not a count")))
+        };
+        let counts = DayCounts::new(count(fields[1])?, count(fields[2])?);
+        if data.days.insert(date.to_string(), counts).is_some() {
+            return Err(AppError::bad_request(format!(
+                "row {row}: {date} appears more than once"
+            )));
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> AppData {
+        let mut data = AppData::default();
+        data.days
+            .insert("2026-01-05".to_string(), DayCounts::new(4, 1));
+        data.days
+            .insert("2026-01-06".to_string(), DayCounts::new(0, 2));
+        data
+    }
+
+    #[test]
+    fn options_are_validated() {
+        assert_eq!(
+            CsvOptions::parse(None, None).unwrap(),
+            CsvOptions::default()
+        );
+        let local = CsvOptions::parse(Some(";"), Some("%d.%m.%Y")).unwrap();
+        assert_eq!(local.delimiter, ';');
+
+        assert!(CsvOptions::parse(Some(";;"), None).is_err());
+        assert!(CsvOptions::parse(Some("\""), None).is_err());
+        assert!(CsvOptions::parse(None, Some("%Y")).is_err());
+        assert!(CsvOptions::parse(None, Some("%Q")).is_err());
+        assert!(CsvOptions::parse(Some("."), Some("%d.%m.%Y")).is_err());
+    }
+
+    #[test]
+    fn export_and_import_round_trip_with_custom_options() {
+        let options = CsvOptions::parse(Some(";"), Some("%d.%m.%Y")).unwrap();
+        let csv = write_csv(&sample(), &options);
+        assert_eq!(
+            csv,
+            "date;add;sub;net\n05.01.2026;4;1;3\n06.01.2026;0;2;-2\n"
+        );
+
+        let parsed = parse_csv(&csv, &options).unwrap();
+        assert_eq!(parsed.days, sample().days);
+    }
+
+    #[test]
+    fn import_reports_the_offending_row() {
+        let options = CsvOptions::default();
+        let err = parse_csv("date,add,sub,net\n2026-01-05,1,x\n", &options).unwrap_err();
+        assert!(err.message.starts_with("row 2:"));
+
+        let err = parse_csv("2026-01-05,1,0\n2026-01-05,2,0\n", &options).unwrap_err();
+        assert!(err.message.contains("more than once"));
+    }
+}
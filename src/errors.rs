@@ -1,24 +1,61 @@
-use axum::http::StatusCode;
+use crate::chart::escape;
+use axum::{
+    Json,
+    body::Body,
+    extract::Request,
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
 
 #[derive(Debug)]
 pub struct AppError {
     pub status: StatusCode,
+    /// Short machine-readable name for the kind of error, e.g. `bad_request`.
+    pub code: &'static str,
     pub message: String,
 }
 
 impl AppError {
-    pub fn bad_request(message: impl Into<String>) -> Self {
+    fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
         Self {
-            status: StatusCode::BAD_REQUEST,
+            status,
+            code,
             message: message.into(),
         }
     }
 
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "unauthorized", message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, "conflict", message)
+    }
+
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, "unprocessable", message)
+    }
+
+    pub fn too_many_requests(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::TOO_MANY_REQUESTS, "rate_limited", message)
+    }
+
+    pub fn unavailable(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::SERVICE_UNAVAILABLE, "unavailable", message)
+    }
+
     pub fn internal(err: impl std::error::Error) -> Self {
-        Self {
-            status: StatusCode::INTERNAL_SERVER_ERROR,
-            message: err.to_string(),
-        }
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal",
+            err.to_string(),
+        )
     }
 }
 
@@ -28,8 +65,101 @@ impl From<std::io::Error> for AppError {
     }
 }
 
-impl axum::response::IntoResponse for AppError {
-    fn into_response(self) -> axum::response::Response {
-        (self.status, self.message).into_response()
+/// `{"error": {"code", "message"}}`, the body of every error response.
+/// Also attached as an extension so [`negotiate_errors`] can re-render it.
+#[derive(Debug, Clone, Serialize)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ErrorDetail {
+    code: &'static str,
+    message: String,
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            error: ErrorDetail {
+                code: self.code,
+                message: self.message,
+            },
+        };
+        let mut response = (self.status, Json(body.clone())).into_response();
+        response.extensions_mut().insert(body);
+        response
+    }
+}
+
+/// Renders `AppError` responses to match the request's `Accept` header: an
+/// HTML page for browsers and the bare message for clients preferring
+/// `text/plain`, by q-value with JSON winning ties. Everyone else keeps the
+/// JSON body. Only the body and its type change, so headers like
+/// `Retry-After` and `WWW-Authenticate` survive. `IntoResponse` can't see the
+/// request, so this runs as a middleware around the whole router.
+pub async fn negotiate_errors(request: Request, next: Next) -> Response {
+    let accept = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let response = next.run(request).await;
+
+    let Some(ErrorBody { error }) = response.extensions().get::<ErrorBody>().cloned() else {
+        return response;
+    };
+    let status = response.status();
+    let json = quality(&accept, "application/json");
+    let (content_type, body) = if quality(&accept, "text/html") > json {
+        let page = format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head><meta charset=\"UTF-8\" /><title>{status}</title></head>\n\
+             <body><h1>{status}</h1><p>{}</p><p><a href=\"/\">Back to the counter</a></p></body>\n</html>\n",
+            escape(&error.message)
+        );
+        ("text/html; charset=utf-8", page)
+    } else if quality(&accept, "text/plain") > json {
+        ("text/plain; charset=utf-8", error.message)
+    } else {
+        return response;
+    };
+
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// The q-value `accept` gives `media`, from the most specific range that
+/// matches it; 0 when none does.
+fn quality(accept: &str, media: &str) -> f32 {
+    let any_subtype = media
+        .split_once('/')
+        .map(|(kind, _)| format!("{kind}/*"))
+        .unwrap_or_default();
+    let mut best: Option<(u8, f32)> = None;
+    for range in accept.split(',') {
+        let mut params = range.split(';');
+        let name = params.next().unwrap_or_default().trim();
+        let specificity = if name == media {
+            2
+        } else if name == any_subtype {
+            1
+        } else if name == "*/*" {
+            0
+        } else {
+            continue;
+        };
+        let q = params
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if best.is_none_or(|(seen, _)| specificity > seen) {
+            best = Some((specificity, q));
+        }
     }
+    best.map_or(0.0, |(_, q)| q)
 }
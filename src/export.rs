@@ -0,0 +1,104 @@
+//! Full-data downloads in formats other than JSON: `GET /api/export.toml`
+//! with the `toml` feature and `GET /api/export.yaml` with the `yaml`
+//! feature. Both carry the same `AppData` as the data file, stored integers
+//! included, so they can be fed back through `/api/import` after converting.
+
+use crate::errors::AppError;
+use crate::models::AppData;
+use crate::state::AppState;
+use axum::{extract::State, http::header, response::IntoResponse};
+
+#[cfg(feature = "toml")]
+pub fn to_toml(data: &AppData) -> Result<String, AppError> {
+    toml::to_string_pretty(data).map_err(AppError::internal)
+}
+
+#[cfg(feature = "yaml")]
+pub fn to_yaml(data: &AppData) -> Result<String, AppError> {
+    serde_yaml::to_string(data).map_err(AppError::internal)
+}
+
+#[cfg(feature = "toml")]
+pub async fn export_toml(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let body = to_toml(&*state.data.lock().await)?;
+    Ok(attachment("application/toml", "clicks.toml", body))
+}
+
+#[cfg(feature = "yaml")]
+pub async fn export_yaml(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let body = to_yaml(&*state.data.lock().await)?;
+    Ok(attachment("application/yaml", "clicks.yaml", body))
+}
+
+fn attachment(content_type: &'static str, filename: &str, body: String) -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        body,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DayCounts;
+    use std::collections::BTreeMap;
+
+    fn sample() -> AppData {
+        let mut data = AppData::default();
+        data.days
+            .insert("2026-01-05".to_string(), DayCounts::new(3, 1));
+        let mut tagged = DayCounts::new(0, 2);
+        tagged.tags = vec!["travel".to_string()];
+        tagged.actions.insert("jump".to_string(), 4);
+        data.days.insert("2026-01-06".to_string(), tagged);
+        data.week_notes
+            .insert("2026-W02".to_string(), "busy week".to_string());
+        data.last_auto_add = Some("2026-01-06".to_string());
+        data.counters.insert(
+            "coffee".to_string(),
+            BTreeMap::from([("2026-01-05".to_string(), DayCounts::new(2, 0))]),
+        );
+        data.log_seq = 7;
+        data
+    }
+
+    /// Compared through JSON, since `AppData` has no `PartialEq`.
+    fn assert_same(left: &AppData, right: &AppData) {
+        assert_eq!(
+            serde_json::to_value(left).unwrap(),
+            serde_json::to_value(right).unwrap()
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_round_trips() {
+        let data = sample();
+        let text = to_toml(&data).unwrap();
+        assert!(text.contains("[days.2026-01-05]"), "{text}");
+        assert_same(&toml::from_str(&text).unwrap(), &data);
+        assert_same(
+            &toml::from_str(&to_toml(&AppData::default()).unwrap()).unwrap(),
+            &AppData::default(),
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_round_trips() {
+        let data = sample();
+        let text = to_yaml(&data).unwrap();
+        assert!(text.contains("2026-01-05:"), "{text}");
+        assert_same(&serde_yaml::from_str(&text).unwrap(), &data);
+        assert_same(
+            &serde_yaml::from_str(&to_yaml(&AppData::default()).unwrap()).unwrap(),
+            &AppData::default(),
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Series for the Grafana JSON datasource plugin. The plugin asks for named
+//! targets over a time range and expects `[value, timestamp_ms]` pairs back.
+
+use crate::clock::AccountingZone;
+use crate::models::{AppData, GrafanaSeries};
+use crate::stats::CountMetric;
+use chrono::{Duration, NaiveDate};
+
+/// Targets offered to `/grafana/search`.
+pub const TARGETS: [&str; 3] = ["net", "add", "sub"];
+
+/// Upper bound on the number of days a single query may cover.
+pub const MAX_RANGE_DAYS: i64 = 3660;
+
+/// One datapoint per day from `from` to `to` (inclusive) for `target`, with
+/// each day stamped at midnight in `zone`. Days without clicks report zero.
+/// Returns `None` for unknown targets.
+pub fn series(
+    data: &AppData,
+    target: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    zone: AccountingZone,
+) -> Option<GrafanaSeries> {
+    let metric = CountMetric::parse(Some(target)).filter(|_| TARGETS.contains(&target))?;
+
+    let mut datapoints = Vec::new();
+    let mut date = from;
+    while date <= to {
+        let counts = data
+            .days
+            .get(&date.to_string())
+            .cloned()
+            .unwrap_or_default();
+        let value = metric.value(&counts) as f64;
+        if let Some(timestamp) = day_timestamp_ms(date, zone) {
+            datapoints.push((value, timestamp));
+        }
+        date += Duration::days(1);
+    }
+
+    Some(GrafanaSeries {
+        target: target.to_string(),
+        datapoints,
+    })
+}
+
+fn day_timestamp_ms(date: NaiveDate, zone: AccountingZone) -> Option<i64> {
+    zone.resolve(date.and_hms_opt(0, 0, 0)?)
+        .map(|midnight| midnight.timestamp_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DayCounts;
+
+    #[test]
+    fn series_fills_every_day_in_range() {
+        let mut data = AppData::default();
+        data.days
+            .insert("2026-01-06".to_string(), DayCounts::new(5, 2));
+        let from = NaiveDate::from_ymd_opt(2026, 1, 5).unwrap();
+        let to = NaiveDate::from_ymd_opt(2026, 1, 7).unwrap();
+
+        let net = series(&data, "net", from, to, AccountingZone::Local).unwrap();
+        let values: Vec<f64> = net.datapoints.iter().map(|(value, _)| *value).collect();
+        assert_eq!(values, vec![0.0, 3.0, 0.0]);
+        assert!(net.datapoints[0].1 < net.datapoints[1].1);
+
+        let sub = series(&data, "sub", from, to, AccountingZone::Local).unwrap();
+        assert_eq!(sub.datapoints[1].0, 2.0);
+
+        assert!(series(&data, "clicks", from, to, AccountingZone::Local).is_none());
+    }
+}
@@ -1,86 +1,1330 @@
+use crate::chart::{ChartView, chart_points, render_svg};
+use crate::client::client_ip;
+use crate::csv::{CsvOptions, parse_csv, write_csv};
 use crate::errors::AppError;
-use crate::models::{ClickRequest, DailyCountsResponse, DayCounts, StatsResponse};
-use crate::state::AppState;
-use crate::stats::build_stats;
-use crate::storage::persist_data;
-use crate::ui::render_index;
+use crate::grafana;
+use crate::idempotency::{Claim, fingerprint, idempotency_key};
+use crate::import::{ImportMode, apply_import, apply_sync, validate_import};
+use crate::metrics::DataGauges;
+use crate::models::{
+    AppData, ChartQuery, ClickPreviewRequest, ClickQuery, ClickRequest, ClickWithStatsResponse,
+    CompareWeekQuery, CompareWeekResponse, ConfigResponse, CounterQuery, CsvQuery,
+    DailyCountsResponse, DailyPoint, DataStampResponse, DayCounts, DayTagsRequest, DayTagsResponse,
+    DaysQuery, GrafanaQueryRequest, GrafanaSeries, HealthResponse, HeatmapQuery, HistogramQuery,
+    HistogramResponse, HistoryQuery, HistoryResponse, ImportQuery, ImportResponse,
+    MaintenanceStatus, MilestoneQuery, MilestoneResponse, RangeQuery, ResetRequest, ResetResponse,
+    SetDayQuery, SetDayRequest, SetupRequest, SetupResponse, SetupStatusResponse, StatsQuery,
+    StatusResponse, SubConfirmForm, SyncRequest, SyncResponse, TargetRequest, TargetResponse,
+    TodayQuery, WeekNoteRequest, WeekNoteResponse, WeekResponse, WhoamiResponse,
+};
+use crate::setup::{setup_path, to_toml, validate_setup};
+use crate::state::{AppState, LastClick};
+use crate::stats::{
+    CountMetric, HEATMAP_YEARS, StatsOptions, build_stats_at, build_week_at, clicks_per_hour,
+    compare_week_at, current_streak, daily_range, first_recorded_date, heatmap_year, histogram_at,
+    history_page, milestone_at, parse_week_label, week_start,
+};
+use crate::storage::data_digest;
+use crate::ui::{PageInputs, render_page};
+use crate::units::{Scaled, to_steps};
 use axum::{
-    extract::State,
-    response::{Html, Redirect},
-    Json,
+    Form, Json,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, Method, StatusCode, Uri, header},
+    response::{Html, IntoResponse, Redirect, Response},
 };
-use chrono::Local;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::time::{Instant, SystemTime};
 
-pub async fn index(State(state): State<AppState>) -> Html<String> {
-    let date = today_string();
+/// The page carries today's counts, so its `Last-Modified` moves with the
+/// data, the date and the maintenance banner as well as the template.
+pub async fn index(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let date = state.today().to_string();
     let data = state.data.lock().await;
     let counts = data.days.get(&date).cloned().unwrap_or_default();
-    Html(render_index(&date, &counts))
+    let maintenance = state.maintenance();
+    let inputs = PageInputs {
+        version: state.current_version(),
+        date: date.clone(),
+        maintenance: maintenance.message.clone(),
+        template: state.ui_template.modified().await,
+    };
+    let modified = state
+        .page_stamp
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .update(inputs, state.clock.now().into());
+    let last_modified = http_date(modified);
+    if unmodified_since(&headers, modified) {
+        return (
+            StatusCode::NOT_MODIFIED,
+            [(header::LAST_MODIFIED, last_modified)],
+        )
+            .into_response();
+    }
+    let template = state.ui_template.current().await;
+    let page = render_page(
+        &template,
+        &date,
+        &counts,
+        &state.config,
+        maintenance.message.as_deref(),
+    );
+    ([(header::LAST_MODIFIED, last_modified)], Html(page)).into_response()
 }
 
-pub async fn get_today(State(state): State<AppState>) -> Result<Json<DailyCountsResponse>, AppError> {
-    let date = today_string();
+pub async fn get_today(
+    State(state): State<AppState>,
+    Query(query): Query<TodayQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let include_time = match query.now.as_deref().map(str::trim) {
+        None | Some("") | Some("0") | Some("false") => false,
+        Some("1") | Some("true") => true,
+        Some(_) => return Err(AppError::bad_request("now must be 1 or 0")),
+    };
+    let counter = parse_counter(query.counter.as_deref())?;
+    let today = state.today();
+    let date = today.to_string();
     let data = state.data.lock().await;
-    let counts = data.days.get(&date).cloned().unwrap_or_default();
+    // `server_time` differs on every request, so there is nothing to validate.
+    let etag = (!include_time).then(|| state.etag(today));
+    if let Some(etag) = &etag
+        && matches_etag(&headers, etag)
+    {
+        return Ok(not_modified(etag.clone()));
+    }
+    let counts = data
+        .counter(&counter)
+        .and_then(|days| days.get(&date))
+        .cloned()
+        .unwrap_or_default();
+
+    let mut response = today_response(&state, date, counts);
+    if include_time {
+        response.server_time = Some(state.display_now().to_rfc3339());
+    }
+    let response = Scaled(response, state.config.unit_scale());
+    Ok(match etag {
+        Some(etag) => ([(header::ETAG, etag)], response).into_response(),
+        None => response.into_response(),
+    })
+}
+
+/// Any day's counts in the same shape as `/api/today`, zeros if nothing was
+/// recorded. `clicks_per_hour` is always null here.
+pub async fn get_day(
+    State(state): State<AppState>,
+    Path(date): Path<String>,
+    Query(query): Query<CounterQuery>,
+) -> Result<Scaled<DailyCountsResponse>, AppError> {
+    let day = parse_date_param(&date)?;
+    let counter = parse_counter(query.counter.as_deref())?;
+    let date = day.to_string();
+    let data = state.data.lock().await;
+    let counts = data
+        .counter(&counter)
+        .and_then(|days| days.get(&date))
+        .cloned()
+        .unwrap_or_default();
+    Ok(Scaled(
+        to_response(&state, date, counts),
+        state.config.unit_scale(),
+    ))
+}
+
+pub async fn get_stats(
+    State(state): State<AppState>,
+    Query(query): Query<StatsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let today = match query.at.as_deref() {
+        Some(at) => {
+            let instant = DateTime::parse_from_rfc3339(at)
+                .map_err(|_| AppError::bad_request("at must be an RFC 3339 timestamp"))?;
+            state.config.timezone.at(instant.to_utc()).date_naive()
+        }
+        None => state.today(),
+    };
+
+    let mut options = StatsOptions::from_config(&state.config).with_target(state.target());
+    if let Some(weeks) = query.weeks {
+        options = options.with_weeks(weeks);
+    }
+
+    let data = state.data.lock().await;
+    let etag = state.etag(today);
+    if matches_etag(&headers, &etag) {
+        return Ok(not_modified(etag));
+    }
+    let stats = Scaled(
+        build_stats_at(today, &data, &options),
+        state.config.unit_scale(),
+    );
+    Ok(([(header::ETAG, etag)], stats).into_response())
+}
+
+/// Whether `If-None-Match` lists `etag` (or is `*`). Weak comparison, as
+/// RFC 9110 asks for on `GET`.
+fn matches_etag(headers: &HeaderMap, etag: &str) -> bool {
+    let strip_weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || strip_weak(tag) == strip_weak(etag))
+}
+
+fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// Whether `If-Modified-Since` is at or after `modified`. An unparseable
+/// date is ignored, as HTTP asks.
+fn unmodified_since(headers: &HeaderMap, modified: SystemTime) -> bool {
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value.trim()).ok())
+        .is_some_and(|since| SystemTime::from(since) >= modified)
+}
+
+fn not_modified(etag: String) -> Response {
+    (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response()
+}
+
+/// Liveness and readiness probe. Never touches the data lock or the disk, so
+/// it answers even while a slow write is in flight.
+pub async fn healthz() -> Json<HealthResponse> {
+    Json(HealthResponse { status: "ok" })
+}
+
+pub async fn grafana_health() -> &'static str {
+    "OK"
+}
+
+pub async fn grafana_search() -> Json<Vec<&'static str>> {
+    Json(grafana::TARGETS.to_vec())
+}
+
+pub async fn grafana_query(
+    State(state): State<AppState>,
+    Json(payload): Json<GrafanaQueryRequest>,
+) -> Result<Json<Vec<GrafanaSeries>>, AppError> {
+    let parse = |value: &str| {
+        DateTime::parse_from_rfc3339(value)
+            .map(|instant| state.config.timezone.at(instant.to_utc()).date_naive())
+            .map_err(|_| AppError::bad_request("range must use RFC 3339 timestamps"))
+    };
+    let from = parse(&payload.range.from)?;
+    let to = parse(&payload.range.to)?;
+    if to < from {
+        return Err(AppError::bad_request(
+            "range.to must not be before range.from",
+        ));
+    }
+    if (to - from).num_days() > grafana::MAX_RANGE_DAYS {
+        return Err(AppError::bad_request(format!(
+            "range may cover at most {} days",
+            grafana::MAX_RANGE_DAYS
+        )));
+    }
+
+    let data = state.data.lock().await;
+    let mut series = Vec::with_capacity(payload.targets.len());
+    for target in &payload.targets {
+        match grafana::series(&data, &target.target, from, to, state.config.timezone) {
+            Some(mut points) => {
+                let scale = state.config.unit_scale() as f64;
+                for (value, _) in &mut points.datapoints {
+                    *value /= scale;
+                }
+                series.push(points)
+            }
+            None => {
+                return Err(AppError::bad_request(format!(
+                    "unknown target '{}', expected one of: {}",
+                    target.target,
+                    grafana::TARGETS.join(", ")
+                )));
+            }
+        }
+    }
+    Ok(Json(series))
+}
+
+pub async fn get_chart_svg(
+    State(state): State<AppState>,
+    Query(query): Query<ChartQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let view = ChartView::parse(query.view.as_deref())
+        .ok_or_else(|| AppError::bad_request("view must be 'daily', 'weekly' or 'average'"))?;
+
+    let stats = {
+        let data = state.data.lock().await;
+        build_stats_at(
+            state.today(),
+            &data,
+            &StatsOptions::from_config(&state.config),
+        )
+    };
+    let scale = state.config.unit_scale() as f64;
+    let mut points = chart_points(&stats, view);
+    for point in &mut points {
+        point.value /= scale;
+    }
+    let svg = render_svg(view.title(), &points);
+
+    Ok(([(header::CONTENT_TYPE, "image/svg+xml")], svg))
+}
+
+pub async fn milestone(
+    State(state): State<AppState>,
+    Query(query): Query<MilestoneQuery>,
+) -> Result<Scaled<MilestoneResponse>, AppError> {
+    let metric = CountMetric::parse(query.metric.as_deref())
+        .ok_or_else(|| AppError::bad_request("metric must be 'add', 'sub' or 'net'"))?;
+    let scale = state.config.unit_scale();
+    let target = query
+        .target
+        .and_then(|target| to_steps(target, scale))
+        .and_then(|steps| i64::try_from(steps).ok())
+        .ok_or_else(|| AppError::bad_request("target must be a positive count"))?;
+
+    let today = state.today();
+    let data = state.data.lock().await;
+    Ok(Scaled(milestone_at(today, &data, metric, target), scale))
+}
+
+pub async fn histogram(
+    State(state): State<AppState>,
+    Query(query): Query<HistogramQuery>,
+) -> Result<Scaled<HistogramResponse>, AppError> {
+    const MAX_BINS: usize = 100;
+
+    let bins = query.bins.unwrap_or(10);
+    if !(1..=MAX_BINS).contains(&bins) {
+        return Err(AppError::bad_request(format!(
+            "bins must be between 1 and {MAX_BINS}"
+        )));
+    }
+    let metric = match query.metric.as_deref().map(str::trim) {
+        None | Some("") => Some(CountMetric::Net),
+        other => CountMetric::parse(other),
+    }
+    .ok_or_else(|| AppError::bad_request("metric must be 'add', 'sub' or 'net'"))?;
+    let from = query.from.as_deref().map(parse_date_param).transpose()?;
+    let to = query.to.as_deref().map(parse_date_param).transpose()?;
+    if let (Some(from), Some(to)) = (from, to)
+        && from > to
+    {
+        return Err(AppError::bad_request("from must not be after to"));
+    }
+
+    let data = state.data.lock().await;
+    Ok(Scaled(
+        histogram_at(&data, metric, from, to, bins),
+        state.config.unit_scale(),
+    ))
+}
+
+pub async fn get_range(
+    State(state): State<AppState>,
+    Query(query): Query<RangeQuery>,
+) -> Result<Scaled<Vec<DailyPoint>>, AppError> {
+    const MAX_RANGE_DAYS: i64 = 366;
 
-    Ok(Json(to_response(date, counts)))
+    let from = parse_date_param(&query.from)?;
+    let to = parse_date_param(&query.to)?;
+    if from > to {
+        return Err(AppError::bad_request("from must not be after to"));
+    }
+    if (to - from).num_days() >= MAX_RANGE_DAYS {
+        return Err(AppError::bad_request(format!(
+            "range must span at most {MAX_RANGE_DAYS} days"
+        )));
+    }
+
+    let data = state.data.lock().await;
+    Ok(Scaled(
+        daily_range(&data, from, to),
+        state.config.unit_scale(),
+    ))
+}
+
+/// Dates that have an entry, oldest first, for date pickers.
+pub async fn list_days(
+    State(state): State<AppState>,
+    Query(query): Query<DaysQuery>,
+) -> Result<Json<Vec<String>>, AppError> {
+    let from = query.from.as_deref().map(parse_date_param).transpose()?;
+    let to = query.to.as_deref().map(parse_date_param).transpose()?;
+    let counter = parse_counter(query.counter.as_deref())?;
+    if let (Some(from), Some(to)) = (from, to)
+        && from > to
+    {
+        return Err(AppError::bad_request("from must not be after to"));
+    }
+
+    let data = state.data.lock().await;
+    let days = data
+        .counter(&counter)
+        .into_iter()
+        .flat_map(|days| days.keys())
+        .filter(|date| {
+            let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+                return false;
+            };
+            from.is_none_or(|from| date >= from) && to.is_none_or(|to| date <= to)
+        })
+        .cloned()
+        .collect();
+    Ok(Json(days))
+}
+
+pub async fn get_history(
+    State(state): State<AppState>,
+    Query(query): Query<HistoryQuery>,
+) -> Scaled<HistoryResponse> {
+    let data = state.data.lock().await;
+    let page = history_page(&data, query.offset.unwrap_or(0), query.limit);
+    Scaled(page, state.config.unit_scale())
+}
+
+pub async fn get_heatmap(
+    State(state): State<AppState>,
+    Query(query): Query<HeatmapQuery>,
+) -> Result<Scaled<Vec<DailyPoint>>, AppError> {
+    let year = query.year.unwrap_or_else(|| state.today().year());
+    let data = state.data.lock().await;
+    let points = heatmap_year(&data, year).ok_or_else(|| {
+        AppError::bad_request(format!(
+            "year must be between {} and {}",
+            HEATMAP_YEARS.start(),
+            HEATMAP_YEARS.end()
+        ))
+    })?;
+    Ok(Scaled(points, state.config.unit_scale()))
+}
+
+pub async fn get_week(State(state): State<AppState>) -> Scaled<WeekResponse> {
+    let today = state.today();
+    let data = state.data.lock().await;
+    let start = week_start(today, state.config.week_start);
+    Scaled(
+        build_week_at(today, start, &data),
+        state.config.unit_scale(),
+    )
+}
+
+pub async fn get_week_by_label(
+    State(state): State<AppState>,
+    Path(label): Path<String>,
+) -> Result<Scaled<WeekResponse>, AppError> {
+    let start = parse_week_label(&label, state.config.week_start).ok_or_else(|| {
+        AppError::bad_request("week label must look like YYYY-Www (e.g. 2026-W02)")
+    })?;
+    let today = state.today();
+    let data = state.data.lock().await;
+    Ok(Scaled(
+        build_week_at(today, start, &data),
+        state.config.unit_scale(),
+    ))
+}
+
+pub async fn get_datastamp(
+    State(state): State<AppState>,
+) -> Result<Json<DataStampResponse>, AppError> {
+    let (sha256, version) = {
+        let data = state.data.lock().await;
+        (data_digest(&data)?, state.current_version())
+    };
+    let modified = tokio::fs::metadata(state.storage.path())
+        .await
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(|time| DateTime::<Utc>::from(time).to_rfc3339());
+
+    Ok(Json(DataStampResponse {
+        modified,
+        sha256,
+        version,
+    }))
+}
+
+pub async fn compare_week(
+    State(state): State<AppState>,
+    Query(query): Query<CompareWeekQuery>,
+) -> Result<Scaled<CompareWeekResponse>, AppError> {
+    const MAX_OFFSET: u32 = 52;
+
+    let offset = query.offset.unwrap_or(1);
+    if !(1..=MAX_OFFSET).contains(&offset) {
+        return Err(AppError::bad_request(format!(
+            "offset must be between 1 and {MAX_OFFSET}"
+        )));
+    }
+
+    let today = state.today();
+    let data = state.data.lock().await;
+    let week_start_day = state.config.week_start;
+    let compared_end =
+        week_start(today, week_start_day) - Duration::weeks(i64::from(offset)) + Duration::days(6);
+    match first_recorded_date(&data) {
+        Some(first) if first <= compared_end => {}
+        _ => {
+            return Err(AppError::bad_request(
+                "offset reaches further back than the recorded history",
+            ));
+        }
+    }
+
+    Ok(Scaled(
+        compare_week_at(today, &data, offset, week_start_day),
+        state.config.unit_scale(),
+    ))
+}
+
+pub async fn get_config(State(state): State<AppState>) -> Json<ConfigResponse> {
+    let maintenance = state.maintenance();
+    Json(ConfigResponse {
+        add_label: state.config.add_label().to_string(),
+        sub_label: state.config.sub_label().to_string(),
+        confirm_sub: state.config.confirm_sub,
+        unit_scale: state.config.unit_scale(),
+        count_mode: state.config.count_mode,
+        week_start: state.config.week_start,
+        accounting_timezone: state.config.timezone.name(),
+        accounting_utc_offset: state.now().offset().to_string(),
+        display_utc_offset: state.display_now().offset().to_string(),
+        title: state.config.title().to_string(),
+        daily_goal: state.config.daily_goal,
+        maintenance_message: maintenance.message,
+        maintenance_read_only: maintenance.read_only,
+    })
+}
+
+pub async fn get_target(State(state): State<AppState>) -> Scaled<TargetResponse> {
+    Scaled(
+        TargetResponse {
+            target: state.target(),
+        },
+        state.config.unit_scale(),
+    )
+}
+
+/// Sets the daily net target until the next restart, which goes back to
+/// `APP_DAILY_TARGET` (or the setup's daily goal).
+pub async fn set_target(
+    State(state): State<AppState>,
+    Json(payload): Json<TargetRequest>,
+) -> Result<Scaled<TargetResponse>, AppError> {
+    let scale = state.config.unit_scale();
+    let target = payload
+        .target
+        .map(|target| {
+            target
+                .checked_mul(scale)
+                .ok_or_else(|| AppError::bad_request("target is too large"))
+        })
+        .transpose()?;
+    state.set_target(target);
+    Ok(Scaled(TargetResponse { target }, scale))
+}
+
+pub async fn set_maintenance(
+    State(state): State<AppState>,
+    Json(payload): Json<MaintenanceStatus>,
+) -> Json<MaintenanceStatus> {
+    Json(state.set_maintenance(payload))
+}
+
+pub async fn whoami(
+    State(state): State<AppState>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Json<WhoamiResponse> {
+    let peer = peer.map(|ConnectInfo(addr)| addr);
+    Json(WhoamiResponse {
+        client_ip: client_ip(&headers, peer, state.config.trust_proxy).map(|ip| ip.to_string()),
+        user_agent: headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        timezone: state.config.timezone.name(),
+        utc_offset: state.now().offset().to_string(),
+        today: state.today().to_string(),
+        day_offset_hours: state.config.day_offset_hours,
+    })
+}
+
+pub async fn setup_status(State(state): State<AppState>) -> Json<SetupStatusResponse> {
+    let is_fresh =
+        state.fresh_install.load(Ordering::SeqCst) && state.data.lock().await.days.is_empty();
+    Json(SetupStatusResponse { is_fresh })
 }
 
-pub async fn get_stats(State(state): State<AppState>) -> Result<Json<StatsResponse>, AppError> {
+/// Writes `config.toml` once on a fresh install. The settings apply from the
+/// next start, since the running configuration is fixed.
+pub async fn setup(
+    State(state): State<AppState>,
+    Json(payload): Json<SetupRequest>,
+) -> Result<Json<SetupResponse>, AppError> {
+    state.ensure_writable()?;
+    validate_setup(&payload)?;
+    // Holding the data lock keeps concurrent setup requests from racing.
     let data = state.data.lock().await;
-    Ok(Json(build_stats(&data)))
+    if !state.fresh_install.load(Ordering::SeqCst) || !data.days.is_empty() {
+        return Err(AppError::conflict("setup only runs on a fresh install"));
+    }
+
+    let path = setup_path(state.storage.path());
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(&path, to_toml(&payload)).await?;
+    state.fresh_install.store(false, Ordering::SeqCst);
+
+    Ok(Json(SetupResponse {
+        config_path: path.display().to_string(),
+        restart_required: true,
+    }))
+}
+
+pub async fn openapi() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        crate::openapi::DOCUMENT,
+    )
+}
+
+pub async fn get_status(State(state): State<AppState>) -> Json<StatusResponse> {
+    Json(StatusResponse {
+        persist_latency: state.metrics.persist_latency.summary(),
+        load_warnings: state.load_warnings.as_ref().clone(),
+    })
 }
 
+pub async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let today = state.today();
+    let gauges = {
+        let start = week_start(today, state.config.week_start);
+        let data = state.data.lock().await;
+        let counts = data
+            .days
+            .get(&today.to_string())
+            .cloned()
+            .unwrap_or_default();
+        DataGauges {
+            today_add: counts.add,
+            today_sub: counts.sub,
+            today_net: counts.net(),
+            week_net: build_week_at(today, start, &data).net,
+            current_streak: current_streak(today, &data, &StatsOptions::from_config(&state.config)),
+        }
+    };
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render_prometheus(&gauges),
+    )
+}
+
+/// With an `Idempotency-Key` header, a repeat of an earlier key gets that
+/// click's response back instead of being counted again.
 pub async fn click(
     State(state): State<AppState>,
+    Query(query): Query<ClickQuery>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
     Json(payload): Json<ClickRequest>,
-) -> Result<Json<DailyCountsResponse>, AppError> {
-    let action = payload.action.trim();
-    if action != "add" && action != "sub" {
-        return Err(AppError::bad_request("action must be 'add' or 'sub'"));
+) -> Result<Response, AppError> {
+    let counter = parse_counter(query.counter.as_deref())?;
+    let include_stats = match query.include.as_deref().map(str::trim) {
+        None | Some("") => false,
+        Some("stats") => true,
+        Some(_) => return Err(AppError::bad_request("include must be stats")),
+    };
+    let dry_run = match query.dry_run.as_deref().map(str::trim) {
+        None | Some("") | Some("0") | Some("false") => false,
+        Some("1") | Some("true") => true,
+        Some(_) => return Err(AppError::bad_request("dry_run must be true or false")),
+    };
+    let action = parse_action(&payload.action)?;
+    if action == "sub" && state.config.confirm_sub && !payload.confirm {
+        return Err(AppError::bad_request("subtract requires confirm:true"));
+    }
+    let scale = state.config.unit_scale();
+    let steps = parse_amount(payload.amount, scale)?;
+    if dry_run {
+        return Ok(dry_run_click(&state, &counter, action, steps, include_stats).await);
     }
 
-    let response = apply_click(&state, action).await?;
-    Ok(Json(response))
+    let response = match idempotency_key(&headers)? {
+        None => apply_click_on(&state, &counter, state.today(), action, steps).await?,
+        Some(key) => {
+            // `uri` is relative to `/api` or `/api/v1`, so a retry may switch
+            // between them.
+            let request = fingerprint(&(
+                method.as_str(),
+                uri.to_string(),
+                &payload.action,
+                payload.amount.map(f64::to_bits),
+                payload.confirm,
+            ));
+            loop {
+                let claim = state
+                    .idempotency
+                    .lock()
+                    .await
+                    .claim(&key, request, Instant::now())?;
+                match claim {
+                    Claim::Replay(response) => break response,
+                    Claim::Wait(mut done) => {
+                        let _ = done.changed().await;
+                    }
+                    Claim::Run(reservation) => {
+                        let result =
+                            apply_click_on(&state, &counter, state.today(), action, steps).await;
+                        state.idempotency.lock().await.finish(
+                            reservation,
+                            result.as_ref().ok().cloned(),
+                            Instant::now(),
+                        );
+                        break result?;
+                    }
+                }
+            }
+        }
+    };
+    if !include_stats {
+        return Ok(Scaled(response, scale).into_response());
+    }
+
+    let options = StatsOptions::from_config(&state.config).with_target(state.target());
+    let stats = {
+        let data = state.data.lock().await;
+        build_stats_at(state.today(), &data, &options)
+    };
+    let body = ClickWithStatsResponse {
+        today: response,
+        stats,
+    };
+    Ok(Scaled(body, scale).into_response())
+}
+
+/// What a click would do, for `?dry_run=true`: today's counts after it, and
+/// with `include_stats` the stats after it too. Works on copies, so nothing is
+/// counted, persisted or broadcast, and no `Idempotency-Key` is recorded.
+async fn dry_run_click(
+    state: &AppState,
+    counter: &str,
+    action: &str,
+    steps: u64,
+    include_stats: bool,
+) -> Response {
+    let today = state.today();
+    let date = today.to_string();
+    let scale = state.config.unit_scale();
+    let data = state.data.lock().await;
+    let mut counts = data
+        .counter(counter)
+        .and_then(|days| days.get(&date))
+        .cloned()
+        .unwrap_or_default();
+    counts.click(state.config.count_mode, action, steps);
+    let response = DailyCountsResponse {
+        dry_run: true,
+        ..today_response(state, date.clone(), counts.clone())
+    };
+    if !include_stats {
+        return Scaled(response, scale).into_response();
+    }
+
+    let mut projected = data.clone();
+    drop(data);
+    projected.counter_mut(counter).insert(date, counts);
+    let options = StatsOptions::from_config(&state.config).with_target(state.target());
+    let body = ClickWithStatsResponse {
+        today: response,
+        stats: build_stats_at(today, &projected, &options),
+    };
+    Scaled(body, scale).into_response()
+}
+
+/// Reverses the most recent click, whatever day it was for. A second undo
+/// has nothing left to reverse and returns `409`.
+pub async fn undo(State(state): State<AppState>) -> Result<Scaled<DailyCountsResponse>, AppError> {
+    state.ensure_writable()?;
+    let mut data = state.data.lock().await;
+    let Some(click) = state.swap_last_click(None) else {
+        return Err(AppError::conflict("nothing to undo"));
+    };
+
+    let date = click.date.to_string();
+    let days = data.counter_mut(&click.counter);
+    let previous = days.get(&date).cloned();
+    let updated = {
+        let entry = days.entry(date.clone()).or_default();
+        entry.unclick(state.config.count_mode, &click.action, click.steps);
+        entry.clone()
+    };
+
+    if let Err(err) = state.persist(&data).await {
+        let days = data.counter_mut(&click.counter);
+        match previous {
+            Some(counts) => days.insert(date, counts),
+            None => days.remove(&date),
+        };
+        state.swap_last_click(Some(click));
+        return Err(err);
+    }
+
+    let response = if click.date == state.today() {
+        today_response(&state, date, updated)
+    } else {
+        to_response(&state, date, updated)
+    };
+    Ok(Scaled(response, state.config.unit_scale()))
+}
+
+/// Backfills a click on a past day (or today), e.g. one forgotten yesterday.
+pub async fn click_on(
+    State(state): State<AppState>,
+    Path(date): Path<String>,
+    Query(query): Query<CounterQuery>,
+    Json(payload): Json<ClickRequest>,
+) -> Result<Scaled<DailyCountsResponse>, AppError> {
+    let counter = parse_counter(query.counter.as_deref())?;
+    let date = parse_date_param(&date)?;
+    if date > state.today() {
+        return Err(AppError::bad_request("date must not be in the future"));
+    }
+    let action = parse_action(&payload.action)?;
+    if action == "sub" && state.config.confirm_sub && !payload.confirm {
+        return Err(AppError::bad_request("subtract requires confirm:true"));
+    }
+    let scale = state.config.unit_scale();
+    let steps = parse_amount(payload.amount, scale)?;
+
+    let response = apply_click_on(&state, &counter, date, action, steps).await?;
+    Ok(Scaled(response, scale))
+}
+
+/// Computes what `/api/click` would return without changing or persisting
+/// anything.
+/// Applies the same checks as `POST /api/click`, so a preview never shows a
+/// result the real click would refuse.
+pub async fn preview_click(
+    State(state): State<AppState>,
+    Query(query): Query<CounterQuery>,
+    Json(payload): Json<ClickPreviewRequest>,
+) -> Result<Scaled<DailyCountsResponse>, AppError> {
+    let counter = parse_counter(query.counter.as_deref())?;
+    let action = parse_action(&payload.action)?;
+    if action == "sub" && state.config.confirm_sub && !payload.confirm {
+        return Err(AppError::bad_request("subtract requires confirm:true"));
+    }
+    let scale = state.config.unit_scale();
+    let steps = parse_amount(payload.amount, scale)?;
+    let today = state.today();
+    let date = match payload.date.as_deref() {
+        Some(value) => parse_date_param(value)?,
+        None => today,
+    };
+
+    let mut counts = {
+        let data = state.data.lock().await;
+        data.counter(&counter)
+            .and_then(|days| days.get(&date.to_string()))
+            .cloned()
+            .unwrap_or_default()
+    };
+    counts.click(state.config.count_mode, action, steps);
+
+    let response = if date == today {
+        today_response(&state, date.to_string(), counts)
+    } else {
+        to_response(&state, date.to_string(), counts)
+    };
+    Ok(Scaled(response, scale))
 }
 
 pub async fn click_add(State(state): State<AppState>) -> Result<Redirect, AppError> {
-    apply_click(&state, "add").await?;
+    apply_click(&state, "add", state.config.unit_scale()).await?;
     Ok(Redirect::to("/"))
 }
 
-pub async fn click_sub(State(state): State<AppState>) -> Result<Redirect, AppError> {
-    apply_click(&state, "sub").await?;
+pub async fn click_sub(
+    State(state): State<AppState>,
+    form: Option<Form<SubConfirmForm>>,
+) -> Result<Redirect, AppError> {
+    let confirmed = form.is_some_and(|Form(form)| form.confirm.as_deref() == Some("true"));
+    if state.config.confirm_sub && !confirmed {
+        return Err(AppError::bad_request("subtract requires confirmation"));
+    }
+    apply_click(&state, "sub", state.config.unit_scale()).await?;
     Ok(Redirect::to("/"))
 }
 
-async fn apply_click(state: &AppState, action: &str) -> Result<DailyCountsResponse, AppError> {
-    let date = today_string();
+pub async fn import(
+    State(state): State<AppState>,
+    Query(query): Query<ImportQuery>,
+    Json(payload): Json<AppData>,
+) -> Result<Json<ImportResponse>, AppError> {
+    state.ensure_writable()?;
+    let mode = ImportMode::parse(query.mode.as_deref())?;
+    validate_import(&payload)?;
+
+    let days_imported = payload.days.len();
     let mut data = state.data.lock().await;
-    let updated = {
-        let entry = data.days.entry(date.clone()).or_default();
-        if action == "add" {
-            entry.add = entry.add.saturating_add(1);
-        } else {
-            entry.sub = entry.sub.saturating_add(1);
+    let previous = data.clone();
+    state.storage.back_up().await;
+    apply_import(&mut data, payload, mode);
+    if let Err(err) = state.persist(&data).await {
+        *data = previous;
+        return Err(err);
+    }
+
+    Ok(Json(ImportResponse {
+        mode: mode.as_str(),
+        days_imported,
+        total_days: data.days.len(),
+    }))
+}
+
+pub async fn export_csv(
+    State(state): State<AppState>,
+    Query(query): Query<CsvQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let options = CsvOptions::parse(query.delimiter.as_deref(), query.date_format.as_deref())?;
+    let body = {
+        let data = state.data.lock().await;
+        write_csv(&data, &options)
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"clicks.csv\"",
+            ),
+        ],
+        body,
+    ))
+}
+
+pub async fn import_csv(
+    State(state): State<AppState>,
+    Query(query): Query<CsvQuery>,
+    Query(import_query): Query<ImportQuery>,
+    body: String,
+) -> Result<Json<ImportResponse>, AppError> {
+    state.ensure_writable()?;
+    let options = CsvOptions::parse(query.delimiter.as_deref(), query.date_format.as_deref())?;
+    let mode = ImportMode::parse(import_query.mode.as_deref())?;
+    let payload = parse_csv(&body, &options)?;
+
+    let days_imported = payload.days.len();
+    let mut data = state.data.lock().await;
+    let previous = data.clone();
+    state.storage.back_up().await;
+    apply_import(&mut data, payload, mode);
+    if let Err(err) = state.persist(&data).await {
+        *data = previous;
+        return Err(err);
+    }
+
+    Ok(Json(ImportResponse {
+        mode: mode.as_str(),
+        days_imported,
+        total_days: data.days.len(),
+    }))
+}
+
+pub async fn sync(
+    State(state): State<AppState>,
+    Json(payload): Json<SyncRequest>,
+) -> Result<Json<SyncResponse>, AppError> {
+    state.ensure_writable()?;
+    validate_import(&payload.data)?;
+
+    let mut data = state.data.lock().await;
+    let previous = data.clone();
+    let days_changed = apply_sync(&mut data, payload.data);
+    if days_changed > 0 {
+        state.storage.back_up().await;
+        if let Err(err) = state.persist(&data).await {
+            *data = previous;
+            return Err(err);
         }
-        entry.clone()
+    }
+
+    Ok(Json(SyncResponse {
+        data: data.clone(),
+        version: state.current_version(),
+        peer_version: payload.version,
+        days_changed,
+    }))
+}
+
+pub async fn set_day_tags(
+    State(state): State<AppState>,
+    Path(date): Path<String>,
+    Json(payload): Json<DayTagsRequest>,
+) -> Result<Json<DayTagsResponse>, AppError> {
+    const MAX_TAGS: usize = 5;
+    const MAX_TAG_CHARS: usize = 16;
+
+    state.ensure_writable()?;
+    let date = parse_date_param(&date)?.to_string();
+    let mut tags: Vec<String> = Vec::with_capacity(payload.tags.len());
+    for tag in payload.tags {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return Err(AppError::bad_request("tags must not be empty"));
+        }
+        if tag.chars().count() > MAX_TAG_CHARS {
+            return Err(AppError::bad_request(format!(
+                "tags must be at most {MAX_TAG_CHARS} characters"
+            )));
+        }
+        if !tags.iter().any(|existing| existing == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    if tags.len() > MAX_TAGS {
+        return Err(AppError::bad_request(format!(
+            "at most {MAX_TAGS} tags per day"
+        )));
+    }
+
+    let mut data = state.data.lock().await;
+    let previous = data.days.get(&date).cloned();
+    data.days.entry(date.clone()).or_default().tags = tags.clone();
+    if let Err(err) = state.persist(&data).await {
+        match previous {
+            Some(previous) => data.days.insert(date, previous),
+            None => data.days.remove(&date),
+        };
+        return Err(err);
+    }
+
+    Ok(Json(DayTagsResponse { date, tags }))
+}
+
+/// Removes a day's counts and tags. Deleting a day with no entry is a no-op.
+pub async fn delete_day(
+    State(state): State<AppState>,
+    Path(date): Path<String>,
+) -> Result<Scaled<DailyCountsResponse>, AppError> {
+    state.ensure_writable()?;
+    let date = parse_date_param(&date)?.to_string();
+
+    let mut data = state.data.lock().await;
+    if let Some(previous) = data.days.remove(&date)
+        && let Err(err) = state.persist(&data).await
+    {
+        data.days.insert(date, previous);
+        return Err(err);
+    }
+
+    Ok(Scaled(
+        to_response(&state, date, DayCounts::default()),
+        state.config.unit_scale(),
+    ))
+}
+
+/// Wipes every counter's days and the week notes. Only the auto-add marker
+/// and the event log position survive, so neither is replayed afterwards.
+pub async fn reset_all(
+    State(state): State<AppState>,
+    Json(payload): Json<ResetRequest>,
+) -> Result<Json<ResetResponse>, AppError> {
+    state.ensure_writable()?;
+    if payload.confirm != "RESET" {
+        return Err(AppError::bad_request(
+            "send {\"confirm\": \"RESET\"} to delete all data",
+        ));
+    }
+
+    let mut data = state.data.lock().await;
+    state.storage.back_up().await;
+    let empty = AppData {
+        last_auto_add: data.last_auto_add.clone(),
+        log_seq: data.log_seq,
+        ..AppData::default()
+    };
+    let previous = std::mem::replace(&mut *data, empty);
+    if let Err(err) = state.persist(&data).await {
+        *data = previous;
+        return Err(err);
+    }
+    state.swap_last_click(None);
+
+    let days_removed =
+        previous.days.len() + previous.counters.values().map(BTreeMap::len).sum::<usize>();
+    Ok(Json(ResetResponse { days_removed }))
+}
+
+/// Overwrites a day's counts (and drops its tags) with exact totals.
+pub async fn set_day(
+    State(state): State<AppState>,
+    Path(date): Path<String>,
+    Query(query): Query<SetDayQuery>,
+    Json(payload): Json<SetDayRequest>,
+) -> Result<Scaled<DailyCountsResponse>, AppError> {
+    state.ensure_writable()?;
+    let day = parse_date_param(&date)?;
+    let today = state.today();
+    if day > today && !query.allow_future.unwrap_or(false) {
+        return Err(AppError::bad_request(
+            "date must not be in the future (pass allow_future=true to override)",
+        ));
+    }
+    let scale = state.config.unit_scale();
+    let to_steps = |value: u64| {
+        value
+            .checked_mul(scale)
+            .ok_or_else(|| AppError::bad_request("add and sub are too large"))
     };
+    let counts = DayCounts::new(to_steps(payload.add)?, to_steps(payload.sub)?);
 
-    persist_data(&state.data_path, &data).await?;
+    let date = day.to_string();
+    let mut data = state.data.lock().await;
+    let previous = data.days.insert(date.clone(), counts.clone());
+    if let Err(err) = state.persist(&data).await {
+        match previous {
+            Some(previous) => data.days.insert(date, previous),
+            None => data.days.remove(&date),
+        };
+        return Err(err);
+    }
 
-    Ok(to_response(date, updated))
+    let response = if day == today {
+        today_response(&state, date, counts)
+    } else {
+        to_response(&state, date, counts)
+    };
+    Ok(Scaled(response, scale))
 }
 
-fn to_response(date: String, counts: DayCounts) -> DailyCountsResponse {
+pub async fn put_week_note(
+    State(state): State<AppState>,
+    Path(label): Path<String>,
+    Json(payload): Json<WeekNoteRequest>,
+) -> Result<Json<WeekNoteResponse>, AppError> {
+    const MAX_NOTE_CHARS: usize = 2000;
+
+    state.ensure_writable()?;
+    if parse_week_label(&label, state.config.week_start).is_none() {
+        return Err(AppError::bad_request(
+            "week label must look like YYYY-Www (e.g. 2026-W02)",
+        ));
+    }
+    let note = payload.note.trim();
+    if note.chars().count() > MAX_NOTE_CHARS {
+        return Err(AppError::bad_request(format!(
+            "note must be at most {MAX_NOTE_CHARS} characters"
+        )));
+    }
+
+    let mut data = state.data.lock().await;
+    let previous = if note.is_empty() {
+        data.week_notes.remove(&label)
+    } else {
+        data.week_notes.insert(label.clone(), note.to_string())
+    };
+    if let Err(err) = state.persist(&data).await {
+        match previous {
+            Some(previous) => data.week_notes.insert(label, previous),
+            None => data.week_notes.remove(&label),
+        };
+        return Err(err);
+    }
+
+    Ok(Json(WeekNoteResponse {
+        note: data.week_notes.get(&label).cloned(),
+        week: label,
+    }))
+}
+
+async fn apply_click(
+    state: &AppState,
+    action: &str,
+    steps: u64,
+) -> Result<DailyCountsResponse, AppError> {
+    apply_click_on(
+        state,
+        AppData::DEFAULT_COUNTER,
+        state.today(),
+        action,
+        steps,
+    )
+    .await
+}
+
+async fn apply_click_on(
+    state: &AppState,
+    counter: &str,
+    day: NaiveDate,
+    action: &str,
+    steps: u64,
+) -> Result<DailyCountsResponse, AppError> {
+    state.ensure_writable()?;
+    let date = day.to_string();
+    let mut data = state.data.lock().await;
+    let is_new_counter = data.counter(counter).is_none();
+    let days = data.counter_mut(counter);
+    let is_new_day = !days.contains_key(&date);
+    let (steps, updated) = {
+        let entry = days.entry(date.clone()).or_default();
+        let steps = entry.click(state.config.count_mode, action, steps);
+        (steps, entry.clone())
+    };
+
+    // Releases the lock before writing, so other clicks can land meanwhile.
+    let revision = match state.persist_click(data, counter, day, action, steps).await {
+        Ok(revision) => revision,
+        Err(err) => {
+            // Take the click back out so memory doesn't run ahead of the data
+            // file. Other clicks may have changed the day since, so reverse
+            // this one rather than restoring what was there before.
+            let mut data = state.data.lock().await;
+            let days = data.counter_mut(counter);
+            if let Some(entry) = days.get_mut(&date) {
+                entry.unclick(state.config.count_mode, action, steps);
+                if is_new_day && *entry == DayCounts::default() {
+                    days.remove(&date);
+                }
+            }
+            if is_new_counter && days.is_empty() {
+                data.counters.remove(counter);
+            }
+            state.click_rolled_back(&data).await;
+            return Err(err);
+        }
+    };
+    state.metrics.record_click(action, steps);
+    state.remember_click(
+        LastClick {
+            counter: counter.to_string(),
+            date: day,
+            action: action.to_string(),
+            steps,
+        },
+        revision,
+    );
+
+    if day == state.today() {
+        let response = today_response(state, date, updated);
+        if counter == AppData::DEFAULT_COUNTER {
+            // Nobody listening is not an error.
+            let _ = state.updates.send(response.clone());
+        }
+        Ok(response)
+    } else {
+        Ok(to_response(state, date, updated))
+    }
+}
+
+/// Longest accepted custom action name.
+const MAX_ACTION_LEN: usize = 32;
+
+/// Accepts `add`, `sub`, or a custom action name made of lowercase letters,
+/// digits, `-` and `_`, which is counted in its own bucket.
+fn parse_action(value: &str) -> Result<&str, AppError> {
+    let action = value.trim();
+    let valid = !action.is_empty()
+        && action.len() <= MAX_ACTION_LEN
+        && action
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_');
+    if valid {
+        Ok(action)
+    } else {
+        Err(AppError::bad_request(format!(
+            "action must be 'add', 'sub' or a name of up to {MAX_ACTION_LEN} lowercase letters, digits, '-' or '_'"
+        )))
+    }
+}
+
+/// Converts a click amount into stored steps; no amount means one unit.
+fn parse_amount(amount: Option<f64>, scale: u64) -> Result<u64, AppError> {
+    match amount {
+        None => Ok(scale),
+        Some(amount) => to_steps(amount, scale).ok_or_else(|| {
+            if scale == 1 {
+                AppError::bad_request("amount must be a positive whole number")
+            } else {
+                AppError::bad_request(format!("amount must be a positive multiple of 1/{scale}"))
+            }
+        }),
+    }
+}
+
+fn to_response(state: &AppState, date: String, counts: DayCounts) -> DailyCountsResponse {
+    let target = state.target();
     DailyCountsResponse {
-        net: counts.add as i64 - counts.sub as i64,
+        net: counts.net(),
         date,
         add_count: counts.add,
         sub_count: counts.sub,
+        clicks_per_hour: None,
+        target,
+        progress: target
+            .filter(|target| *target > 0)
+            .map(|target| counts.net() as f64 / target as f64),
+        actions: counts.actions,
+        server_time: None,
+        dry_run: false,
+    }
+}
+
+fn today_response(state: &AppState, date: String, counts: DayCounts) -> DailyCountsResponse {
+    let day_offset = Duration::hours(i64::from(state.config.day_offset_hours));
+    let pace = clicks_per_hour(&counts, (state.now() - day_offset).time());
+    DailyCountsResponse {
+        clicks_per_hour: pace,
+        ..to_response(state, date, counts)
+    }
+}
+
+/// Counter names are 1 to 32 lowercase letters, digits, `-` or `_`; none
+/// means the default counter.
+fn parse_counter(value: Option<&str>) -> Result<String, AppError> {
+    const MAX_COUNTER_CHARS: usize = 32;
+
+    let Some(name) = value.map(str::trim).filter(|name| !name.is_empty()) else {
+        return Ok(AppData::DEFAULT_COUNTER.to_string());
+    };
+    let valid = name.len() <= MAX_COUNTER_CHARS
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-' || ch == '_');
+    if !valid {
+        return Err(AppError::bad_request(format!(
+            "counter must be 1 to {MAX_COUNTER_CHARS} lowercase letters, digits, '-' or '_'"
+        )));
     }
+    Ok(name.to_string())
 }
 
-fn today_string() -> String {
-    Local::now().date_naive().to_string()
+fn parse_date_param(value: &str) -> Result<NaiveDate, AppError> {
+    // chrono accepts unpadded fields, so round-trip to insist on the canonical key form.
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .filter(|date| date.to_string() == value)
+        .ok_or_else(|| AppError::bad_request("date must be formatted as YYYY-MM-DD"))
 }
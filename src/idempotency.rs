@@ -0,0 +1,275 @@
+//! Replays for retried clicks. A `POST /api/click` carrying an
+//! `Idempotency-Key` header is remembered for [`KEY_TTL`]; a repeat with the
+//! same key gets the first response back without counting again, so a retry
+//! over a flaky connection can't double-count. A retry that arrives while the
+//! first is still being counted waits for its answer. A key reused for a
+//! different request is rejected with `422` instead of replaying the wrong
+//! response.
+
+use crate::errors::AppError;
+use crate::models::DailyCountsResponse;
+use axum::http::HeaderMap;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+pub const HEADER: &str = "idempotency-key";
+
+/// How long a key is remembered after its first use.
+pub const KEY_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Keys kept at most; the oldest are forgotten first beyond that.
+const MAX_KEYS: usize = 1_000;
+
+const MAX_KEY_LEN: usize = 255;
+
+#[derive(Debug, Default)]
+pub struct IdempotencyCache {
+    /// Each key's request [`fingerprint`] and where its click is at.
+    responses: HashMap<String, (u64, Entry)>,
+    /// Keys in the order they were first seen, with when that was. Every key
+    /// has the same TTL, so expired ones are always at the front.
+    seen: VecDeque<(String, Instant)>,
+}
+
+#[derive(Debug)]
+enum Entry {
+    /// Still being counted. The sender lives in the [`Reservation`], so this
+    /// reports closed once the click finishes or is abandoned.
+    Pending(watch::Receiver<()>),
+    Done(DailyCountsResponse),
+}
+
+/// What to do with a keyed click, from [`IdempotencyCache::claim`].
+#[derive(Debug)]
+pub enum Claim {
+    /// The key was already answered; send this again.
+    Replay(DailyCountsResponse),
+    /// Another request with the key is being counted. Wait for `changed()` to
+    /// return, then claim again.
+    Wait(watch::Receiver<()>),
+    /// Count the click, then hand the outcome to [`IdempotencyCache::finish`].
+    Run(Reservation),
+}
+
+/// The right to count a keyed click. Dropping it wakes anyone waiting on the
+/// key, even if the click never finished.
+#[derive(Debug)]
+pub struct Reservation {
+    key: String,
+    _done: watch::Sender<()>,
+}
+
+impl IdempotencyCache {
+    /// Looks up `key`, reserving it if nobody has answered or is answering it
+    /// by `now`. Fails if `key` was first sent with a request of a different
+    /// `fingerprint`. Only this is done under the cache's lock, so clicks with
+    /// other keys never wait on this one.
+    pub fn claim(&mut self, key: &str, fingerprint: u64, now: Instant) -> Result<Claim, AppError> {
+        self.expire(now);
+        match self.responses.get(key) {
+            Some((seen, _)) if *seen != fingerprint => {
+                return Err(AppError::unprocessable(
+                    "Idempotency-Key was already used for a different request",
+                ));
+            }
+            Some((_, Entry::Done(response))) => return Ok(Claim::Replay(response.clone())),
+            Some((_, Entry::Pending(done))) if done.has_changed().is_ok() => {
+                return Ok(Claim::Wait(done.clone()));
+            }
+            // Abandoned, e.g. the client went away mid-click: start over.
+            Some((_, Entry::Pending(_))) | None => {}
+        }
+        let (done, pending) = watch::channel(());
+        self.record(key.to_string(), (fingerprint, Entry::Pending(pending)), now);
+        Ok(Claim::Run(Reservation {
+            key: key.to_string(),
+            _done: done,
+        }))
+    }
+
+    /// Stores the `response` to a reserved click for replays, or with `None`
+    /// (the click failed) forgets the key so a retry counts it afresh.
+    pub fn finish(
+        &mut self,
+        reservation: Reservation,
+        response: Option<DailyCountsResponse>,
+        now: Instant,
+    ) {
+        self.expire(now);
+        let Some((fingerprint, _)) = self.responses.get(&reservation.key) else {
+            return;
+        };
+        match response {
+            Some(response) => {
+                let entry = (*fingerprint, Entry::Done(response));
+                self.record(reservation.key, entry, now);
+            }
+            None => {
+                self.responses.remove(&reservation.key);
+                self.seen.retain(|(key, _)| *key != reservation.key);
+            }
+        }
+    }
+
+    fn record(&mut self, key: String, entry: (u64, Entry), now: Instant) {
+        if self.responses.insert(key.clone(), entry).is_none() {
+            self.seen.push_back((key, now));
+        }
+        while self.seen.len() > MAX_KEYS {
+            if let Some((oldest, _)) = self.seen.pop_front() {
+                self.responses.remove(&oldest);
+            }
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some((key, at)) = self.seen.front() {
+            if now.saturating_duration_since(*at) < KEY_TTL {
+                break;
+            }
+            self.responses.remove(key);
+            self.seen.pop_front();
+        }
+    }
+}
+
+/// Identifies a request for [`IdempotencyCache`], from its method, path,
+/// query and parsed body. Only compared within one process, so the std
+/// hasher is stable enough.
+pub fn fingerprint(request: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    request.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The request's `Idempotency-Key`, if it sent one. Keys are 1 to 255
+/// visible ASCII characters.
+pub fn idempotency_key(headers: &HeaderMap) -> Result<Option<String>, AppError> {
+    let Some(value) = headers.get(HEADER) else {
+        return Ok(None);
+    };
+    let key = value
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|key| {
+            !key.is_empty() && key.len() <= MAX_KEY_LEN && key.bytes().all(|b| b.is_ascii_graphic())
+        })
+        .ok_or_else(|| {
+            AppError::bad_request(format!(
+                "Idempotency-Key must be 1 to {MAX_KEY_LEN} visible ASCII characters"
+            ))
+        })?;
+    Ok(Some(key.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn response(add_count: u64) -> DailyCountsResponse {
+        DailyCountsResponse {
+            date: "2026-01-05".to_string(),
+            add_count,
+            sub_count: 0,
+            net: add_count as i64,
+            clicks_per_hour: None,
+            target: None,
+            progress: None,
+            actions: BTreeMap::new(),
+            server_time: None,
+            dry_run: false,
+        }
+    }
+
+    fn answer(cache: &mut IdempotencyCache, key: &str, fingerprint: u64, add: u64, now: Instant) {
+        match cache.claim(key, fingerprint, now).unwrap() {
+            Claim::Run(reservation) => cache.finish(reservation, Some(response(add)), now),
+            claim => panic!("{key} was already claimed: {claim:?}"),
+        }
+    }
+
+    fn replayed(
+        cache: &mut IdempotencyCache,
+        key: &str,
+        fingerprint: u64,
+        now: Instant,
+    ) -> Option<u64> {
+        match cache.claim(key, fingerprint, now).unwrap() {
+            Claim::Replay(response) => Some(response.add_count),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn keys_expire_and_the_oldest_are_dropped_first() {
+        let mut cache = IdempotencyCache::default();
+        let start = Instant::now();
+        answer(&mut cache, "a", 1, 1, start);
+        assert_eq!(replayed(&mut cache, "a", 1, start + KEY_TTL / 2), Some(1));
+
+        // Reusing an expired key starts over.
+        let later = start + KEY_TTL;
+        answer(&mut cache, "a", 2, 2, later);
+        assert_eq!(replayed(&mut cache, "a", 2, later), Some(2));
+
+        for n in 0..MAX_KEYS {
+            answer(&mut cache, &n.to_string(), 0, 0, later);
+        }
+        assert_eq!(cache.responses.len(), MAX_KEYS);
+        assert_eq!(cache.seen.len(), MAX_KEYS);
+        assert_eq!(replayed(&mut cache, "0", 0, later), Some(0));
+        assert!(!cache.responses.contains_key("a"));
+    }
+
+    #[test]
+    fn a_reused_key_must_match_its_first_request() {
+        let mut cache = IdempotencyCache::default();
+        let now = Instant::now();
+        let add = fingerprint(&("POST", "/click", "add", 1u64));
+        let sub = fingerprint(&("POST", "/click", "sub", 1u64));
+        assert_ne!(add, sub);
+
+        let Claim::Run(reservation) = cache.claim("a", add, now).unwrap() else {
+            panic!("a fresh key should be reserved");
+        };
+        // Checked while the first request is still counting, too.
+        let err = cache.claim("a", sub, now).unwrap_err();
+        assert_eq!(err.status, axum::http::StatusCode::UNPROCESSABLE_ENTITY);
+        cache.finish(reservation, Some(response(1)), now);
+        assert_eq!(replayed(&mut cache, "a", add, now), Some(1));
+        assert!(cache.claim("a", sub, now).is_err());
+    }
+
+    #[tokio::test]
+    async fn retries_wait_for_the_reserved_click() {
+        let mut cache = IdempotencyCache::default();
+        let now = Instant::now();
+        let Claim::Run(reservation) = cache.claim("a", 1, now).unwrap() else {
+            panic!("a fresh key should be reserved");
+        };
+        let Claim::Wait(mut done) = cache.claim("a", 1, now).unwrap() else {
+            panic!("a pending key should be waited on");
+        };
+        assert!(matches!(cache.claim("b", 1, now).unwrap(), Claim::Run(_)));
+
+        cache.finish(reservation, Some(response(1)), now);
+        let _ = done.changed().await;
+        assert_eq!(replayed(&mut cache, "a", 1, now), Some(1));
+
+        // A failed click frees its key, and so does an abandoned one.
+        let Claim::Run(reservation) = cache.claim("c", 1, now).unwrap() else {
+            panic!("a fresh key should be reserved");
+        };
+        cache.finish(reservation, None, now);
+        assert!(!cache.seen.iter().any(|(key, _)| key == "c"));
+        let Claim::Run(reservation) = cache.claim("c", 1, now).unwrap() else {
+            panic!("a failed key should be free again");
+        };
+        drop(reservation);
+        assert!(matches!(cache.claim("c", 1, now).unwrap(), Claim::Run(_)));
+    }
+}
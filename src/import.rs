@@ -0,0 +1,202 @@
+use crate::errors::AppError;
+use crate::models::{AppData, DayCounts};
+use chrono::NaiveDate;
+
+/// How an uploaded `AppData` payload is combined with the stored history.
+///
+/// - `Merge` (also accepted as `sum`) adds the imported add/sub counts onto
+///   any existing day and appends tags it doesn't have yet.
+/// - `Replace` discards the stored history and keeps only the payload.
+/// - `Upsert` overwrites the days present in the payload and leaves every
+///   other stored day untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    Merge,
+    Replace,
+    Upsert,
+}
+
+impl ImportMode {
+    pub fn parse(value: Option<&str>) -> Result<Self, AppError> {
+        match value.map(str::trim) {
+            None | Some("") | Some("merge") | Some("sum") => Ok(Self::Merge),
+            Some("replace") => Ok(Self::Replace),
+            Some("upsert") => Ok(Self::Upsert),
+            Some(_) => Err(AppError::bad_request(
+                "mode must be 'merge' (or 'sum'), 'replace' or 'upsert'",
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Merge => "merge",
+            Self::Replace => "replace",
+            Self::Upsert => "upsert",
+        }
+    }
+}
+
+pub fn validate_import(incoming: &AppData) -> Result<(), AppError> {
+    for key in incoming.days.keys() {
+        // Round-trip so unpadded keys like `2026-1-5` can't slip in as
+        // separate days.
+        let canonical =
+            NaiveDate::parse_from_str(key, "%Y-%m-%d").is_ok_and(|date| date.to_string() == *key);
+        if !canonical {
+            return Err(AppError::bad_request(format!(
+                "invalid date key '{key}', expected YYYY-MM-DD"
+            )));
+        }
+    }
+    Ok(())
+}
+
+pub fn apply_import(data: &mut AppData, incoming: AppData, mode: ImportMode) {
+    match mode {
+        ImportMode::Merge => {
+            for (date, counts) in incoming.days {
+                let entry = data.days.entry(date).or_default();
+                entry.add = entry.add.saturating_add(counts.add);
+                entry.sub = entry.sub.saturating_add(counts.sub);
+                for (action, count) in counts.actions {
+                    entry.record(&action, count);
+                }
+                for tag in counts.tags {
+                    if !entry.tags.contains(&tag) {
+                        entry.tags.push(tag);
+                    }
+                }
+            }
+        }
+        ImportMode::Replace => {
+            data.days = incoming.days;
+        }
+        ImportMode::Upsert => {
+            data.days.extend(incoming.days);
+        }
+    }
+}
+
+/// Merges another instance's history by taking the larger count of every
+/// action for every day. This converges as long as counts only ever grow; a
+/// manual correction that lowers a count on one side is undone by the next
+/// sync with a peer that still has the higher value. Returns how many days
+/// changed locally.
+pub fn apply_sync(data: &mut AppData, incoming: AppData) -> usize {
+    let mut changed = 0;
+    for (date, counts) in incoming.days {
+        let entry = data.days.entry(date).or_default();
+        let mut actions = entry.actions.clone();
+        for (action, count) in counts.actions {
+            let local = actions.entry(action).or_default();
+            *local = (*local).max(count);
+        }
+        let merged = DayCounts {
+            add: entry.add.max(counts.add),
+            sub: entry.sub.max(counts.sub),
+            tags: if entry.tags.is_empty() {
+                counts.tags
+            } else {
+                entry.tags.clone()
+            },
+            actions,
+        };
+        if *entry != merged {
+            *entry = merged;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(days: &[(&str, u64, u64)]) -> AppData {
+        let mut data = AppData::default();
+        for (date, add, sub) in days {
+            data.days
+                .insert(date.to_string(), DayCounts::new(*add, *sub));
+        }
+        data
+    }
+
+    #[test]
+    fn merge_sums_matching_days() {
+        let mut data = sample(&[("2026-01-01", 2, 1), ("2026-01-02", 4, 0)]);
+        let incoming = sample(&[("2026-01-01", 3, 2), ("2026-01-03", 1, 1)]);
+
+        apply_import(&mut data, incoming, ImportMode::Merge);
+
+        assert_eq!(data.days.len(), 3);
+        assert_eq!(data.days["2026-01-01"].add, 5);
+        assert_eq!(data.days["2026-01-01"].sub, 3);
+        assert_eq!(data.days["2026-01-02"].add, 4);
+        assert_eq!(data.days["2026-01-03"].add, 1);
+    }
+
+    #[test]
+    fn replace_discards_existing_days() {
+        let mut data = sample(&[("2026-01-01", 2, 1), ("2026-01-02", 4, 0)]);
+        let incoming = sample(&[("2026-01-03", 1, 1)]);
+
+        apply_import(&mut data, incoming, ImportMode::Replace);
+
+        assert_eq!(data.days.len(), 1);
+        assert_eq!(data.days["2026-01-03"].add, 1);
+    }
+
+    #[test]
+    fn upsert_overwrites_only_imported_days() {
+        let mut data = sample(&[("2026-01-01", 2, 1), ("2026-01-02", 4, 0)]);
+        let incoming = sample(&[("2026-01-01", 7, 0), ("2026-01-03", 1, 1)]);
+
+        apply_import(&mut data, incoming, ImportMode::Upsert);
+
+        assert_eq!(data.days.len(), 3);
+        assert_eq!(data.days["2026-01-01"].add, 7);
+        assert_eq!(data.days["2026-01-01"].sub, 0);
+        assert_eq!(data.days["2026-01-02"].add, 4);
+        assert_eq!(data.days["2026-01-03"].sub, 1);
+    }
+
+    #[test]
+    fn sync_takes_the_larger_count_per_field() {
+        let mut data = sample(&[("2026-01-01", 5, 1), ("2026-01-02", 4, 0)]);
+        let incoming = sample(&[
+            ("2026-01-01", 3, 2),
+            ("2026-01-02", 4, 0),
+            ("2026-01-03", 1, 0),
+        ]);
+
+        let changed = apply_sync(&mut data, incoming.clone());
+
+        assert_eq!(changed, 2);
+        assert_eq!(data.days["2026-01-01"], DayCounts::new(5, 2));
+        assert_eq!(data.days["2026-01-02"], DayCounts::new(4, 0));
+        assert_eq!(data.days["2026-01-03"], DayCounts::new(1, 0));
+
+        // Syncing the same payload again is a no-op.
+        assert_eq!(apply_sync(&mut data, incoming), 0);
+    }
+
+    #[test]
+    fn mode_parsing_defaults_to_merge() {
+        assert_eq!(ImportMode::parse(None).unwrap(), ImportMode::Merge);
+        assert_eq!(
+            ImportMode::parse(Some("upsert")).unwrap(),
+            ImportMode::Upsert
+        );
+        assert_eq!(ImportMode::parse(Some("sum")).unwrap(), ImportMode::Merge);
+        assert!(ImportMode::parse(Some("sum-ish")).is_err());
+    }
+
+    #[test]
+    fn validate_rejects_bad_keys() {
+        assert!(validate_import(&sample(&[("2026-01-01", 1, 0)])).is_ok());
+        assert!(validate_import(&sample(&[("2026-13-40", 1, 0)])).is_err());
+        assert!(validate_import(&sample(&[("2026-1-5", 1, 0)])).is_err());
+    }
+}
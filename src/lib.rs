@@ -1,12 +1,34 @@
 pub mod app;
+pub mod auth;
+pub mod chart;
+pub mod client;
+pub mod clock;
+pub mod config;
+pub mod csv;
 pub mod errors;
+#[cfg(any(feature = "toml", feature = "yaml"))]
+pub mod export;
+pub mod grafana;
 pub mod handlers;
+pub mod idempotency;
+pub mod import;
+pub mod live;
+pub mod metrics;
 pub mod models;
+pub mod openapi;
+pub mod ratelimit;
+pub mod setup;
+pub mod state;
 pub mod stats;
 pub mod storage;
+pub mod tasks;
 pub mod ui;
-pub mod state;
+pub mod units;
 
 pub use app::router;
+pub use config::Config;
 pub use state::AppState;
-pub use storage::{load_data, resolve_data_path};
+pub use storage::{
+    ClickEvent, EventLog, JsonStorage, MemoryStorage, Storage, default_data_dir, load_archives,
+    load_data, merge_archive, resolve_archive_paths, resolve_data_path, resolve_storage,
+};
@@ -0,0 +1,107 @@
+//! Pushes changes to connected clients instead of making them poll.
+//! `GET /api/ws` is a WebSocket that receives today's counts (the same JSON
+//! as `GET /api/today`) after every click on the default counter, and
+//! `GET /api/stats/stream` is a Server-Sent Events stream of the full stats
+//! driven by the same clicks.
+
+use crate::models::DailyCountsResponse;
+use crate::state::AppState;
+use crate::stats::{StatsOptions, build_stats_at};
+use crate::units::scaled_value;
+use axum::{
+    extract::{
+        State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    response::{
+        Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::stream::{self, Stream};
+use tokio::sync::broadcast::{Receiver, error::RecvError};
+use tracing::debug;
+
+/// Updates buffered per client before a slow one starts skipping ahead.
+pub const UPDATE_BUFFER: usize = 64;
+
+pub async fn get_ws(State(state): State<AppState>, upgrade: WebSocketUpgrade) -> Response {
+    // Subscribe before the handshake finishes so no click in between is
+    // missed.
+    let updates = state.updates.subscribe();
+    let scale = state.config.unit_scale();
+    upgrade.on_upgrade(move |socket| push_updates(socket, updates, scale))
+}
+
+async fn push_updates(
+    mut socket: WebSocket,
+    mut updates: Receiver<DailyCountsResponse>,
+    scale: u64,
+) {
+    loop {
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(counts) => {
+                    let Ok(value) = scaled_value(&counts, scale) else {
+                        continue;
+                    };
+                    if socket.send(Message::Text(value.to_string())).await.is_err() {
+                        break;
+                    }
+                }
+                // Only the latest counts matter, so a client that fell
+                // behind just carries on with the next one.
+                Err(RecvError::Lagged(skipped)) => debug!("websocket client skipped {skipped} updates"),
+                Err(RecvError::Closed) => break,
+            },
+            incoming = socket.recv() => match incoming {
+                // Pings are answered by axum; anything else from the client
+                // is ignored.
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// Sends the stats once on connect and again after every click, each as an
+/// `event: stats` whose `data:` is the `GET /api/stats` JSON. The stream is
+/// dropped, and the subscription with it, when the client disconnects, and
+/// ends when the server shuts down so graceful shutdown isn't held up.
+pub async fn stats_stream(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, serde_json::Error>>> {
+    let updates = state.updates.subscribe();
+    let shutdown = state.shutdown.subscribe();
+    let first = stats_event(&state).await;
+    let events = stream::unfold(
+        (state, updates, shutdown, Some(first)),
+        |(state, mut updates, mut shutdown, pending)| async move {
+            if let Some(event) = pending {
+                return Some((event, (state, updates, shutdown, None)));
+            }
+            tokio::select! {
+                update = updates.recv() => match update {
+                    // A client that fell behind only needs the latest stats.
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = shutdown.wait_for(|stopping| *stopping) => return None,
+            }
+            let event = stats_event(&state).await;
+            Some((event, (state, updates, shutdown, None)))
+        },
+    );
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+async fn stats_event(state: &AppState) -> Result<Event, serde_json::Error> {
+    let today = state.today();
+    let options = StatsOptions::from_config(&state.config).with_target(state.target());
+    let stats = {
+        let data = state.data.lock().await;
+        build_stats_at(today, &data, &options)
+    };
+    let value = scaled_value(&stats, state.config.unit_scale())?;
+    Ok(Event::default().event("stats").data(value.to_string()))
+}
@@ -1,7 +1,7 @@
-use std::{env, net::SocketAddr};
+use std::net::SocketAddr;
 use tokio::fs;
-use tracing::info;
-use tracing_subscriber::{fmt, EnvFilter};
+use tracing::{error, info, warn};
+use tracing_subscriber::{EnvFilter, fmt};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -9,25 +9,118 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_env_filter(EnvFilter::from_default_env().add_directive("info".parse()?))
         .init();
 
-    let data_path = web_app::resolve_data_path()?;
-    if let Some(parent) = data_path.parent() {
-        fs::create_dir_all(parent).await?;
+    let mut config = web_app::Config::from_env();
+    let storage = web_app::resolve_storage()?;
+    let data_path = storage.path().to_path_buf();
+    let in_memory = storage.in_memory();
+    if in_memory {
+        info!("keeping data in memory only; nothing is written to disk");
+        if config.event_log {
+            warn!("APP_EVENT_LOG is ignored with APP_STORAGE=memory");
+            config.event_log = false;
+        }
+    } else {
+        info!("data file: {}", data_path.display());
+        if let Some(parent) = data_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
     }
 
-    let data = web_app::load_data(&data_path).await;
-    let state = web_app::AppState::new(data_path, data);
+    // Setup writes `config.toml` beside the data file, so it needs one.
+    let (setup, mut load_warnings) = if in_memory {
+        (None, Vec::new())
+    } else {
+        web_app::setup::load_setup(&web_app::setup::setup_path(&data_path)).await
+    };
+    if let Some(setup) = &setup {
+        config.apply_setup(setup);
+    }
+    let fresh_install =
+        !in_memory && setup.is_none() && !fs::try_exists(&data_path).await.unwrap_or(true);
 
-    let app = web_app::router(state);
+    let (archive, archive_warnings) =
+        web_app::load_archives(&web_app::resolve_archive_paths()).await;
+    load_warnings.extend(archive_warnings);
+    let (mut live, live_warnings) = storage.load().await;
+    load_warnings.extend(live_warnings);
+    let event_log = config
+        .event_log
+        .then(|| web_app::EventLog::beside(&data_path));
+    if let Some(log) = &event_log {
+        load_warnings.extend(log.replay(&mut live).await);
+    }
+    let data = web_app::merge_archive(live, &archive);
+    let auto_add_daily = config.auto_add_steps();
+    let mut state = web_app::AppState::new(data_path, data, config)
+        .with_storage(storage)
+        .with_archive(archive)
+        .with_load_warnings(load_warnings)
+        .with_fresh_install(fresh_install);
+    if let Some(log) = event_log {
+        state = state.with_event_log(log);
+    }
+    if let Some(amount) = auto_add_daily {
+        tokio::spawn(web_app::tasks::run_auto_add(state.clone(), amount));
+    }
+    if let Some(interval) = state.config.persist_debounce() {
+        tokio::spawn(web_app::tasks::run_persist_flusher(state.clone(), interval));
+    }
+    if let Some(interval) = state.config.event_log_compaction() {
+        tokio::spawn(web_app::tasks::run_log_compactor(state.clone(), interval));
+    }
 
-    let port = env::var("PORT")
-        .ok()
-        .and_then(|value| value.parse::<u16>().ok())
-        .unwrap_or(8080);
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let app = web_app::router(state.clone());
 
-    info!("listening on http://{addr}");
+    let addr = web_app::config::resolve_listen_addr()?;
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    info!("listening on http://{}", listener.local_addr()?);
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(state.clone()))
+    .await?;
+
+    // In-flight requests have finished; write anything still pending (from
+    // the debounce or the event log) so the file matches what clients were
+    // told.
+    match state.flush().await {
+        Ok(()) => info!("final write complete, exiting"),
+        Err(err) => error!("final write failed: {}", err.message),
+    }
 
     Ok(())
 }
+
+/// Resolves on Ctrl-C, or SIGTERM on unix (what `docker stop` and systemd
+/// send), after telling long-lived streams to finish.
+async fn shutdown_signal(state: web_app::AppState) {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            error!("failed to listen for Ctrl-C: {err}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                error!("failed to listen for SIGTERM: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
+    info!("shutdown signal received, finishing in-flight requests");
+    state.shutdown.send_replace(true);
+}
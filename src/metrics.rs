@@ -0,0 +1,273 @@
+use serde::Serialize;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds (in microseconds) of the fixed latency buckets. Anything slower
+/// than the last bound lands in the overflow bucket.
+const BUCKET_BOUNDS_US: [u64; 14] = [
+    100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000,
+    1_000_000, 5_000_000,
+];
+
+/// Lock-free fixed-bucket histogram. Recording is a couple of relaxed atomic
+/// increments, so it is cheap enough to sit on the write path.
+#[derive(Debug, Default)]
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; BUCKET_BOUNDS_US.len() + 1],
+    count: AtomicU64,
+    sum_us: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub p99_ms: Option<f64>,
+}
+
+impl LatencyHistogram {
+    pub fn record(&self, elapsed: Duration) {
+        let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        let index = BUCKET_BOUNDS_US
+            .iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(BUCKET_BOUNDS_US.len());
+        self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    /// Returns the bucket upper bound (in milliseconds) that contains the given
+    /// quantile, or `None` before anything has been recorded. Samples in the
+    /// overflow bucket report the largest finite bound.
+    pub fn quantile_ms(&self, quantile: f64) -> Option<f64> {
+        let counts = self.snapshot();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+
+        let rank = ((quantile * total as f64).ceil() as u64).clamp(1, total);
+        let mut seen = 0u64;
+        for (index, count) in counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let bound = BUCKET_BOUNDS_US
+                    .get(index)
+                    .copied()
+                    .unwrap_or(BUCKET_BOUNDS_US[BUCKET_BOUNDS_US.len() - 1]);
+                return Some(bound as f64 / 1000.0);
+            }
+        }
+        None
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            count: self.count.load(Ordering::Relaxed),
+            p50_ms: self.quantile_ms(0.50),
+            p95_ms: self.quantile_ms(0.95),
+            p99_ms: self.quantile_ms(0.99),
+        }
+    }
+
+    /// Appends the histogram in Prometheus text format under `name` (which
+    /// should end in `_seconds`).
+    pub fn write_prometheus(&self, out: &mut String, name: &str, help: &str) {
+        let counts = self.snapshot();
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        let mut cumulative = 0u64;
+        for (bound, count) in BUCKET_BOUNDS_US.iter().zip(counts.iter()) {
+            cumulative += count;
+            let le = *bound as f64 / 1_000_000.0;
+            let _ = writeln!(out, "{name}_bucket{{le=\"{le}\"}} {cumulative}");
+        }
+        cumulative += counts[BUCKET_BOUNDS_US.len()];
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {cumulative}");
+        let sum = self.sum_us.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{name}_sum {sum}");
+        let _ = writeln!(out, "{name}_count {}", self.count.load(Ordering::Relaxed));
+    }
+
+    fn snapshot(&self) -> Vec<u64> {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub persist_latency: LatencyHistogram,
+    /// Stored steps added through clicks since the process started.
+    pub clicks_add: AtomicU64,
+    /// Stored steps subtracted through clicks since the process started.
+    pub clicks_sub: AtomicU64,
+}
+
+/// Gauges derived from the stored counts rather than from the process itself.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DataGauges {
+    pub today_add: u64,
+    pub today_sub: u64,
+    pub today_net: i64,
+    pub week_net: i64,
+    pub current_streak: u64,
+}
+
+impl DataGauges {
+    fn write_prometheus(&self, out: &mut String) {
+        write_gauge(
+            out,
+            "clickcounter_today_add",
+            "Adds recorded today.",
+            self.today_add,
+        );
+        write_gauge(
+            out,
+            "clickcounter_today_sub",
+            "Subtracts recorded today.",
+            self.today_sub,
+        );
+        write_gauge(
+            out,
+            "clickcounter_today_net",
+            "Net change today.",
+            self.today_net,
+        );
+        write_gauge(
+            out,
+            "clickcounter_week_net",
+            "Net change this week.",
+            self.week_net,
+        );
+        write_gauge(
+            out,
+            "clickcounter_current_streak_days",
+            "Consecutive days with a positive net.",
+            self.current_streak,
+        );
+    }
+}
+
+fn write_gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    let _ = writeln!(out, "{name} {value}");
+}
+
+impl Metrics {
+    /// Counts a recorded click of `steps` towards `clickcounter_clicks_total`.
+    /// Custom actions aren't broken out there.
+    pub fn record_click(&self, action: &str, steps: u64) {
+        let counter = match action {
+            "add" => &self.clicks_add,
+            "sub" => &self.clicks_sub,
+            _ => return,
+        };
+        counter.fetch_add(steps, Ordering::Relaxed);
+    }
+
+    pub fn render_prometheus(&self, gauges: &DataGauges) -> String {
+        let mut out = String::new();
+        gauges.write_prometheus(&mut out);
+
+        let name = "clickcounter_clicks_total";
+        let _ = writeln!(
+            out,
+            "# HELP {name} Clicks recorded since the server started."
+        );
+        let _ = writeln!(out, "# TYPE {name} counter");
+        for (action, counter) in [("add", &self.clicks_add), ("sub", &self.clicks_sub)] {
+            let value = counter.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}{{action=\"{action}\"}} {value}");
+        }
+
+        self.persist_latency.write_prometheus(
+            &mut out,
+            "clickcounter_persist_duration_seconds",
+            "Time spent writing the data file.",
+        );
+
+        let summary = self.persist_latency.summary();
+        let name = "clickcounter_persist_latency_seconds";
+        let _ = writeln!(
+            out,
+            "# HELP {name} Persist latency percentiles (bucket upper bounds)."
+        );
+        let _ = writeln!(out, "# TYPE {name} gauge");
+        for (quantile, value) in [
+            ("0.5", summary.p50_ms),
+            ("0.95", summary.p95_ms),
+            ("0.99", summary.p99_ms),
+        ] {
+            if let Some(ms) = value {
+                let _ = writeln!(out, "{name}{{quantile=\"{quantile}\"}} {}", ms / 1000.0);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_histogram_has_no_percentiles() {
+        let histogram = LatencyHistogram::default();
+        let summary = histogram.summary();
+        assert_eq!(summary.count, 0);
+        assert_eq!(summary.p50_ms, None);
+    }
+
+    #[test]
+    fn percentiles_report_bucket_upper_bounds() {
+        let histogram = LatencyHistogram::default();
+        for _ in 0..90 {
+            histogram.record(Duration::from_micros(800));
+        }
+        for _ in 0..9 {
+            histogram.record(Duration::from_millis(20));
+        }
+        histogram.record(Duration::from_secs(10));
+
+        let summary = histogram.summary();
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.p50_ms, Some(1.0));
+        assert_eq!(summary.p95_ms, Some(25.0));
+        assert_eq!(summary.p99_ms, Some(25.0));
+        assert_eq!(histogram.quantile_ms(1.0), Some(5000.0));
+    }
+
+    #[test]
+    fn clicks_are_counted_per_action() {
+        let metrics = Metrics::default();
+        metrics.record_click("add", 1);
+        metrics.record_click("add", 4);
+        metrics.record_click("sub", 2);
+
+        let out = metrics.render_prometheus(&DataGauges::default());
+        assert!(out.contains("# TYPE clickcounter_clicks_total counter\n"));
+        assert!(out.contains("clickcounter_clicks_total{action=\"add\"} 5\n"));
+        assert!(out.contains("clickcounter_clicks_total{action=\"sub\"} 2\n"));
+    }
+
+    #[test]
+    fn prometheus_buckets_are_cumulative() {
+        let histogram = LatencyHistogram::default();
+        histogram.record(Duration::from_micros(50));
+        histogram.record(Duration::from_millis(2));
+
+        let mut out = String::new();
+        histogram.write_prometheus(&mut out, "test_seconds", "test");
+        assert!(out.contains("test_seconds_bucket{le=\"0.0001\"} 1"));
+        assert!(out.contains("test_seconds_bucket{le=\"0.0025\"} 2"));
+        assert!(out.contains("test_seconds_bucket{le=\"+Inf\"} 2"));
+        assert!(out.contains("test_seconds_count 2"));
+    }
+}
@@ -1,28 +1,344 @@
+use crate::metrics::LatencySummary;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
 pub struct DayCounts {
     pub add: u64,
     pub sub: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Counts for actions other than `add` and `sub`, keyed by action name.
+    /// They are tallied but never move `net`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub actions: BTreeMap<String, u64>,
+}
+
+impl DayCounts {
+    pub fn new(add: u64, sub: u64) -> Self {
+        Self {
+            add,
+            sub,
+            tags: Vec::new(),
+            actions: BTreeMap::new(),
+        }
+    }
+
+    pub fn net(&self) -> i64 {
+        net(self.add, self.sub)
+    }
+
+    /// Adds `steps` to the bucket for `action`.
+    pub fn record(&mut self, action: &str, steps: u64) {
+        let count = match action {
+            "add" => &mut self.add,
+            "sub" => &mut self.sub,
+            _ => self.actions.entry(action.to_string()).or_default(),
+        };
+        *count = count.saturating_add(steps);
+    }
+
+    /// Applies a click the way `mode` counts and returns how many steps it
+    /// actually moved. In [`CountMode::Single`] a `sub` takes from `add`,
+    /// the day's one running value, and stops at zero.
+    pub fn click(&mut self, mode: CountMode, action: &str, steps: u64) -> u64 {
+        if mode == CountMode::Single && action == "sub" {
+            let taken = steps.min(self.add);
+            self.add -= taken;
+            taken
+        } else {
+            self.record(action, steps);
+            steps
+        }
+    }
+
+    /// Reverses a [`DayCounts::click`] that moved `steps`.
+    pub fn unclick(&mut self, mode: CountMode, action: &str, steps: u64) {
+        if mode == CountMode::Single && action == "sub" {
+            self.record("add", steps);
+        } else {
+            self.unrecord(action, steps);
+        }
+    }
+
+    /// Takes `steps` back out of the bucket for `action`, dropping a custom
+    /// bucket once it reaches zero.
+    pub fn unrecord(&mut self, action: &str, steps: u64) {
+        match action {
+            "add" => self.add = self.add.saturating_sub(steps),
+            "sub" => self.sub = self.sub.saturating_sub(steps),
+            _ => {
+                if let Some(count) = self.actions.get_mut(action) {
+                    *count = count.saturating_sub(steps);
+                    if *count == 0 {
+                        self.actions.remove(action);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// How `sub` clicks are counted (`APP_COUNT_MODE`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CountMode {
+    /// `add` and `sub` each only grow, and `net` is their difference, so it
+    /// can go below zero.
+    #[default]
+    Split,
+    /// One running value per day, kept in `add`: `sub` clicks take from it
+    /// and stop at zero, `sub` itself stays 0 and `net` equals `add`.
+    Single,
+}
+
+/// The day weeks begin on (`APP_WEEK_START`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    #[default]
+    Monday,
+    Sunday,
+}
+
+impl WeekStart {
+    pub fn weekday(self) -> chrono::Weekday {
+        match self {
+            Self::Monday => chrono::Weekday::Mon,
+            Self::Sunday => chrono::Weekday::Sun,
+        }
+    }
+}
+
+/// `add - sub`, clamped to the `i64` range. Counts only ever grow by
+/// saturating adds, so either can exceed `i64::MAX` and a plain cast would
+/// wrap to the wrong sign.
+pub fn net(add: u64, sub: u64) -> i64 {
+    let difference = i128::from(add) - i128::from(sub);
+    difference.clamp(i128::from(i64::MIN), i128::from(i64::MAX)) as i64
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AppData {
+    #[serde(default)]
     pub days: BTreeMap<String, DayCounts>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub week_notes: BTreeMap<String, String>,
+    /// Date of the last `APP_AUTO_ADD_DAILY` increment, so it is applied once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_auto_add: Option<String>,
+    /// Named counters other than the default one, keyed by name then date.
+    /// The default counter stays in `days`, so files written before counters
+    /// existed load unchanged.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub counters: BTreeMap<String, BTreeMap<String, DayCounts>>,
+    /// Last `APP_EVENT_LOG` event folded into this snapshot, so replaying the
+    /// log after a crash mid-compaction doesn't count a click twice.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub log_seq: u64,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+impl AppData {
+    pub const DEFAULT_COUNTER: &'static str = "default";
+
+    /// The days of counter `name`, if it has any.
+    pub fn counter(&self, name: &str) -> Option<&BTreeMap<String, DayCounts>> {
+        if name == Self::DEFAULT_COUNTER {
+            Some(&self.days)
+        } else {
+            self.counters.get(name)
+        }
+    }
+
+    /// The days of counter `name`, creating it if needed.
+    pub fn counter_mut(&mut self, name: &str) -> &mut BTreeMap<String, DayCounts> {
+        if name == Self::DEFAULT_COUNTER {
+            &mut self.days
+        } else {
+            self.counters.entry(name.to_string()).or_default()
+        }
+    }
+
+    /// Folds a `counters.default` entry, as a hand-written file might
+    /// contain, into `days`. Days already in `days` win.
+    pub fn normalize_counters(&mut self) {
+        if let Some(days) = self.counters.remove(Self::DEFAULT_COUNTER) {
+            for (date, counts) in days {
+                self.days.entry(date).or_insert(counts);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TodayQuery {
+    pub now: Option<String>,
+    pub counter: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CounterQuery {
+    pub counter: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClickQuery {
+    pub counter: Option<String>,
+    /// `stats` embeds the recomputed stats in the response.
+    pub include: Option<String>,
+    /// `true` works out the click's result without counting it.
+    pub dry_run: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    pub at: Option<String>,
+    pub weeks: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChartQuery {
+    pub view: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    pub mode: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CsvQuery {
+    pub delimiter: Option<String>,
+    pub date_format: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportResponse {
+    pub mode: &'static str,
+    pub days_imported: usize,
+    pub total_days: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WeekNoteRequest {
+    pub note: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WeekNoteResponse {
+    pub week: String,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SyncRequest {
+    pub data: AppData,
+    pub version: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncResponse {
+    pub data: AppData,
+    pub version: u64,
+    pub peer_version: u64,
+    pub days_changed: usize,
+}
+
+/// Body of `POST /api/reset`; `confirm` must be exactly `"RESET"`.
+#[derive(Debug, Deserialize)]
+pub struct ResetRequest {
+    #[serde(default)]
+    pub confirm: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResetResponse {
+    /// Days removed across all counters.
+    pub days_removed: usize,
+}
+
+/// Exact totals for `PUT /api/day/{date}`, in units like the responses.
+#[derive(Debug, Deserialize)]
+pub struct SetDayRequest {
+    pub add: u64,
+    pub sub: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetDayQuery {
+    pub allow_future: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DayTagsRequest {
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DayTagsResponse {
+    pub date: String,
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ClickRequest {
     pub action: String,
+    /// Units to add or subtract, in multiples of `1/APP_UNIT_SCALE`.
+    /// Defaults to one unit.
+    pub amount: Option<f64>,
+    #[serde(default)]
+    pub confirm: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Deserialize)]
+pub struct ClickPreviewRequest {
+    pub action: String,
+    pub amount: Option<f64>,
+    /// Day to preview, `YYYY-MM-DD`. Defaults to today.
+    pub date: Option<String>,
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubConfirmForm {
+    pub confirm: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DailyCountsResponse {
     pub date: String,
     pub add_count: u64,
     pub sub_count: u64,
     pub net: i64,
+    pub clicks_per_hour: Option<f64>,
+    /// The daily net target, if one is set.
+    pub target: Option<u64>,
+    /// `net / target`, so `1.0` means the target was reached. Null without a
+    /// target or when it is 0.
+    pub progress: Option<f64>,
+    /// Counts for custom actions, only included once the day has any.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub actions: BTreeMap<String, u64>,
+    /// When the response was computed, only included for `?now=1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_time: Option<String>,
+    /// Set for `?dry_run=true` clicks, which counted nothing. Only included
+    /// then.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+/// `POST /api/click?include=stats`: the click's result and the stats it
+/// leads to, saving a second request.
+#[derive(Debug, Serialize)]
+pub struct ClickWithStatsResponse {
+    pub today: DailyCountsResponse,
+    pub stats: StatsResponse,
 }
 
 #[derive(Debug, Serialize)]
@@ -31,6 +347,7 @@ pub struct DailyPoint {
     pub add_count: u64,
     pub sub_count: u64,
     pub net: i64,
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -41,6 +358,154 @@ pub struct WeeklyPoint {
     pub add_count: u64,
     pub sub_count: u64,
     pub net: i64,
+    pub note: Option<String>,
+    /// Today's net, set only on the current week.
+    pub today_contribution: Option<i64>,
+}
+
+/// Runs of consecutive days with a positive net. A day with no entry breaks
+/// a run like a non-positive one; with `APP_STREAK_SKIP_WEEKENDS` weekends are
+/// skipped over instead.
+#[derive(Debug, Serialize)]
+pub struct StreakInfo {
+    /// The run ending today, or yesterday while today isn't positive yet.
+    pub current_streak: u64,
+    pub longest_streak: u64,
+}
+
+/// Highest and lowest daily net over all recorded days, ties going to the
+/// earliest date. All `None` when nothing has been recorded.
+#[derive(Debug, Serialize)]
+pub struct BestWorst {
+    pub best_date: Option<String>,
+    pub best_net: Option<i64>,
+    pub worst_date: Option<String>,
+    pub worst_net: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MonthlyPoint {
+    /// `YYYY-MM`.
+    pub month: String,
+    pub add_count: u64,
+    pub sub_count: u64,
+    pub net: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WeekResponse {
+    pub week: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub add: u64,
+    pub sub: u64,
+    pub net: i64,
+    pub days_counted: u8,
+    pub note: Option<String>,
+    pub daily: Vec<DailyPoint>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompareWeekQuery {
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompareWeekResponse {
+    pub offset: u32,
+    pub current: WeekResponse,
+    pub previous: WeekResponse,
+    pub net_delta: i64,
+    /// `net_delta` relative to the earlier week's net; `null` when that was 0.
+    pub percent_change: Option<f64>,
+    /// True while the current week is still in progress.
+    pub current_partial: bool,
+    /// Current net minus the earlier week's net scaled to the days counted so
+    /// far, for a like-for-like comparison mid-week.
+    pub prorated_net_delta: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MilestoneQuery {
+    pub target: Option<f64>,
+    pub metric: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MilestoneResponse {
+    pub metric: &'static str,
+    pub current: i64,
+    pub target: i64,
+    /// Mean per day over the last 30 days.
+    pub daily_rate: f64,
+    /// `null` when the rate is zero or negative.
+    pub projected_date: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RangeQuery {
+    pub from: String,
+    pub to: String,
+}
+
+/// Optional inclusive bounds for `GET /api/days`.
+#[derive(Debug, Deserialize)]
+pub struct DaysQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub counter: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+/// One page of `GET /api/history`, oldest day first.
+#[derive(Debug, Serialize)]
+pub struct HistoryResponse {
+    /// Recorded days in all pages.
+    pub total: usize,
+    pub offset: usize,
+    /// The page size actually used, after clamping.
+    pub limit: usize,
+    pub has_more: bool,
+    /// Offset of the next page; null on the last one.
+    pub next_offset: Option<usize>,
+    pub days: Vec<DailyPoint>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HeatmapQuery {
+    /// Calendar year; the current one when absent.
+    pub year: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistogramQuery {
+    pub bins: Option<usize>,
+    pub metric: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistogramBin {
+    /// Inclusive lower edge.
+    pub lo: i64,
+    /// Exclusive upper edge.
+    pub hi: i64,
+    pub count: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistogramResponse {
+    pub metric: &'static str,
+    pub bins: Vec<HistogramBin>,
+    /// `null` when no recorded day is in range.
+    pub min: Option<i64>,
+    pub max: Option<i64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -50,11 +515,187 @@ pub struct WeeklyAveragePoint {
     pub avg_add: f64,
     pub avg_sub: f64,
     pub avg_net: f64,
+    /// Population standard deviation and median of the daily net over the
+    /// `days_counted` days (zero-click days included); 0 when none are.
+    pub stddev_net: f64,
+    pub median_net: f64,
+}
+
+/// Mean daily net over the 7 days ending on `date`.
+#[derive(Debug, Serialize)]
+pub struct MovingAveragePoint {
+    pub date: String,
+    pub avg_net: f64,
+}
+
+/// Net of every day up to and including `date`.
+#[derive(Debug, Serialize)]
+pub struct CumulativePoint {
+    pub date: String,
+    pub total_net: i64,
+}
+
+/// A run of days without any clicks, both ends inclusive.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Gap {
+    pub start: String,
+    pub end: String,
+    /// Days in the run.
+    pub length: u64,
 }
 
 #[derive(Debug, Serialize)]
 pub struct StatsResponse {
     pub last_7_days: Vec<DailyPoint>,
+    /// Lowest and highest daily net within `last_7_days`; ties go to the
+    /// earliest date.
+    pub min_net: i64,
+    pub min_net_date: String,
+    pub max_net: i64,
+    pub max_net_date: String,
     pub weekly_totals: Vec<WeeklyPoint>,
     pub weekly_averages: Vec<WeeklyAveragePoint>,
+    /// Calendar months up to and including the current one, oldest first.
+    pub monthly_totals: Vec<MonthlyPoint>,
+    /// Trailing 7-day average of daily net for the last 30 days, oldest
+    /// first. The window always spans 7 days: missing days, including those
+    /// before the first recorded one, count as zero.
+    pub moving_avg_7: Vec<MovingAveragePoint>,
+    /// Running total of daily net for the last 30 days, oldest first. Each
+    /// point sums all history up to that day, so it starts from whatever
+    /// came before the window; days before the first recorded one are 0.
+    pub cumulative: Vec<CumulativePoint>,
+    /// Dates in `last_7_days` whose net reached the daily target; empty when
+    /// no target is set.
+    pub target_met: Vec<String>,
+    pub streaks: StreakInfo,
+    pub best_worst: BestWorst,
+    /// Share of the recorded days before today whose net today's net beats,
+    /// from 0 to 100 with ties counting half. `null` until there is at least
+    /// one such day.
+    pub today_percentile: Option<f64>,
+    /// Days since the last day with any clicks, up to today: 0 when today
+    /// has some. `null` when nothing has been recorded yet.
+    pub days_since_last_activity: Option<u64>,
+    /// Runs of days without clicks among the 30 days before today, oldest
+    /// first. A run reaching past the window is cut at its start; days
+    /// before the first recorded activity don't count.
+    pub recent_gaps: Vec<Gap>,
+    /// Set when `APP_MAX_STATS_POINTS` cut a series short.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DataStampResponse {
+    pub modified: Option<String>,
+    pub sha256: String,
+    pub version: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatusResponse {
+    pub persist_latency: LatencySummary,
+    pub load_warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigResponse {
+    pub add_label: String,
+    pub sub_label: String,
+    pub confirm_sub: bool,
+    pub unit_scale: u64,
+    pub count_mode: CountMode,
+    pub week_start: WeekStart,
+    /// The `TZ` day keys are computed in, if set.
+    pub accounting_timezone: Option<String>,
+    pub accounting_utc_offset: String,
+    pub display_utc_offset: String,
+    pub title: String,
+    pub daily_goal: Option<u64>,
+    pub maintenance_message: Option<String>,
+    pub maintenance_read_only: bool,
+}
+
+/// Body of `PUT /api/target`, in units; null clears the target.
+#[derive(Debug, Deserialize)]
+pub struct TargetRequest {
+    pub target: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TargetResponse {
+    pub target: Option<u64>,
+}
+
+/// The live maintenance banner, also the body of
+/// `POST /api/maintenance/message`. An empty or missing message clears it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MaintenanceStatus {
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Reject writes with `503` while the message is set.
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WhoamiResponse {
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    /// The `TZ` the server runs with, if set.
+    pub timezone: Option<String>,
+    pub utc_offset: String,
+    pub today: String,
+    pub day_offset_hours: u32,
+}
+
+/// Settings chosen in the first-run setup and stored in `config.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetupRequest {
+    pub title: Option<String>,
+    /// UTC offset used as the display timezone.
+    pub timezone: Option<String>,
+    pub daily_goal: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    pub status: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SetupStatusResponse {
+    pub is_fresh: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SetupResponse {
+    pub config_path: String,
+    pub restart_required: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrafanaQueryRequest {
+    pub range: GrafanaRange,
+    #[serde(default)]
+    pub targets: Vec<GrafanaTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrafanaRange {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrafanaTarget {
+    #[serde(default)]
+    pub target: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GrafanaSeries {
+    pub target: String,
+    /// `[value, unix_ms]` pairs, as the JSON datasource expects.
+    pub datapoints: Vec<(f64, i64)>,
 }
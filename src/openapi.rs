@@ -0,0 +1,182 @@
+//! The hand-maintained OpenAPI description served at `/api/openapi.json`.
+//! When a request or response type in `models.rs` changes, update the
+//! matching schema in `openapi.json`; the tests below catch drift in the
+//! response types.
+
+pub const DOCUMENT: &str = include_str!("openapi.json");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::LatencySummary;
+    use crate::models::{
+        AppData, ClickWithStatsResponse, ConfigResponse, CountMode, DailyCountsResponse,
+        DataStampResponse, DayCounts, MaintenanceStatus, StatusResponse, TargetResponse, WeekStart,
+        WhoamiResponse,
+    };
+    use crate::stats::{
+        CountMetric, StatsOptions, build_stats_at, compare_week_at, histogram_at, history_page,
+        milestone_at,
+    };
+    use chrono::NaiveDate;
+    use serde::Serialize;
+    use serde_json::Value;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    fn document() -> Value {
+        serde_json::from_str(DOCUMENT).expect("openapi.json is valid JSON")
+    }
+
+    /// Asserts that `value` serializes to exactly the properties `schema`
+    /// declares, recursing into `$ref`ed object properties.
+    fn assert_matches(doc: &Value, schema: &str, value: &impl Serialize) {
+        let value = serde_json::to_value(value).unwrap();
+        check(doc, schema, &value);
+    }
+
+    fn check(doc: &Value, schema: &str, value: &Value) {
+        let properties = doc["components"]["schemas"][schema]["properties"]
+            .as_object()
+            .unwrap_or_else(|| panic!("schema {schema} is missing"));
+        let declared: BTreeSet<_> = properties.keys().cloned().collect();
+        let actual: BTreeSet<_> = value.as_object().unwrap().keys().cloned().collect();
+        assert_eq!(actual, declared, "{schema} drifted from its model");
+
+        for (key, property) in properties {
+            let reference = property["$ref"]
+                .as_str()
+                .or_else(|| property["items"]["$ref"].as_str());
+            let (Some(reference), Some(field)) = (reference, value.get(key)) else {
+                continue;
+            };
+            let nested = reference.rsplit('/').next().unwrap();
+            match field {
+                Value::Array(items) => items.iter().for_each(|item| check(doc, nested, item)),
+                Value::Object(_) => check(doc, nested, field),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn document_lists_the_core_paths() {
+        let doc = document();
+        for path in ["/today", "/click", "/stats", "/week/{label}", "/sync"] {
+            assert!(doc["paths"].get(path).is_some(), "missing {path}");
+        }
+    }
+
+    #[test]
+    fn response_schemas_match_the_models() {
+        let doc = document();
+        let today = NaiveDate::from_ymd_opt(2026, 1, 7).unwrap();
+        let data = AppData::default();
+
+        let counts = DailyCountsResponse {
+            date: today.to_string(),
+            add_count: 1,
+            sub_count: 0,
+            net: 1,
+            clicks_per_hour: Some(0.5),
+            target: Some(4),
+            progress: Some(0.25),
+            actions: BTreeMap::from([("jump".to_string(), 2)]),
+            server_time: Some("2026-01-07T10:00:00+00:00".to_string()),
+            dry_run: true,
+        };
+        assert_matches(&doc, "DailyCountsResponse", &counts);
+        assert_matches(
+            &doc,
+            "StatsResponse",
+            &build_stats_at(today, &data, &StatsOptions::default()),
+        );
+        assert_matches(
+            &doc,
+            "ClickWithStatsResponse",
+            &ClickWithStatsResponse {
+                today: counts,
+                stats: build_stats_at(today, &data, &StatsOptions::default()),
+            },
+        );
+        assert_matches(
+            &doc,
+            "MilestoneResponse",
+            &milestone_at(today, &data, CountMetric::Add, 10),
+        );
+        let mut days = AppData::default();
+        days.days.insert(today.to_string(), DayCounts::new(3, 1));
+        assert_matches(
+            &doc,
+            "HistogramResponse",
+            &histogram_at(&days, CountMetric::Net, None, None, 10),
+        );
+        assert_matches(&doc, "HistoryResponse", &history_page(&days, 0, None));
+        assert_matches(
+            &doc,
+            "CompareWeekResponse",
+            &compare_week_at(today, &data, 1, WeekStart::Monday),
+        );
+        assert_matches(
+            &doc,
+            "DataStampResponse",
+            &DataStampResponse {
+                modified: None,
+                sha256: String::new(),
+                version: 0,
+            },
+        );
+        assert_matches(
+            &doc,
+            "StatusResponse",
+            &StatusResponse {
+                persist_latency: LatencySummary {
+                    count: 0,
+                    p50_ms: None,
+                    p95_ms: None,
+                    p99_ms: None,
+                },
+                load_warnings: Vec::new(),
+            },
+        );
+        assert_matches(
+            &doc,
+            "ConfigResponse",
+            &ConfigResponse {
+                add_label: String::new(),
+                sub_label: String::new(),
+                confirm_sub: false,
+                unit_scale: 1,
+                count_mode: CountMode::Split,
+                week_start: WeekStart::Sunday,
+                accounting_timezone: None,
+                accounting_utc_offset: String::new(),
+                display_utc_offset: String::new(),
+                title: String::new(),
+                daily_goal: Some(10),
+                maintenance_message: None,
+                maintenance_read_only: false,
+            },
+        );
+        assert_matches(&doc, "Target", &TargetResponse { target: Some(10) });
+        assert_matches(
+            &doc,
+            "MaintenanceStatus",
+            &MaintenanceStatus {
+                message: Some("Back soon".to_string()),
+                read_only: true,
+            },
+        );
+        assert_matches(
+            &doc,
+            "WhoamiResponse",
+            &WhoamiResponse {
+                client_ip: None,
+                user_agent: None,
+                timezone: None,
+                utc_offset: String::new(),
+                today: today.to_string(),
+                day_offset_hours: 0,
+            },
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Per-client token buckets for the click endpoints (`APP_CLICK_RATE_LIMIT`).
+//! Each client address gets a bucket holding up to a minute's worth of
+//! clicks that refills continuously, so short bursts are fine but a script
+//! hammering the buttons is cut off with `429`.
+
+use crate::client::client_ip;
+use crate::errors::AppError;
+use crate::state::AppState;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderValue, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Buckets kept before full ones are dropped; a full bucket behaves exactly
+/// like a missing one, so pruning them loses nothing.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_minute: u32,
+    buckets: Mutex<HashMap<Option<IpAddr>, Bucket>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute: per_minute.max(1),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token for `client`, or returns how long until one is free.
+    /// Clients without a known address share one bucket.
+    pub fn check(&self, client: Option<IpAddr>, now: Instant) -> Result<(), Duration> {
+        let capacity = f64::from(self.per_minute);
+        let per_sec = capacity / 60.0;
+        let mut buckets = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            buckets.retain(|_, bucket| bucket.refilled(now, per_sec, capacity) < capacity);
+        }
+
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.tokens = bucket.refilled(now, per_sec, capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_sec))
+        }
+    }
+}
+
+impl Bucket {
+    fn refilled(&self, now: Instant, per_sec: f64, capacity: f64) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * per_sec).min(capacity)
+    }
+}
+
+/// Route layer for the click endpoints. Passes everything through when no
+/// limit is configured.
+pub async fn limit_clicks(
+    State(state): State<AppState>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = &state.rate_limiter else {
+        return next.run(request).await;
+    };
+    let peer = peer.map(|ConnectInfo(addr)| addr);
+    let client = client_ip(request.headers(), peer, state.config.trust_proxy);
+    match limiter.check(client, Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            let mut response =
+                AppError::too_many_requests("too many clicks, slow down").into_response();
+            let seconds = wait.as_secs_f64().ceil().max(1.0) as u64;
+            if let Ok(value) = HeaderValue::from_str(&seconds.to_string()) {
+                response.headers_mut().insert(header::RETRY_AFTER, value);
+            }
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_refill_per_client() {
+        let limiter = RateLimiter::new(3);
+        let start = Instant::now();
+        let alice: Option<IpAddr> = Some("203.0.113.1".parse().unwrap());
+        let bob: Option<IpAddr> = Some("203.0.113.2".parse().unwrap());
+
+        for _ in 0..3 {
+            assert!(limiter.check(alice, start).is_ok());
+        }
+        let wait = limiter.check(alice, start).unwrap_err();
+        assert_eq!(wait.as_secs_f64().round(), 20.0);
+        assert!(limiter.check(bob, start).is_ok());
+
+        // One token comes back every 20 seconds at 3 per minute.
+        let later = start + Duration::from_secs(41);
+        assert!(
+            limiter
+                .check(alice, start + Duration::from_secs(19))
+                .is_err()
+        );
+        assert!(limiter.check(alice, later).is_ok());
+        assert!(limiter.check(alice, later).is_ok());
+        assert!(limiter.check(alice, later).is_err());
+    }
+}
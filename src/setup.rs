@@ -0,0 +1,184 @@
+//! First-run setup. A fresh install can write a `config.toml` next to the
+//! data file once; it is read at startup and fills in settings that no
+//! environment variable sets. Only flat `key = value` lines are understood,
+//! which is all the setup step ever writes.
+
+use crate::config::parse_utc_offset;
+use crate::errors::AppError;
+use crate::models::SetupRequest;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::warn;
+
+const MAX_TITLE_CHARS: usize = 60;
+
+/// `config.toml` in the same directory as the data file.
+pub fn setup_path(data_path: &Path) -> PathBuf {
+    data_path.with_file_name("config.toml")
+}
+
+pub fn validate_setup(setup: &SetupRequest) -> Result<(), AppError> {
+    if let Some(title) = &setup.title {
+        let title = title.trim();
+        if title.is_empty() || title.chars().count() > MAX_TITLE_CHARS {
+            return Err(AppError::bad_request(format!(
+                "title must be 1 to {MAX_TITLE_CHARS} characters"
+            )));
+        }
+    }
+    if let Some(timezone) = &setup.timezone
+        && parse_utc_offset(timezone).is_none()
+    {
+        return Err(AppError::bad_request(
+            "timezone must be a UTC offset such as +02:00 or UTC",
+        ));
+    }
+    if setup.daily_goal == Some(0) {
+        return Err(AppError::bad_request("daily_goal must be positive"));
+    }
+    Ok(())
+}
+
+pub fn to_toml(setup: &SetupRequest) -> String {
+    let mut out =
+        String::from("# Written by the first-run setup. Environment variables take precedence.\n");
+    if let Some(title) = &setup.title {
+        out.push_str(&format!("title = {}\n", quote(title.trim())));
+    }
+    if let Some(timezone) = &setup.timezone {
+        out.push_str(&format!("timezone = {}\n", quote(timezone.trim())));
+    }
+    if let Some(goal) = setup.daily_goal {
+        out.push_str(&format!("daily_goal = {goal}\n"));
+    }
+    out
+}
+
+/// Reads the lines [`to_toml`] writes. Unknown keys, comments and lines that
+/// don't parse are skipped with a warning rather than failing startup.
+pub fn parse_toml(text: &str) -> (SetupRequest, Vec<String>) {
+    let mut setup = SetupRequest::default();
+    let mut warnings = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parsed = line.split_once('=').and_then(|(key, value)| {
+            let value = value.trim();
+            match key.trim() {
+                "title" => unquote(value).map(|title| setup.title = Some(title)),
+                "timezone" => unquote(value).map(|timezone| setup.timezone = Some(timezone)),
+                "daily_goal" => value.parse().ok().map(|goal| setup.daily_goal = Some(goal)),
+                _ => None,
+            }
+        });
+        if parsed.is_none() {
+            warnings.push(format!("config.toml line {}: ignored '{line}'", index + 1));
+        }
+    }
+    (setup, warnings)
+}
+
+/// Loads `path` if it exists. A missing file is not an error.
+pub async fn load_setup(path: &Path) -> (Option<SetupRequest>, Vec<String>) {
+    match fs::read_to_string(path).await {
+        Ok(text) => {
+            let (setup, warnings) = parse_toml(&text);
+            for warning in &warnings {
+                warn!("{warning}");
+            }
+            (Some(setup), warnings)
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => (None, Vec::new()),
+        Err(err) => {
+            let message = format!("failed to read {}: {err}", path.display());
+            warn!("{message}");
+            (None, vec![message])
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            _ => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn unquote(value: &str) -> Option<String> {
+    let inner = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => out.push(chars.next()?),
+            '"' => return None,
+            _ => out.push(ch),
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_round_trips() {
+        let setup = SetupRequest {
+            title: Some("Push-ups \"daily\"".to_string()),
+            timezone: Some("+02:00".to_string()),
+            daily_goal: Some(50),
+        };
+        let (parsed, warnings) = parse_toml(&to_toml(&setup));
+        assert!(warnings.is_empty());
+        assert_eq!(parsed, setup);
+    }
+
+    #[test]
+    fn unknown_lines_are_reported() {
+        let (parsed, warnings) =
+            parse_toml("title = \"Coffee\"\ncolor = \"red\"\ndaily_goal = lots\n");
+        assert_eq!(parsed.title.as_deref(), Some("Coffee"));
+        assert_eq!(parsed.daily_goal, None);
+        assert_eq!(warnings.len(), 2);
+    }
+
+    #[test]
+    fn setup_values_are_validated() {
+        let valid = SetupRequest {
+            title: Some("Coffee".to_string()),
+            timezone: Some("UTC".to_string()),
+            daily_goal: Some(3),
+        };
+        assert!(validate_setup(&valid).is_ok());
+        assert!(
+            validate_setup(&SetupRequest {
+                timezone: Some("Mars/Olympus".to_string()),
+                ..valid.clone()
+            })
+            .is_err()
+        );
+        assert!(
+            validate_setup(&SetupRequest {
+                daily_goal: Some(0),
+                ..valid.clone()
+            })
+            .is_err()
+        );
+        assert!(
+            validate_setup(&SetupRequest {
+                title: Some(" ".to_string()),
+                ..valid
+            })
+            .is_err()
+        );
+    }
+}
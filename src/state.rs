@@ -1,18 +1,428 @@
-use crate::models::AppData;
-use std::{path::PathBuf, sync::Arc};
-use tokio::sync::Mutex;
+use crate::clock::{Clock, SystemClock};
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::idempotency::IdempotencyCache;
+use crate::metrics::Metrics;
+use crate::models::DailyCountsResponse;
+use crate::models::{AppData, CountMode, MaintenanceStatus};
+use crate::ratelimit::RateLimiter;
+use crate::stats::day_for;
+use crate::storage::{ClickEvent, EventLog, JsonStorage, Storage, strip_archived};
+use crate::ui::{PageStamp, UiTemplate};
+use chrono::{DateTime, FixedOffset, NaiveDate, SecondsFormat};
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc, Mutex as StdMutex, RwLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{Mutex, MutexGuard, Notify, broadcast, watch};
+use tracing::warn;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub data_path: PathBuf,
+    /// Where writes go; the JSON file at `data_path` unless replaced with
+    /// [`AppState::with_storage`].
+    pub storage: Arc<dyn Storage>,
     pub data: Arc<Mutex<AppData>>,
+    pub config: Arc<Config>,
+    pub metrics: Arc<Metrics>,
+    pub clock: Arc<dyn Clock>,
+    /// Read-only history merged underneath the live data. Never written back.
+    pub archive: Arc<AppData>,
+    /// Bumped after every successful (or, when debounced, accepted) write so
+    /// clients can detect changes.
+    pub version: Arc<AtomicU64>,
+    /// Random-enough id of this process, so `version` numbers (which restart
+    /// at 0) from an earlier run never produce the same ETag.
+    pub instance: u64,
+    /// Problems found while loading data at startup.
+    pub load_warnings: Arc<Vec<String>>,
+    /// Neither a data file nor `config.toml` existed at startup, and setup
+    /// hasn't run since.
+    pub fresh_install: Arc<AtomicBool>,
+    /// Banner text and write gate, seeded from the config.
+    pub maintenance: Arc<RwLock<MaintenanceStatus>>,
+    /// Daily net target in stored steps, seeded from the config.
+    pub target: Arc<RwLock<Option<u64>>>,
+    /// The most recent click whose write succeeded, for `POST /api/undo`.
+    pub last_click: Arc<StdMutex<Option<LastClick>>>,
+    /// Revision of the newest click ever put in `last_click`, so a slow
+    /// write finishing late can't replace a newer click there.
+    pub last_click_revision: Arc<AtomicU64>,
+    /// Bumped under the data lock for every change handed to storage, so
+    /// snapshots written after the lock is released can be put in order.
+    pub revision: Arc<AtomicU64>,
+    /// Revision of the newest snapshot in storage. Held for the whole write,
+    /// so writes never overlap and an older snapshot never replaces a newer
+    /// one.
+    pub written: Arc<Mutex<u64>>,
+    /// Set when a debounced write is waiting for the flusher.
+    pub dirty: Arc<AtomicBool>,
+    /// Wakes [`crate::tasks::run_persist_flusher`] when `dirty` is set.
+    pub flush_requested: Arc<Notify>,
+    /// Throttles the click endpoints when `APP_CLICK_RATE_LIMIT` is set.
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Where clicks are appended when `APP_EVENT_LOG` is set. Every full
+    /// write of the data empties it.
+    pub event_log: Option<Arc<EventLog>>,
+    /// Markup for `/`, from `APP_UI_TEMPLATE` or built in.
+    pub ui_template: Arc<UiTemplate>,
+    /// Today's counts after each click, for `GET /api/ws` subscribers.
+    pub updates: broadcast::Sender<DailyCountsResponse>,
+    /// Flips to `true` when the server starts shutting down, so endless
+    /// responses like `/api/stats/stream` can finish.
+    pub shutdown: Arc<watch::Sender<bool>>,
+    /// Responses to recent `Idempotency-Key` clicks, and the keys still being
+    /// counted. Only held to look a key up or record it, never across a click.
+    pub idempotency: Arc<Mutex<IdempotencyCache>>,
+    /// When the page at `/` last changed, for its `Last-Modified`.
+    pub page_stamp: Arc<StdMutex<PageStamp>>,
+}
+
+/// A click that `POST /api/undo` can reverse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastClick {
+    pub counter: String,
+    pub date: NaiveDate,
+    pub action: String,
+    pub steps: u64,
 }
 
 impl AppState {
-    pub fn new(data_path: PathBuf, data: AppData) -> Self {
+    pub fn new(data_path: PathBuf, data: AppData, config: Config) -> Self {
+        let maintenance = MaintenanceStatus {
+            message: config.maintenance_message.clone(),
+            read_only: config.maintenance_read_only && config.maintenance_message.is_some(),
+        };
+        let rate_limiter = config
+            .click_rate_limit
+            .map(|per_minute| Arc::new(RateLimiter::new(per_minute)));
+        let ui_template = UiTemplate::load(config.ui_template.clone());
+        let target = config
+            .daily_goal
+            .map(|goal| goal.saturating_mul(config.unit_scale()));
         Self {
-            data_path,
+            storage: Arc::new(JsonStorage::new(data_path)),
             data: Arc::new(Mutex::new(data)),
+            config: Arc::new(config),
+            metrics: Arc::new(Metrics::default()),
+            clock: Arc::new(SystemClock),
+            archive: Arc::new(AppData::default()),
+            version: Arc::new(AtomicU64::new(0)),
+            instance: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos() as u64)
+                .unwrap_or_default(),
+            load_warnings: Arc::new(Vec::new()),
+            fresh_install: Arc::new(AtomicBool::new(false)),
+            maintenance: Arc::new(RwLock::new(maintenance)),
+            target: Arc::new(RwLock::new(target)),
+            last_click: Arc::new(StdMutex::new(None)),
+            last_click_revision: Arc::new(AtomicU64::new(0)),
+            revision: Arc::new(AtomicU64::new(0)),
+            written: Arc::new(Mutex::new(0)),
+            dirty: Arc::new(AtomicBool::new(false)),
+            flush_requested: Arc::new(Notify::new()),
+            rate_limiter,
+            event_log: None,
+            ui_template: Arc::new(ui_template),
+            updates: broadcast::channel(crate::live::UPDATE_BUFFER).0,
+            shutdown: Arc::new(watch::channel(false).0),
+            idempotency: Arc::new(Mutex::new(IdempotencyCache::default())),
+            page_stamp: Arc::new(StdMutex::new(PageStamp::new(SystemTime::now()))),
+        }
+    }
+
+    pub fn with_fresh_install(self, fresh: bool) -> Self {
+        self.fresh_install.store(fresh, Ordering::SeqCst);
+        self
+    }
+
+    pub fn with_load_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.load_warnings = Arc::new(warnings);
+        self
+    }
+
+    pub fn with_archive(mut self, archive: AppData) -> Self {
+        self.archive = Arc::new(archive);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Sends clicks to `log`. The data starts out dirty so the first
+    /// compaction folds in whatever was replayed from the log at startup.
+    pub fn with_event_log(mut self, log: EventLog) -> Self {
+        self.event_log = Some(Arc::new(log));
+        self.dirty.store(true, Ordering::SeqCst);
+        self
+    }
+
+    /// The current time in the accounting zone.
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        self.config.timezone.at(self.clock.now())
+    }
+
+    /// The current time in the display timezone, falling back to the
+    /// accounting zone. Only for showing to clients, never for day keys.
+    pub fn display_now(&self) -> DateTime<FixedOffset> {
+        match self.config.display_timezone {
+            Some(offset) => self.clock.now().with_timezone(&offset),
+            None => self.now(),
+        }
+    }
+
+    /// The calendar day clicks are currently counted towards, taking the
+    /// configured day offset into account.
+    pub fn today(&self) -> NaiveDate {
+        day_for(self.now().naive_local(), self.config.day_offset_hours)
+    }
+
+    /// Writes `data` to the data file and records how long the write took.
+    /// Days that still match the archive are left out of the live file.
+    ///
+    /// Durability contract: callers hold the data lock across the mutation and
+    /// this call, and roll the mutation back if it fails, so other requests
+    /// only ever observe state that has been written. Whether "written" means
+    /// "on disk" or "handed to the OS" depends on `APP_SYNC_WRITES`. Clicks
+    /// are the exception; see [`AppState::persist_click`].
+    ///
+    /// With `APP_PERSIST_DEBOUNCE_MS` set this only marks the data dirty and
+    /// returns `Ok`; the flusher task writes the latest state shortly after,
+    /// and [`AppState::flush`] writes it on shutdown. A failed debounced write
+    /// is logged and retried rather than rolled back. `data` must be the
+    /// locked `self.data`, since the flusher reads from there.
+    pub async fn persist(&self, data: &AppData) -> Result<(), AppError> {
+        if self.config.persist_debounce().is_some() {
+            self.mark_dirty();
+            return Ok(());
         }
+        self.write(data).await?;
+        self.version.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Records a click already applied to the locked `data` and returns its
+    /// revision, for [`AppState::remember_click`]. With an event log it is
+    /// appended there and the data is marked dirty for the next compaction.
+    /// When writing through, the lock is released before the write, which
+    /// goes out from a snapshot, so a slow disk doesn't hold up other clicks.
+    ///
+    /// Other requests may therefore see the click before it is written, so
+    /// the version is bumped before the lock is released and no `ETag` from
+    /// before the click matches a response that includes it. On error the
+    /// caller takes the click back out of the data and calls
+    /// [`AppState::click_rolled_back`].
+    pub async fn persist_click(
+        &self,
+        mut data: MutexGuard<'_, AppData>,
+        counter: &str,
+        date: NaiveDate,
+        action: &str,
+        steps: u64,
+    ) -> Result<u64, AppError> {
+        let revision = self.revision.fetch_add(1, Ordering::SeqCst) + 1;
+        let Some(log) = &self.event_log else {
+            if self.config.persist_debounce().is_some() {
+                self.mark_dirty();
+                return Ok(revision);
+            }
+            self.version.fetch_add(1, Ordering::SeqCst);
+            let snapshot = data.clone();
+            drop(data);
+            self.write_revision(revision, &snapshot).await?;
+            return Ok(revision);
+        };
+        let event = ClickEvent {
+            seq: data.log_seq + 1,
+            ts: self
+                .clock
+                .now()
+                .to_rfc3339_opts(SecondsFormat::Millis, true),
+            counter: (counter != AppData::DEFAULT_COUNTER).then(|| counter.to_string()),
+            date: date.to_string(),
+            action: action.to_string(),
+            amount: steps,
+            single: self.config.count_mode == CountMode::Single && action == "sub",
+        };
+        log.append(&event, self.config.sync_writes).await?;
+        data.log_seq = event.seq;
+        self.dirty.store(true, Ordering::SeqCst);
+        self.version.fetch_add(1, Ordering::SeqCst);
+        Ok(revision)
+    }
+
+    /// Finishes taking a failed click back out of `data`, which must be the
+    /// locked `self.data`. Bumps the version, since the data changed again,
+    /// and when writing through writes it once more: a snapshot another
+    /// click took while the failed one was in memory may have been written
+    /// meanwhile, and would otherwise keep it on disk until the next write.
+    pub async fn click_rolled_back(&self, data: &AppData) {
+        self.version.fetch_add(1, Ordering::SeqCst);
+        if self.event_log.is_some() || self.config.persist_debounce().is_some() {
+            return;
+        }
+        if let Err(err) = self.write(data).await {
+            warn!(
+                "failed to rewrite data after a failed click: {}",
+                err.message
+            );
+        }
+    }
+
+    /// Leaves the write to the flusher task.
+    fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
+        self.flush_requested.notify_one();
+        self.version.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Writes the current data if a debounced write is pending. On failure
+    /// the data stays dirty so the next flush tries again.
+    pub async fn flush(&self) -> Result<(), AppError> {
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let data = self.data.lock().await;
+        let result = self.write(&data).await;
+        if result.is_err() {
+            self.dirty.store(true, Ordering::SeqCst);
+        }
+        result
+    }
+
+    /// Writes `data`, which must be the locked `self.data`.
+    async fn write(&self, data: &AppData) -> Result<(), AppError> {
+        let revision = self.revision.fetch_add(1, Ordering::SeqCst) + 1;
+        self.write_revision(revision, data).await
+    }
+
+    /// Writes the snapshot `data` taken at `revision`, unless a newer one,
+    /// which already includes its changes, is in storage.
+    async fn write_revision(&self, revision: u64, data: &AppData) -> Result<(), AppError> {
+        let mut written = self.written.lock().await;
+        if *written >= revision {
+            return Ok(());
+        }
+        let started = Instant::now();
+        let sync = self.config.sync_writes;
+        let result = if self.archive.days.is_empty() {
+            self.storage.persist(data, sync).await
+        } else {
+            self.storage
+                .persist(&strip_archived(data, &self.archive), sync)
+                .await
+        };
+        self.metrics.persist_latency.record(started.elapsed());
+        // The snapshot now holds every logged click. If emptying the log
+        // fails, `log_seq` keeps a later replay from counting them twice.
+        if result.is_ok()
+            && let Some(log) = &self.event_log
+            && let Err(err) = log.truncate().await
+        {
+            warn!("failed to truncate event log: {}", err.message);
+        }
+        if result.is_ok() {
+            *written = revision;
+        }
+        result
+    }
+
+    pub fn maintenance(&self) -> MaintenanceStatus {
+        self.maintenance
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Replaces the maintenance status; a blank message clears it.
+    pub fn set_maintenance(&self, mut status: MaintenanceStatus) -> MaintenanceStatus {
+        status.message = status
+            .message
+            .map(|message| message.trim().to_string())
+            .filter(|message| !message.is_empty());
+        status.read_only &= status.message.is_some();
+        *self
+            .maintenance
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = status.clone();
+        status
+    }
+
+    /// The daily net target in stored steps.
+    pub fn target(&self) -> Option<u64> {
+        *self
+            .target
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Replaces the target. Bumps the version so cached stats and counts,
+    /// which include it, are refetched.
+    pub fn set_target(&self, target: Option<u64>) {
+        *self
+            .target
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = target;
+        self.version.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Fails with `503` and the maintenance message while writes are gated.
+    /// Mutating handlers call this before touching the data.
+    pub fn ensure_writable(&self) -> Result<(), AppError> {
+        match self.maintenance() {
+            MaintenanceStatus {
+                message: Some(message),
+                read_only: true,
+            } => Err(AppError::unavailable(message)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Makes `click`, written at `revision`, the one `POST /api/undo`
+    /// reverses, unless a newer click got there first.
+    pub fn remember_click(&self, click: LastClick, revision: u64) {
+        let mut last = self
+            .last_click
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if self
+            .last_click_revision
+            .fetch_max(revision, Ordering::SeqCst)
+            < revision
+        {
+            *last = Some(click);
+        }
+    }
+
+    /// Replaces the remembered last click, returning the previous one.
+    pub fn swap_last_click(&self, click: Option<LastClick>) -> Option<LastClick> {
+        let mut last = self
+            .last_click
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        std::mem::replace(&mut *last, click)
+    }
+
+    pub fn current_version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    /// Weak validator for responses that only change when the data does or
+    /// `day` rolls over. Read it while holding the data lock so it matches
+    /// the data the response is built from.
+    pub fn etag(&self, day: NaiveDate) -> String {
+        format!("W/\"{:x}-{}-{day}\"", self.instance, self.current_version())
     }
 }
@@ -1,75 +1,798 @@
-use crate::models::{AppData, DailyPoint, StatsResponse, WeeklyAveragePoint, WeeklyPoint};
-use chrono::{Datelike, Duration, Local, NaiveDate};
+use crate::clock::AccountingZone;
+use crate::config::Config;
+use crate::models::{
+    AppData, BestWorst, CompareWeekResponse, CumulativePoint, DailyPoint, DayCounts, Gap,
+    HistogramBin, HistogramResponse, HistoryResponse, MilestoneResponse, MonthlyPoint,
+    MovingAveragePoint, StatsResponse, StreakInfo, WeekResponse, WeekStart, WeeklyAveragePoint,
+    WeeklyPoint, net,
+};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc, Weekday};
 
-pub fn build_stats(data: &AppData) -> StatsResponse {
-    build_stats_at(Local::now().date_naive(), data)
+/// Knobs that change how stats are computed, derived from [`Config`].
+#[derive(Debug, Clone, Copy)]
+pub struct StatsOptions {
+    /// Weekends neither extend nor break a streak.
+    pub skip_weekends: bool,
+    /// Upper bound on the points in each `/api/stats` series.
+    pub max_points: usize,
+    /// Number of points in `weekly_totals` and `weekly_averages`.
+    pub weeks: usize,
+    /// Daily net target in stored steps, for `target_met`.
+    pub target: Option<u64>,
+    /// The day `weekly_totals` and `weekly_averages` weeks begin on.
+    pub week_start: WeekStart,
 }
 
-pub fn build_stats_at(today: NaiveDate, data: &AppData) -> StatsResponse {
-    const WEEK_COUNT: usize = 8;
+/// Weekly points in `/api/stats` when `?weeks=` is absent.
+pub const DEFAULT_WEEKS: usize = 8;
+/// Largest `?weeks=` that `/api/stats` accepts; bigger values are clamped.
+pub const MAX_WEEKS: usize = 52;
 
-    let mut last_7_days = Vec::with_capacity(7);
-    for offset in (0..7).rev() {
+impl StatsOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            skip_weekends: config.streak_skip_weekends,
+            max_points: config.max_stats_points(),
+            weeks: DEFAULT_WEEKS,
+            target: None,
+            week_start: config.week_start,
+        }
+    }
+
+    pub fn with_target(mut self, target: Option<u64>) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Overrides the weekly window, clamped to `1..=MAX_WEEKS`.
+    pub fn with_weeks(mut self, weeks: usize) -> Self {
+        self.weeks = weeks.clamp(1, MAX_WEEKS);
+        self
+    }
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        Self::from_config(&Config::default())
+    }
+}
+
+pub fn build_stats(
+    data: &AppData,
+    zone: AccountingZone,
+    day_offset_hours: u32,
+    options: &StatsOptions,
+) -> StatsResponse {
+    build_stats_at(
+        day_for(zone.at(Utc::now()).naive_local(), day_offset_hours),
+        data,
+        options,
+    )
+}
+
+/// The day a local timestamp counts towards when days start
+/// `day_offset_hours` after midnight.
+pub fn day_for(now: NaiveDateTime, day_offset_hours: u32) -> NaiveDate {
+    (now - Duration::hours(i64::from(day_offset_hours))).date()
+}
+
+/// Each series is clamped to its most recent `options.max_points` entries (at
+/// least one), and `truncated` reports whether that happened.
+pub fn build_stats_at(today: NaiveDate, data: &AppData, options: &StatsOptions) -> StatsResponse {
+    const DAY_COUNT: usize = 7;
+    const MONTH_COUNT: usize = 6;
+    const MOVING_AVG_COUNT: usize = 30;
+    const CUMULATIVE_COUNT: usize = 30;
+
+    let max_points = options.max_points.max(1);
+    let day_count = DAY_COUNT.min(max_points);
+    let weeks = options.weeks.max(1);
+    let week_count = weeks.min(max_points);
+    let month_count = MONTH_COUNT.min(max_points);
+    let moving_avg_count = MOVING_AVG_COUNT.min(max_points);
+    let cumulative_count = CUMULATIVE_COUNT.min(max_points);
+    let truncated = day_count < DAY_COUNT
+        || week_count < weeks
+        || month_count < MONTH_COUNT
+        || moving_avg_count < MOVING_AVG_COUNT
+        || cumulative_count < CUMULATIVE_COUNT;
+
+    let mut last_7_days = Vec::with_capacity(day_count);
+    for offset in (0..day_count).rev() {
         let date = today - Duration::days(offset as i64);
-        let counts = data.days.get(&date_key(date)).cloned().unwrap_or_default();
-        last_7_days.push(DailyPoint {
-            date: date.to_string(),
-            add_count: counts.add,
-            sub_count: counts.sub,
-            net: counts.add as i64 - counts.sub as i64,
-        });
+        last_7_days.push(daily_point(data, date));
     }
 
-    let current_week_start = week_start(today);
-    let mut weekly_totals = Vec::with_capacity(WEEK_COUNT);
-    let mut weekly_averages = Vec::with_capacity(WEEK_COUNT);
+    let target_met = match options.target {
+        Some(target) => last_7_days
+            .iter()
+            .filter(|point| i128::from(point.net) >= i128::from(target))
+            .map(|point| point.date.clone())
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let (min_point, max_point) = net_extremes(&last_7_days);
+    let (min_net, min_net_date) = (min_point.net, min_point.date.clone());
+    let (max_net, max_net_date) = (max_point.net, max_point.date.clone());
+
+    let current_week_start = week_start(today, options.week_start);
+    let mut weekly_totals = Vec::with_capacity(week_count);
+    let mut weekly_averages = Vec::with_capacity(week_count);
 
-    for offset in (0..WEEK_COUNT).rev() {
+    for offset in (0..week_count).rev() {
         let start = current_week_start - Duration::weeks(offset as i64);
-        let end = start + Duration::days(6);
-
-        let mut add_sum = 0u64;
-        let mut sub_sum = 0u64;
-        for day_offset in 0..7 {
-            let date = start + Duration::days(day_offset);
-            let counts = data.days.get(&date_key(date)).cloned().unwrap_or_default();
-            add_sum = add_sum.saturating_add(counts.add);
-            sub_sum = sub_sum.saturating_add(counts.sub);
-        }
-
-        let net = add_sum as i64 - sub_sum as i64;
-        let days_counted = if today < start {
-            0
-        } else if today > end {
-            7
+        let week = build_week_at(today, start, data);
+        let today_contribution = (offset == 0).then(|| daily_point(data, today).net);
+
+        let denom = if week.days_counted == 0 {
+            1.0
         } else {
-            (today - start).num_days() as u8 + 1
+            f64::from(week.days_counted)
         };
 
-        let denom = if days_counted == 0 { 1.0 } else { f64::from(days_counted) };
+        let counted_nets: Vec<i64> = week
+            .daily
+            .iter()
+            .take(usize::from(week.days_counted))
+            .map(|point| point.net)
+            .collect();
+        let (stddev_net, median_net) = spread(&counted_nets);
 
-        weekly_totals.push(WeeklyPoint {
-            week: week_label(start),
-            start_date: start.to_string(),
-            end_date: end.to_string(),
-            add_count: add_sum,
-            sub_count: sub_sum,
-            net,
+        weekly_averages.push(WeeklyAveragePoint {
+            week: week.week.clone(),
+            days_counted: week.days_counted,
+            avg_add: week.add as f64 / denom,
+            avg_sub: week.sub as f64 / denom,
+            avg_net: week.net as f64 / denom,
+            stddev_net,
+            median_net,
         });
 
-        weekly_averages.push(WeeklyAveragePoint {
-            week: week_label(start),
-            days_counted,
-            avg_add: add_sum as f64 / denom,
-            avg_sub: sub_sum as f64 / denom,
-            avg_net: net as f64 / denom,
+        weekly_totals.push(WeeklyPoint {
+            week: week.week,
+            start_date: week.start_date,
+            end_date: week.end_date,
+            add_count: week.add,
+            sub_count: week.sub,
+            net: week.net,
+            note: week.note,
+            today_contribution,
         });
     }
 
     StatsResponse {
         last_7_days,
+        min_net,
+        min_net_date,
+        max_net,
+        max_net_date,
         weekly_totals,
         weekly_averages,
+        monthly_totals: monthly_totals(today, data, month_count),
+        moving_avg_7: moving_average(today, data, moving_avg_count),
+        cumulative: cumulative_net(today, data, cumulative_count),
+        target_met,
+        streaks: StreakInfo {
+            current_streak: current_streak(today, data, options),
+            longest_streak: longest_streak(today, data, options),
+        },
+        best_worst: best_worst(data),
+        today_percentile: today_percentile(today, data),
+        days_since_last_activity: days_since_last_activity(today, data),
+        recent_gaps: recent_gaps(today, data),
+        truncated,
+    }
+}
+
+/// Population standard deviation and median of `values`, both 0 when empty.
+/// An even count takes the mean of the two middle values.
+fn spread(values: &[i64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let count = values.len() as f64;
+    let mean = values.iter().map(|value| *value as f64).sum::<f64>() / count;
+    let variance = values
+        .iter()
+        .map(|value| (*value as f64 - mean).powi(2))
+        .sum::<f64>()
+        / count;
+
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    let middle = sorted.len() / 2;
+    let median = if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] as f64 + sorted[middle] as f64) / 2.0
+    } else {
+        sorted[middle] as f64
+    };
+    (variance.sqrt(), median)
+}
+
+fn best_worst(data: &AppData) -> BestWorst {
+    let mut best: Option<(&String, i64)> = None;
+    let mut worst: Option<(&String, i64)> = None;
+    // Keys are in date order, so keeping the first of equal values keeps the
+    // earliest date.
+    for (date, counts) in &data.days {
+        let net = counts.net();
+        if best.is_none_or(|(_, max)| net > max) {
+            best = Some((date, net));
+        }
+        if worst.is_none_or(|(_, min)| net < min) {
+            worst = Some((date, net));
+        }
+    }
+    BestWorst {
+        best_date: best.map(|(date, _)| date.clone()),
+        best_net: best.map(|(_, net)| net),
+        worst_date: worst.map(|(date, _)| date.clone()),
+        worst_net: worst.map(|(_, net)| net),
+    }
+}
+
+/// Days before today that `recent_gaps` looks at. Today is still going, so
+/// it is never a gap.
+const GAP_WINDOW_DAYS: i64 = 30;
+
+/// A day counts as active once it has any click, custom actions included;
+/// an entry taken back to zero by undo doesn't.
+fn is_active(counts: &DayCounts) -> bool {
+    counts.add > 0 || counts.sub > 0 || counts.actions.values().any(|count| *count > 0)
+}
+
+fn days_since_last_activity(today: NaiveDate, data: &AppData) -> Option<u64> {
+    let last = data
+        .days
+        .range(..=date_key(today))
+        .rev()
+        .filter(|(_, counts)| is_active(counts))
+        .find_map(|(date, _)| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())?;
+    Some((today - last).num_days() as u64)
+}
+
+fn recent_gaps(today: NaiveDate, data: &AppData) -> Vec<Gap> {
+    let Some(first) = data
+        .days
+        .range(..date_key(today))
+        .filter(|(_, counts)| is_active(counts))
+        .find_map(|(date, _)| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+    else {
+        return Vec::new();
+    };
+    let gap = |start: NaiveDate, end: NaiveDate| Gap {
+        start: date_key(start),
+        end: date_key(end),
+        length: (end - start).num_days() as u64 + 1,
+    };
+
+    let mut gaps = Vec::new();
+    let mut open: Option<NaiveDate> = None;
+    let window_start = (today - Duration::days(GAP_WINDOW_DAYS)).max(first);
+    for date in window_start.iter_days().take_while(|date| *date < today) {
+        let active = data.days.get(&date_key(date)).is_some_and(is_active);
+        match (active, open) {
+            (false, None) => open = Some(date),
+            (true, Some(start)) => {
+                gaps.push(gap(start, date - Duration::days(1)));
+                open = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = open {
+        gaps.push(gap(start, today - Duration::days(1)));
+    }
+    gaps
+}
+
+/// Where today's net ranks among the nets of every recorded day before it.
+/// Today counts even without an entry, as a net of 0.
+fn today_percentile(today: NaiveDate, data: &AppData) -> Option<f64> {
+    let today_key = date_key(today);
+    let history: Vec<i64> = data
+        .days
+        .range(..today_key.clone())
+        .map(|(_, counts)| counts.net())
+        .collect();
+    let today_net = data.days.get(&today_key).map_or(0, DayCounts::net);
+    percentile_rank(today_net, &history)
+}
+
+/// Percentile rank of `value` in `population`: the percentage of values
+/// below it, plus half of those equal to it, so a value tied with every
+/// other one sits at 50. With a single value that gives 0, 50 or 100.
+/// `None` for an empty population.
+fn percentile_rank(value: i64, population: &[i64]) -> Option<f64> {
+    if population.is_empty() {
+        return None;
+    }
+    let below = population.iter().filter(|other| **other < value).count();
+    let equal = population.iter().filter(|other| **other == value).count();
+    Some((below as f64 + equal as f64 / 2.0) / population.len() as f64 * 100.0)
+}
+
+/// Trailing 7-day mean of daily net for the `count` days ending today, oldest
+/// first. Every window divides by 7, zero-filling missing days, so a new
+/// counter's first points ramp up instead of averaging over fewer days.
+fn moving_average(today: NaiveDate, data: &AppData, count: usize) -> Vec<MovingAveragePoint> {
+    const WINDOW: usize = 7;
+
+    let first = today - Duration::days((count + WINDOW - 2) as i64);
+    let nets: Vec<i64> = (0..count + WINDOW - 1)
+        .map(|offset| daily_point(data, first + Duration::days(offset as i64)).net)
+        .collect();
+    nets.windows(WINDOW)
+        .enumerate()
+        .map(|(index, window)| MovingAveragePoint {
+            date: date_key(first + Duration::days((index + WINDOW - 1) as i64)),
+            avg_net: window.iter().map(|net| *net as f64).sum::<f64>() / WINDOW as f64,
+        })
+        .collect()
+}
+
+/// Running net total for the `count` days ending today, oldest first. The
+/// first point already includes every recorded day before the window, and a
+/// day without clicks repeats the previous total.
+fn cumulative_net(today: NaiveDate, data: &AppData, count: usize) -> Vec<CumulativePoint> {
+    let first = today - Duration::days(count as i64 - 1);
+    let mut total = data
+        .days
+        .range(..date_key(first))
+        .fold(0i64, |total, (_, counts)| {
+            total.saturating_add(counts.net())
+        });
+    (0..count)
+        .map(|offset| {
+            let date = first + Duration::days(offset as i64);
+            total = total.saturating_add(daily_point(data, date).net);
+            CumulativePoint {
+                date: date_key(date),
+                total_net: total,
+            }
+        })
+        .collect()
+}
+
+/// Totals for the `count` calendar months ending with today's, oldest first.
+/// Days after `today` are left out; months without clicks report zeros.
+fn monthly_totals(today: NaiveDate, data: &AppData, count: usize) -> Vec<MonthlyPoint> {
+    let current = today.year() * 12 + today.month0() as i32;
+    let mut points: Vec<MonthlyPoint> = (0..count as i32)
+        .rev()
+        .map(|back| {
+            let index = current - back;
+            MonthlyPoint {
+                month: format!(
+                    "{:04}-{:02}",
+                    index.div_euclid(12),
+                    index.rem_euclid(12) + 1
+                ),
+                add_count: 0,
+                sub_count: 0,
+                net: 0,
+            }
+        })
+        .collect();
+
+    let Some(first) = points.first() else {
+        return points;
+    };
+    // Keys sort chronologically, so `YYYY-MM` is a lower bound for its month.
+    let range = first.month.clone()..=date_key(today);
+    for (key, counts) in data.days.range(range) {
+        let Some(point) = points
+            .iter_mut()
+            .find(|point| key.starts_with(&point.month))
+        else {
+            continue;
+        };
+        point.add_count = point.add_count.saturating_add(counts.add);
+        point.sub_count = point.sub_count.saturating_add(counts.sub);
+        point.net = point.net.saturating_add(counts.net());
+    }
+    points
+}
+
+/// The lowest and highest net points, preferring the earliest on ties.
+/// `points` must not be empty.
+fn net_extremes(points: &[DailyPoint]) -> (&DailyPoint, &DailyPoint) {
+    let mut min = &points[0];
+    let mut max = &points[0];
+    for point in &points[1..] {
+        if point.net < min.net {
+            min = point;
+        }
+        if point.net > max.net {
+            max = point;
+        }
+    }
+    (min, max)
+}
+
+/// Totals for the week starting at `start`, with one zero-filled point per day.
+/// `days_counted` is how many of its days have happened as of `today`.
+pub fn build_week_at(today: NaiveDate, start: NaiveDate, data: &AppData) -> WeekResponse {
+    let end = start + Duration::days(6);
+
+    let mut add_sum = 0u64;
+    let mut sub_sum = 0u64;
+    let mut daily = Vec::with_capacity(7);
+    for day_offset in 0..7 {
+        let point = daily_point(data, start + Duration::days(day_offset));
+        add_sum = add_sum.saturating_add(point.add_count);
+        sub_sum = sub_sum.saturating_add(point.sub_count);
+        daily.push(point);
+    }
+
+    let days_counted = if today < start {
+        0
+    } else if today > end {
+        7
+    } else {
+        (today - start).num_days() as u8 + 1
+    };
+
+    let label = week_label(start);
+    WeekResponse {
+        note: data.week_notes.get(&label).cloned(),
+        week: label,
+        start_date: start.to_string(),
+        end_date: end.to_string(),
+        add: add_sum,
+        sub: sub_sum,
+        net: net(add_sum, sub_sum),
+        days_counted,
+        daily,
+    }
+}
+
+/// Number of consecutive days with a positive net ending today. A day with no
+/// entry breaks the streak. Today only extends it once it turns positive, so
+/// an untouched morning doesn't reset yesterday's streak. With
+/// `skip_weekends`, Saturdays and Sundays are skipped over entirely.
+pub fn current_streak(today: NaiveDate, data: &AppData, options: &StatsOptions) -> u64 {
+    let mut streak = 0;
+    let mut date = today;
+    loop {
+        if options.skip_weekends && is_weekend(date) {
+            // Neutral day: neither extends nor breaks the streak.
+        } else if is_positive_day(data, date) {
+            streak += 1;
+        } else if date != today {
+            break;
+        }
+
+        match date.pred_opt() {
+            Some(previous) => date = previous,
+            None => break,
+        }
+    }
+    streak
+}
+
+/// The longest run of consecutive positive-net days up to `today`, under the
+/// same rules as [`current_streak`]: a missing or non-positive day ends a run,
+/// and with `skip_weekends` weekends are passed over.
+pub fn longest_streak(today: NaiveDate, data: &AppData, options: &StatsOptions) -> u64 {
+    let Some(mut date) = first_recorded_date(data) else {
+        return 0;
+    };
+    let (mut run, mut longest) = (0, 0);
+    while date <= today {
+        if options.skip_weekends && is_weekend(date) {
+            // Neutral day.
+        } else if is_positive_day(data, date) {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+        match date.succ_opt() {
+            Some(next) => date = next,
+            None => break,
+        }
+    }
+    longest
+}
+
+fn is_weekend(date: NaiveDate) -> bool {
+    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+fn is_positive_day(data: &AppData, date: NaiveDate) -> bool {
+    data.days
+        .get(&date_key(date))
+        .is_some_and(|counts| counts.add > counts.sub)
+}
+
+/// Clicks (adds plus subtracts) per hour elapsed since midnight. Returns `None`
+/// during the first few minutes of the day, where the tiny denominator would
+/// produce a meaningless spike.
+pub fn clicks_per_hour(counts: &DayCounts, time_of_day: NaiveTime) -> Option<f64> {
+    const MIN_ELAPSED_SECS: u32 = 15 * 60;
+
+    let elapsed = time_of_day.num_seconds_from_midnight();
+    if elapsed < MIN_ELAPSED_SECS {
+        return None;
+    }
+    let clicks = counts.add.saturating_add(counts.sub) as f64;
+    Some(clicks / (f64::from(elapsed) / 3600.0))
+}
+
+/// Compares the current week with the week `offset` weeks earlier.
+pub fn compare_week_at(
+    today: NaiveDate,
+    data: &AppData,
+    offset: u32,
+    first_day: WeekStart,
+) -> CompareWeekResponse {
+    let current_start = week_start(today, first_day);
+    let current = build_week_at(today, current_start, data);
+    let previous = build_week_at(
+        today,
+        current_start - Duration::weeks(i64::from(offset)),
+        data,
+    );
+
+    let net_delta = current.net.saturating_sub(previous.net);
+    let percent_change =
+        (previous.net != 0).then(|| net_delta as f64 / previous.net.abs() as f64 * 100.0);
+    let scale = f64::from(current.days_counted) / 7.0;
+    let prorated_net_delta = current.net as f64 - previous.net as f64 * scale;
+
+    CompareWeekResponse {
+        offset,
+        current_partial: current.days_counted < 7,
+        current,
+        previous,
+        net_delta,
+        percent_change,
+        prorated_net_delta,
+    }
+}
+
+/// Which count a total or series is built from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountMetric {
+    Add,
+    Sub,
+    Net,
+}
+
+impl CountMetric {
+    pub fn parse(value: Option<&str>) -> Option<Self> {
+        match value.map(str::trim) {
+            None | Some("") | Some("add") => Some(Self::Add),
+            Some("sub") => Some(Self::Sub),
+            Some("net") => Some(Self::Net),
+            Some(_) => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Sub => "sub",
+            Self::Net => "net",
+        }
+    }
+
+    pub fn value(self, counts: &DayCounts) -> i64 {
+        match self {
+            Self::Add => i64::try_from(counts.add).unwrap_or(i64::MAX),
+            Self::Sub => i64::try_from(counts.sub).unwrap_or(i64::MAX),
+            Self::Net => counts.net(),
+        }
+    }
+}
+
+/// Projects when the all-time total of `metric` reaches `target`, assuming
+/// the mean daily rate of the last 30 days (today included) continues.
+/// `projected_date` is today once the target is reached and `None` when the
+/// rate is zero or negative.
+pub fn milestone_at(
+    today: NaiveDate,
+    data: &AppData,
+    metric: CountMetric,
+    target: i64,
+) -> MilestoneResponse {
+    const RATE_WINDOW_DAYS: i64 = 30;
+
+    let mut current = 0i64;
+    let mut recent = 0i64;
+    let window_start = today - Duration::days(RATE_WINDOW_DAYS - 1);
+    for (key, counts) in &data.days {
+        let Ok(date) = NaiveDate::parse_from_str(key, "%Y-%m-%d") else {
+            continue;
+        };
+        if date > today {
+            continue;
+        }
+        let value = metric.value(counts);
+        current = current.saturating_add(value);
+        if date >= window_start {
+            recent = recent.saturating_add(value);
+        }
+    }
+
+    let daily_rate = recent as f64 / RATE_WINDOW_DAYS as f64;
+    let projected_date = if current >= target {
+        Some(today)
+    } else if daily_rate > 0.0 {
+        let days = (target.saturating_sub(current) as f64 / daily_rate).ceil() as i64;
+        Duration::try_days(days).and_then(|days| today.checked_add_signed(days))
+    } else {
+        None
+    };
+
+    MilestoneResponse {
+        metric: metric.as_str(),
+        current,
+        target,
+        daily_rate,
+        projected_date: projected_date.map(|date| date.to_string()),
+    }
+}
+
+/// Distribution of `metric` over the recorded days between `from` and `to`
+/// (inclusive, either open). Bins are equal-width, aligned to a multiple of
+/// their width and at most `max_bins` of them; the width is the smallest
+/// 1, 2 or 5 times a power of ten that fits, so edges read as round numbers.
+/// Widths stop at `i64::MAX`, where values spanning most of the `i64` range
+/// can take up to four bins, and edges beyond the range saturate.
+pub fn histogram_at(
+    data: &AppData,
+    metric: CountMetric,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    max_bins: usize,
+) -> HistogramResponse {
+    let values: Vec<i64> = data
+        .days
+        .iter()
+        .filter_map(|(key, counts)| {
+            let date = NaiveDate::parse_from_str(key, "%Y-%m-%d").ok()?;
+            let in_range = from.is_none_or(|from| date >= from) && to.is_none_or(|to| date <= to);
+            in_range.then(|| metric.value(counts))
+        })
+        .collect();
+
+    let (Some(&min), Some(&max)) = (values.iter().min(), values.iter().max()) else {
+        return HistogramResponse {
+            metric: metric.as_str(),
+            bins: Vec::new(),
+            min: None,
+            max: None,
+        };
+    };
+
+    let max_bins = max_bins.max(1) as u64;
+    let span = max.abs_diff(min).saturating_add(1);
+    let mut width = nice_width(i64::try_from(span.div_ceil(max_bins)).unwrap_or(i64::MAX));
+    let bins_needed = |width: i64| {
+        max.div_euclid(width)
+            .abs_diff(min.div_euclid(width))
+            .saturating_add(1)
+    };
+    while width < i64::MAX && bins_needed(width) > max_bins {
+        width = nice_width(width + 1);
+    }
+
+    let first = min.div_euclid(width);
+    let mut bins: Vec<HistogramBin> = (first..=max.div_euclid(width))
+        .map(|index| HistogramBin {
+            lo: index.saturating_mul(width),
+            hi: index.saturating_add(1).saturating_mul(width),
+            count: 0,
+        })
+        .collect();
+    for value in values {
+        bins[(value.div_euclid(width) - first) as usize].count += 1;
+    }
+
+    HistogramResponse {
+        metric: metric.as_str(),
+        bins,
+        min: Some(min),
+        max: Some(max),
+    }
+}
+
+/// The smallest of 1, 2 or 5 times a power of ten that is at least `raw`,
+/// or `i64::MAX` when that doesn't fit.
+fn nice_width(raw: i64) -> i64 {
+    let mut magnitude = 1i64;
+    loop {
+        for step in [1, 2, 5] {
+            let Some(width) = magnitude.checked_mul(step) else {
+                return i64::MAX;
+            };
+            if width >= raw {
+                return width;
+            }
+        }
+        let Some(next) = magnitude.checked_mul(10) else {
+            return i64::MAX;
+        };
+        magnitude = next;
+    }
+}
+
+/// The earliest recorded day, if any.
+pub fn first_recorded_date(data: &AppData) -> Option<NaiveDate> {
+    data.days
+        .keys()
+        .find_map(|key| NaiveDate::parse_from_str(key, "%Y-%m-%d").ok())
+}
+
+/// One point per day from `from` to `to` inclusive, zeros for missing days.
+pub fn daily_range(data: &AppData, from: NaiveDate, to: NaiveDate) -> Vec<DailyPoint> {
+    from.iter_days()
+        .take_while(|date| *date <= to)
+        .map(|date| daily_point(data, date))
+        .collect()
+}
+
+/// Page size of `/api/history` when none is given.
+pub const HISTORY_DEFAULT_LIMIT: usize = 100;
+/// Largest page `/api/history` returns; bigger limits are clamped to it.
+pub const HISTORY_MAX_LIMIT: usize = 500;
+
+/// A page of the recorded days, oldest first. Unlike [`daily_range`] there
+/// are no zero points for days without an entry.
+pub fn history_page(data: &AppData, offset: usize, limit: Option<usize>) -> HistoryResponse {
+    let limit = limit
+        .unwrap_or(HISTORY_DEFAULT_LIMIT)
+        .clamp(1, HISTORY_MAX_LIMIT);
+    let total = data.days.len();
+    let days = data
+        .days
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(|(date, counts)| DailyPoint {
+            date: date.clone(),
+            add_count: counts.add,
+            sub_count: counts.sub,
+            net: counts.net(),
+            tags: counts.tags.clone(),
+        })
+        .collect();
+    let next = offset.saturating_add(limit);
+    HistoryResponse {
+        total,
+        offset,
+        limit,
+        has_more: next < total,
+        next_offset: (next < total).then_some(next),
+        days,
+    }
+}
+
+/// Years `/api/heatmap` accepts.
+pub const HEATMAP_YEARS: std::ops::RangeInclusive<i32> = 1970..=2100;
+
+/// Every day of calendar year `year`, zeros for missing days. `None` for a
+/// year outside [`HEATMAP_YEARS`].
+pub fn heatmap_year(data: &AppData, year: i32) -> Option<Vec<DailyPoint>> {
+    if !HEATMAP_YEARS.contains(&year) {
+        return None;
+    }
+    let from = NaiveDate::from_ymd_opt(year, 1, 1)?;
+    let to = NaiveDate::from_ymd_opt(year, 12, 31)?;
+    Some(daily_range(data, from, to))
+}
+
+fn daily_point(data: &AppData, date: NaiveDate) -> DailyPoint {
+    let counts = data.days.get(&date_key(date)).cloned().unwrap_or_default();
+    DailyPoint {
+        date: date.to_string(),
+        add_count: counts.add,
+        sub_count: counts.sub,
+        net: counts.net(),
+        tags: counts.tags,
     }
 }
 
@@ -77,15 +800,38 @@ fn date_key(date: NaiveDate) -> String {
     date.format("%Y-%m-%d").to_string()
 }
 
-fn week_start(date: NaiveDate) -> NaiveDate {
-    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+/// The first day of the week containing `date`.
+pub fn week_start(date: NaiveDate, first_day: WeekStart) -> NaiveDate {
+    let since_first = (7 + date.weekday().num_days_from_monday()
+        - first_day.weekday().num_days_from_monday())
+        % 7;
+    date - Duration::days(i64::from(since_first))
 }
 
-fn week_label(date: NaiveDate) -> String {
-    let iso = date.iso_week();
+/// The ISO week label of the week starting on `start`. A week starting on
+/// Sunday takes the label of the ISO week its Monday through Saturday fall
+/// in, so labels stay unique and [`parse_week_label`] maps them back.
+fn week_label(start: NaiveDate) -> String {
+    let until_monday = (7 - start.weekday().num_days_from_monday()) % 7;
+    let iso = (start + Duration::days(i64::from(until_monday))).iso_week();
     format!("{}-W{:02}", iso.year(), iso.week())
 }
 
+/// Parses an ISO week label like `2026-W02` and returns the day that starts
+/// it: its Monday, or the Sunday before with [`WeekStart::Sunday`]. Returns
+/// `None` for malformed labels or weeks that don't exist in that year (e.g.
+/// `2025-W53`).
+pub fn parse_week_label(label: &str, first_day: WeekStart) -> Option<NaiveDate> {
+    let (year, week) = label.split_once("-W")?;
+    if year.len() != 4 || week.len() != 2 {
+        return None;
+    }
+    let year: i32 = year.parse().ok()?;
+    let week: u32 = week.parse().ok()?;
+    let monday = NaiveDate::from_isoywd_opt(year, week, chrono::Weekday::Mon)?;
+    Some(week_start(monday, first_day))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -97,10 +843,10 @@ mod tests {
         let two_days_ago = today - Duration::days(2);
         data.days.insert(
             two_days_ago.to_string(),
-            crate::models::DayCounts { add: 3, sub: 1 },
+            crate::models::DayCounts::new(3, 1),
         );
 
-        let stats = build_stats_at(today, &data);
+        let stats = build_stats_at(today, &data, &StatsOptions::default());
         assert_eq!(stats.last_7_days.len(), 7);
         let point = stats
             .last_7_days
@@ -112,11 +858,828 @@ mod tests {
         assert_eq!(point.net, 2);
     }
 
+    #[test]
+    fn stats_weekly_totals_carry_week_notes() {
+        let mut data = AppData::default();
+        let today = NaiveDate::from_ymd_opt(2026, 1, 5).unwrap();
+        data.week_notes
+            .insert("2026-W02".to_string(), "fresh start".to_string());
+
+        let stats = build_stats_at(today, &data, &StatsOptions::default());
+        let current = stats.weekly_totals.last().unwrap();
+        assert_eq!(current.week, "2026-W02");
+        assert_eq!(current.note.as_deref(), Some("fresh start"));
+        assert!(stats.weekly_totals[0].note.is_none());
+    }
+
+    #[test]
+    fn parse_week_label_validates_format() {
+        assert_eq!(
+            parse_week_label("2026-W02", WeekStart::Monday),
+            NaiveDate::from_ymd_opt(2026, 1, 5)
+        );
+        assert_eq!(
+            parse_week_label("2020-W53", WeekStart::Monday),
+            NaiveDate::from_ymd_opt(2020, 12, 28)
+        );
+        assert!(parse_week_label("2025-W53", WeekStart::Monday).is_none());
+        assert!(parse_week_label("2026-W2", WeekStart::Monday).is_none());
+        assert!(parse_week_label("2026W02", WeekStart::Monday).is_none());
+        assert!(parse_week_label("garbage", WeekStart::Monday).is_none());
+    }
+
+    #[test]
+    fn percentile_rank_counts_ties_as_half() {
+        let nets = [1, 2, 2, 3, 4, 5, 6, 7, 8, 10];
+        assert_eq!(percentile_rank(0, &nets), Some(0.0));
+        assert_eq!(percentile_rank(2, &nets), Some(20.0));
+        assert_eq!(percentile_rank(6, &nets), Some(65.0));
+        assert_eq!(percentile_rank(11, &nets), Some(100.0));
+        assert_eq!(percentile_rank(3, &[3]), Some(50.0));
+        assert_eq!(percentile_rank(4, &[3]), Some(100.0));
+        assert_eq!(percentile_rank(3, &[]), None);
+    }
+
+    #[test]
+    fn today_percentile_ranks_against_earlier_days() {
+        let today = NaiveDate::from_ymd_opt(2026, 1, 10).unwrap();
+        let percentile =
+            |data: &AppData| build_stats_at(today, data, &StatsOptions::default()).today_percentile;
+
+        let mut data = AppData::default();
+        data.days
+            .insert("2026-01-10".to_string(), DayCounts::new(4, 0));
+        assert_eq!(percentile(&data), None);
+
+        let earlier = [
+            ("2026-01-06", 1),
+            ("2026-01-07", 4),
+            ("2026-01-08", 9),
+            ("2026-01-09", 2),
+        ];
+        for (date, add) in earlier {
+            data.days.insert(date.to_string(), DayCounts::new(add, 0));
+        }
+        // A later day is not part of the history.
+        data.days
+            .insert("2026-01-11".to_string(), DayCounts::new(0, 0));
+        assert_eq!(percentile(&data), Some(62.5));
+
+        data.days.remove("2026-01-10");
+        assert_eq!(percentile(&data), Some(0.0));
+    }
+
+    #[test]
+    fn weeks_can_start_on_sunday() {
+        let mut data = AppData::default();
+        let today = NaiveDate::from_ymd_opt(2026, 1, 14).unwrap();
+        data.days
+            .insert("2026-01-10".to_string(), DayCounts::new(2, 0));
+        data.days
+            .insert("2026-01-11".to_string(), DayCounts::new(5, 0));
+
+        let weeks = |week_start| {
+            let options = StatsOptions {
+                week_start,
+                ..StatsOptions::default()
+            };
+            let totals = build_stats_at(today, &data, &options).weekly_totals;
+            totals[totals.len() - 2..]
+                .iter()
+                .map(|week| (week.week.clone(), week.start_date.clone(), week.net))
+                .collect::<Vec<_>>()
+        };
+
+        // Sunday the 11th closes the Monday week but opens the Sunday one.
+        assert_eq!(
+            weeks(WeekStart::Monday),
+            [
+                ("2026-W02".to_string(), "2026-01-05".to_string(), 7),
+                ("2026-W03".to_string(), "2026-01-12".to_string(), 0),
+            ]
+        );
+        assert_eq!(
+            weeks(WeekStart::Sunday),
+            [
+                ("2026-W02".to_string(), "2026-01-04".to_string(), 2),
+                ("2026-W03".to_string(), "2026-01-11".to_string(), 5),
+            ]
+        );
+
+        let sunday = NaiveDate::from_ymd_opt(2026, 1, 11).unwrap();
+        assert_eq!(week_start(sunday, WeekStart::Sunday), sunday);
+        assert_eq!(
+            parse_week_label("2026-W03", WeekStart::Sunday),
+            Some(sunday)
+        );
+        assert_eq!(
+            parse_week_label("2026-W03", WeekStart::Monday),
+            NaiveDate::from_ymd_opt(2026, 1, 12)
+        );
+    }
+
+    #[test]
+    fn week_summary_counts_days_so_far() {
+        let mut data = AppData::default();
+        let today = NaiveDate::from_ymd_opt(2026, 1, 7).unwrap();
+        data.days.insert(
+            "2026-01-05".to_string(),
+            crate::models::DayCounts::new(4, 1),
+        );
+        data.days.insert(
+            "2026-01-07".to_string(),
+            crate::models::DayCounts::new(1, 3),
+        );
+
+        let week = build_week_at(today, week_start(today, WeekStart::Monday), &data);
+        assert_eq!(week.week, "2026-W02");
+        assert_eq!(week.start_date, "2026-01-05");
+        assert_eq!(week.end_date, "2026-01-11");
+        assert_eq!(week.add, 5);
+        assert_eq!(week.sub, 4);
+        assert_eq!(week.net, 1);
+        assert_eq!(week.days_counted, 3);
+        assert_eq!(week.daily.len(), 7);
+        assert_eq!(week.daily[2].net, -2);
+
+        let previous = build_week_at(
+            today,
+            week_start(today, WeekStart::Monday) - Duration::weeks(1),
+            &data,
+        );
+        assert_eq!(previous.days_counted, 7);
+        assert_eq!(previous.net, 0);
+    }
+
+    #[test]
+    fn stats_window_follows_day_offset() {
+        let one_am = NaiveDate::from_ymd_opt(2026, 1, 6)
+            .unwrap()
+            .and_hms_opt(1, 0, 0)
+            .unwrap();
+        let today = day_for(one_am, 3);
+        assert_eq!(today, NaiveDate::from_ymd_opt(2026, 1, 5).unwrap());
+        assert_eq!(day_for(one_am, 0), one_am.date());
+
+        let stats = build_stats_at(today, &AppData::default(), &StatsOptions::default());
+        assert_eq!(stats.last_7_days.last().unwrap().date, "2026-01-05");
+        assert_eq!(stats.weekly_totals.last().unwrap().start_date, "2026-01-05");
+    }
+
+    #[test]
+    fn daily_range_fills_gaps_with_zeros() {
+        let mut data = AppData::default();
+        data.days
+            .insert("2026-01-02".to_string(), DayCounts::new(3, 1));
+        data.days
+            .insert("2026-01-09".to_string(), DayCounts::new(9, 0));
+        let from = NaiveDate::from_ymd_opt(2026, 1, 1).unwrap();
+        let to = NaiveDate::from_ymd_opt(2026, 1, 4).unwrap();
+
+        let points = daily_range(&data, from, to);
+        let dates: Vec<_> = points.iter().map(|point| point.date.as_str()).collect();
+        assert_eq!(
+            dates,
+            ["2026-01-01", "2026-01-02", "2026-01-03", "2026-01-04"]
+        );
+        assert_eq!(points[1].net, 2);
+        assert_eq!((points[0].add_count, points[3].net), (0, 0));
+        assert_eq!(daily_range(&data, from, from).len(), 1);
+    }
+
+    #[test]
+    fn spread_gives_population_stddev_and_median() {
+        assert_eq!(spread(&[]), (0.0, 0.0));
+        assert_eq!(spread(&[5]), (0.0, 5.0));
+        assert_eq!(spread(&[2, 4, 4, 4, 5, 5, 7, 9]), (2.0, 4.5));
+        assert_eq!(spread(&[3, -1, 1]), ((8.0f64 / 3.0).sqrt(), 1.0));
+    }
+
+    #[test]
+    fn weekly_spread_only_covers_counted_days() {
+        // Wednesday: Monday to Wednesday are counted, the rest of the week
+        // hasn't happened yet.
+        let today = NaiveDate::from_ymd_opt(2026, 1, 7).unwrap();
+        let mut data = AppData::default();
+        data.days
+            .insert("2026-01-05".to_string(), DayCounts::new(6, 0));
+        data.days
+            .insert("2026-01-07".to_string(), DayCounts::new(0, 3));
+        data.days
+            .insert("2026-01-09".to_string(), DayCounts::new(50, 0));
+        // Last week's nets are 1 through 7.
+        let last_monday = NaiveDate::from_ymd_opt(2025, 12, 29).unwrap();
+        for day in 0..7u64 {
+            let date = last_monday + Duration::days(day as i64);
+            data.days
+                .insert(date.to_string(), DayCounts::new(day + 1, 0));
+        }
+
+        let averages = build_stats_at(today, &data, &StatsOptions::default()).weekly_averages;
+        let current = averages.last().unwrap();
+        assert_eq!(current.days_counted, 3);
+        // Nets 6, 0 and -3; Friday's 50 is left out.
+        assert_eq!(current.median_net, 0.0);
+        assert_eq!(current.stddev_net, 14.0f64.sqrt());
+        let previous = &averages[averages.len() - 2];
+        assert_eq!(previous.median_net, 4.0);
+        assert_eq!(previous.stddev_net, 2.0);
+    }
+
+    #[test]
+    fn huge_counts_saturate_instead_of_wrapping() {
+        assert_eq!(net(u64::MAX, 0), i64::MAX);
+        assert_eq!(net(0, u64::MAX), i64::MIN);
+        assert_eq!(net(u64::MAX, u64::MAX), 0);
+        assert_eq!(net(u64::MAX, u64::MAX - 5), 5);
+        assert_eq!(net(i64::MAX as u64 + 1, 1), i64::MAX);
+
+        let today = NaiveDate::from_ymd_opt(2026, 1, 7).unwrap();
+        let mut data = AppData::default();
+        data.days
+            .insert("2026-01-06".to_string(), DayCounts::new(u64::MAX, 0));
+        data.days
+            .insert("2026-01-07".to_string(), DayCounts::new(u64::MAX, 1));
+        let stats = build_stats_at(today, &data, &StatsOptions::default());
+        assert_eq!(stats.max_net, i64::MAX);
+        assert_eq!(stats.weekly_totals.last().unwrap().net, i64::MAX);
+        assert_eq!(stats.monthly_totals.last().unwrap().net, i64::MAX);
+        assert!(stats.moving_avg_7.last().unwrap().avg_net > 0.0);
+
+        for metric in [CountMetric::Add, CountMetric::Net] {
+            let milestone = milestone_at(today, &data, metric, i64::MAX);
+            assert_eq!(milestone.current, i64::MAX);
+            assert_eq!(milestone.projected_date.as_deref(), Some("2026-01-07"));
+        }
+        let milestone = milestone_at(today, &data, CountMetric::Sub, i64::MAX);
+        assert_eq!(milestone.current, 1);
+
+        // Far below the target with a positive recent rate.
+        let mut data = AppData::default();
+        data.days
+            .insert("2025-01-01".to_string(), DayCounts::new(0, u64::MAX));
+        data.days
+            .insert("2026-01-07".to_string(), DayCounts::new(5, 0));
+        let milestone = milestone_at(today, &data, CountMetric::Net, i64::MAX);
+        assert_eq!(milestone.current, i64::MIN + 5);
+        assert!(milestone.projected_date.is_none());
+        let milestone = milestone_at(today, &data, CountMetric::Sub, i64::MAX);
+        assert_eq!(milestone.current, i64::MAX);
+    }
+
+    #[test]
+    fn heatmap_covers_every_day_of_the_year() {
+        let mut data = AppData::default();
+        data.days
+            .insert("2024-02-29".to_string(), DayCounts::new(4, 1));
+        data.days
+            .insert("2025-01-01".to_string(), DayCounts::new(1, 0));
+
+        let leap = heatmap_year(&data, 2024).unwrap();
+        assert_eq!(leap.len(), 366);
+        assert_eq!(leap[0].date, "2024-01-01");
+        assert_eq!(leap[59].date, "2024-02-29");
+        assert_eq!(leap[59].net, 3);
+        assert_eq!(leap[365].date, "2024-12-31");
+
+        let common = heatmap_year(&data, 2025).unwrap();
+        assert_eq!(common.len(), 365);
+        assert_eq!(common[0].net, 1);
+        assert_eq!(common[364].date, "2025-12-31");
+
+        assert!(heatmap_year(&data, 1).is_none());
+        assert!(heatmap_year(&data, 99999).is_none());
+    }
+
+    #[test]
+    fn weekly_window_follows_the_requested_week_count() {
+        let today = NaiveDate::from_ymd_opt(2026, 1, 7).unwrap();
+        let data = AppData::default();
+        let weeks = |options: StatsOptions| build_stats_at(today, &data, &options).weekly_totals;
+
+        assert_eq!(weeks(StatsOptions::default()).len(), DEFAULT_WEEKS);
+        let twelve = weeks(StatsOptions::default().with_weeks(12));
+        assert_eq!(twelve.len(), 12);
+        assert_eq!(twelve.last().unwrap().start_date, "2026-01-05");
+        assert_eq!(twelve[0].start_date, "2025-10-20");
+        assert_eq!(weeks(StatsOptions::default().with_weeks(0)).len(), 1);
+        assert_eq!(
+            weeks(StatsOptions::default().with_weeks(500)).len(),
+            MAX_WEEKS
+        );
+    }
+
+    #[test]
+    fn stats_series_are_capped_to_the_most_recent_points() {
+        let today = NaiveDate::from_ymd_opt(2026, 1, 7).unwrap();
+        assert!(!build_stats_at(today, &AppData::default(), &StatsOptions::default()).truncated);
+
+        let options = StatsOptions {
+            max_points: 3,
+            ..StatsOptions::default()
+        };
+        let stats = build_stats_at(today, &AppData::default(), &options);
+        assert!(stats.truncated);
+        assert_eq!(stats.last_7_days.len(), 3);
+        assert_eq!(stats.last_7_days[0].date, "2026-01-05");
+        assert_eq!(stats.last_7_days[2].date, "2026-01-07");
+        assert_eq!(stats.weekly_totals.len(), 3);
+        assert_eq!(stats.weekly_averages.len(), 3);
+        assert_eq!(stats.moving_avg_7.len(), 3);
+        assert_eq!(stats.moving_avg_7[2].date, "2026-01-07");
+        assert_eq!(stats.cumulative.len(), 3);
+        assert_eq!(stats.weekly_totals[2].start_date, "2026-01-05");
+    }
+
+    #[test]
+    fn histogram_handles_nets_across_the_whole_i64_range() {
+        let mut data = AppData::default();
+        data.days
+            .insert("2026-01-01".to_string(), DayCounts::new(0, u64::MAX));
+        data.days
+            .insert("2026-01-02".to_string(), DayCounts::new(u64::MAX, 0));
+        data.days
+            .insert("2026-01-03".to_string(), DayCounts::new(1, 0));
+
+        for max_bins in [1, 4, 10, 1000] {
+            let histogram = histogram_at(&data, CountMetric::Net, None, None, max_bins);
+            assert_eq!(
+                (histogram.min, histogram.max),
+                (Some(i64::MIN), Some(i64::MAX))
+            );
+            assert!(histogram.bins.len() <= max_bins.max(4), "{max_bins}");
+            assert_eq!(histogram.bins.iter().map(|bin| bin.count).sum::<u32>(), 3);
+            assert_eq!(histogram.bins[0].lo, i64::MIN);
+            assert!(
+                histogram
+                    .bins
+                    .windows(2)
+                    .all(|pair| pair[0].hi == pair[1].lo)
+            );
+        }
+
+        let histogram = histogram_at(&data, CountMetric::Add, None, None, 10);
+        assert_eq!(histogram.max, Some(i64::MAX));
+        assert_eq!(histogram.bins.last().unwrap().hi, i64::MAX);
+    }
+
+    #[test]
+    fn histogram_uses_round_bin_edges() {
+        assert_eq!(nice_width(1), 1);
+        assert_eq!(nice_width(3), 5);
+        assert_eq!(nice_width(7), 10);
+        assert_eq!(nice_width(11), 20);
+        assert_eq!(nice_width(6_000_000_000_000_000_000), i64::MAX);
+        assert_eq!(nice_width(i64::MAX), i64::MAX);
+
+        let mut data = AppData::default();
+        for (day, net) in [(1, -3i64), (2, 0), (3, 4), (4, 4), (5, 17)] {
+            let counts = if net < 0 {
+                DayCounts::new(0, net.unsigned_abs())
+            } else {
+                DayCounts::new(net as u64, 0)
+            };
+            data.days.insert(format!("2026-01-0{day}"), counts);
+        }
+
+        let histogram = histogram_at(&data, CountMetric::Net, None, None, 5);
+        assert_eq!((histogram.min, histogram.max), (Some(-3), Some(17)));
+        let bins: Vec<_> = histogram
+            .bins
+            .iter()
+            .map(|bin| (bin.lo, bin.hi, bin.count))
+            .collect();
+        assert_eq!(
+            bins,
+            [(-5, 0, 1), (0, 5, 3), (5, 10, 0), (10, 15, 0), (15, 20, 1)]
+        );
+        assert!(histogram.bins.len() <= 5);
+
+        let from = NaiveDate::from_ymd_opt(2026, 1, 2);
+        let to = NaiveDate::from_ymd_opt(2026, 1, 4);
+        let histogram = histogram_at(&data, CountMetric::Net, from, to, 10);
+        assert_eq!((histogram.min, histogram.max), (Some(0), Some(4)));
+        assert_eq!(histogram.bins.iter().map(|bin| bin.count).sum::<u32>(), 3);
+
+        let empty = histogram_at(&AppData::default(), CountMetric::Add, None, None, 10);
+        assert!(empty.bins.is_empty());
+        assert_eq!(empty.min, None);
+    }
+
+    #[test]
+    fn monthly_totals_cover_six_months_with_zeros() {
+        let today = NaiveDate::from_ymd_opt(2026, 2, 10).unwrap();
+        let mut data = AppData::default();
+        data.days
+            .insert("2026-02-01".to_string(), DayCounts::new(3, 1));
+        data.days
+            .insert("2026-02-09".to_string(), DayCounts::new(2, 0));
+        data.days
+            .insert("2026-02-11".to_string(), DayCounts::new(9, 0));
+        data.days
+            .insert("2025-09-30".to_string(), DayCounts::new(1, 4));
+        data.days
+            .insert("2025-08-31".to_string(), DayCounts::new(7, 0));
+
+        let months = build_stats_at(today, &data, &StatsOptions::default()).monthly_totals;
+        let labels: Vec<_> = months.iter().map(|point| point.month.as_str()).collect();
+        assert_eq!(
+            labels,
+            [
+                "2025-09", "2025-10", "2025-11", "2025-12", "2026-01", "2026-02"
+            ]
+        );
+        assert_eq!(months[0].net, -3);
+        assert_eq!(months[1].add_count, 0);
+        assert_eq!(months[5].add_count, 5);
+        assert_eq!(months[5].sub_count, 1);
+        assert_eq!(months[5].net, 4);
+    }
+
+    #[test]
+    fn moving_average_spans_seven_zero_filled_days() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 31).unwrap();
+        let mut data = AppData::default();
+        data.days
+            .insert("2026-03-01".to_string(), DayCounts::new(14, 0));
+        data.days
+            .insert("2026-03-02".to_string(), DayCounts::new(0, 7));
+        data.days
+            .insert("2026-03-25".to_string(), DayCounts::new(10, 3));
+        data.days
+            .insert("2026-03-31".to_string(), DayCounts::new(7, 0));
+
+        let series = build_stats_at(today, &data, &StatsOptions::default()).moving_avg_7;
+        assert_eq!(series.len(), 30);
+        assert_eq!(series[0].date, "2026-03-02");
+        assert_eq!(series[29].date, "2026-03-31");
+        // Only two days of history, but still divided by seven.
+        assert_eq!(series[0].avg_net, 1.0);
+        assert_eq!(series[6].avg_net, -1.0);
+        assert_eq!(series[7].avg_net, 0.0);
+        // 2026-03-25 through 2026-03-31.
+        assert_eq!(series[29].avg_net, 2.0);
+        assert_eq!(series[28].avg_net, 1.0);
+    }
+
+    #[test]
+    fn cumulative_net_carries_earlier_history_forward() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 31).unwrap();
+        let mut data = AppData::default();
+        data.days
+            .insert("2025-12-24".to_string(), DayCounts::new(10, 0));
+        data.days
+            .insert("2026-03-01".to_string(), DayCounts::new(5, 1));
+        data.days
+            .insert("2026-03-02".to_string(), DayCounts::new(3, 0));
+        data.days
+            .insert("2026-03-20".to_string(), DayCounts::new(0, 20));
+        data.days
+            .insert("2026-04-01".to_string(), DayCounts::new(50, 0));
+
+        let series = build_stats_at(today, &data, &StatsOptions::default()).cumulative;
+        assert_eq!(series.len(), 30);
+        assert_eq!(series[0].date, "2026-03-02");
+        // Everything through 2026-03-01, plus 2026-03-02 itself.
+        assert_eq!(series[0].total_net, 17);
+        assert_eq!(series[17].total_net, 17);
+        assert_eq!(series[18].date, "2026-03-20");
+        assert_eq!(series[18].total_net, -3);
+        // Tomorrow's clicks aren't counted yet.
+        assert_eq!(series[29].total_net, -3);
+
+        let empty = build_stats_at(today, &AppData::default(), &StatsOptions::default());
+        assert!(empty.cumulative.iter().all(|point| point.total_net == 0));
+    }
+
+    #[test]
+    fn gaps_and_days_since_last_activity() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 31).unwrap();
+        let mut data = AppData::default();
+        for date in [
+            "2026-02-10",
+            "2026-03-05",
+            "2026-03-06",
+            "2026-03-10",
+            "2026-03-28",
+        ] {
+            data.days.insert(date.to_string(), DayCounts::new(1, 0));
+        }
+        // Undone back to nothing, so still a gap.
+        data.days
+            .insert("2026-03-11".to_string(), DayCounts::new(0, 0));
+        let mut custom = DayCounts::default();
+        custom.actions.insert("jump".to_string(), 1);
+        data.days.insert("2026-03-12".to_string(), custom);
+
+        let stats = build_stats_at(today, &data, &StatsOptions::default());
+        assert_eq!(stats.days_since_last_activity, Some(3));
+        let gaps: Vec<_> = stats
+            .recent_gaps
+            .iter()
+            .map(|gap| (gap.start.as_str(), gap.end.as_str(), gap.length))
+            .collect();
+        assert_eq!(
+            gaps,
+            [
+                // Cut at the start of the 30-day window.
+                ("2026-03-01", "2026-03-04", 4),
+                ("2026-03-07", "2026-03-09", 3),
+                ("2026-03-11", "2026-03-11", 1),
+                ("2026-03-13", "2026-03-27", 15),
+                // Today doesn't count yet.
+                ("2026-03-29", "2026-03-30", 2),
+            ]
+        );
+
+        data.days
+            .insert("2026-03-31".to_string(), DayCounts::new(0, 1));
+        let stats = build_stats_at(today, &data, &StatsOptions::default());
+        assert_eq!(stats.days_since_last_activity, Some(0));
+
+        // Nothing before the first activity is a gap.
+        let mut new = AppData::default();
+        new.days
+            .insert("2026-03-27".to_string(), DayCounts::new(2, 0));
+        let stats = build_stats_at(today, &new, &StatsOptions::default());
+        assert_eq!(stats.days_since_last_activity, Some(4));
+        assert_eq!(
+            stats.recent_gaps,
+            [Gap {
+                start: "2026-03-28".to_string(),
+                end: "2026-03-30".to_string(),
+                length: 3,
+            }]
+        );
+
+        let empty = build_stats_at(today, &AppData::default(), &StatsOptions::default());
+        assert_eq!(empty.days_since_last_activity, None);
+        assert!(empty.recent_gaps.is_empty());
+    }
+
+    #[test]
+    fn target_met_flags_days_at_or_above_the_target() {
+        let today = NaiveDate::from_ymd_opt(2026, 1, 7).unwrap();
+        let mut data = AppData::default();
+        data.days
+            .insert("2026-01-02".to_string(), DayCounts::new(5, 0));
+        data.days
+            .insert("2026-01-05".to_string(), DayCounts::new(9, 1));
+        data.days
+            .insert("2026-01-07".to_string(), DayCounts::new(4, 0));
+        data.days
+            .insert("2026-01-06".to_string(), DayCounts::new(0, 2));
+        let met = |target| {
+            build_stats_at(today, &data, &StatsOptions::default().with_target(target)).target_met
+        };
+
+        assert!(met(None).is_empty());
+        assert_eq!(met(Some(5)), ["2026-01-02", "2026-01-05"]);
+        // Every day with a net of at least zero meets a zero target.
+        assert_eq!(met(Some(0)).len(), 6);
+    }
+
+    #[test]
+    fn best_and_worst_days_cover_all_history_with_earliest_ties() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        let mut data = AppData::default();
+        data.days
+            .insert("2025-06-01".to_string(), DayCounts::new(9, 1));
+        data.days
+            .insert("2025-07-01".to_string(), DayCounts::new(2, 6));
+        data.days
+            .insert("2026-01-10".to_string(), DayCounts::new(8, 0));
+        data.days
+            .insert("2026-02-01".to_string(), DayCounts::new(0, 4));
+        data.days
+            .insert("2026-02-20".to_string(), DayCounts::new(1, 1));
+
+        let extremes = build_stats_at(today, &data, &StatsOptions::default()).best_worst;
+        assert_eq!(extremes.best_date.as_deref(), Some("2025-06-01"));
+        assert_eq!(extremes.best_net, Some(8));
+        assert_eq!(extremes.worst_date.as_deref(), Some("2025-07-01"));
+        assert_eq!(extremes.worst_net, Some(-4));
+
+        let empty = build_stats_at(today, &AppData::default(), &StatsOptions::default());
+        let json = serde_json::to_value(&empty.best_worst).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "best_date": null,
+                "best_net": null,
+                "worst_date": null,
+                "worst_net": null,
+            })
+        );
+    }
+
+    #[test]
+    fn streaks_break_on_missing_and_non_positive_days() {
+        let today = NaiveDate::from_ymd_opt(2026, 1, 20).unwrap();
+        let mut data = AppData::default();
+        // Four positive days, then a gap on the 5th, then a zero day on the
+        // 8th, then an ongoing run of three up to today.
+        for day in ["2026-01-01", "2026-01-02", "2026-01-03", "2026-01-04"] {
+            data.days.insert(day.to_string(), DayCounts::new(2, 1));
+        }
+        data.days
+            .insert("2026-01-06".to_string(), DayCounts::new(1, 0));
+        data.days
+            .insert("2026-01-07".to_string(), DayCounts::new(1, 0));
+        data.days
+            .insert("2026-01-08".to_string(), DayCounts::new(1, 1));
+        for day in ["2026-01-18", "2026-01-19", "2026-01-20"] {
+            data.days.insert(day.to_string(), DayCounts::new(1, 0));
+        }
+
+        let streaks = build_stats_at(today, &data, &StatsOptions::default()).streaks;
+        assert_eq!(streaks.current_streak, 3);
+        assert_eq!(streaks.longest_streak, 4);
+
+        let later = NaiveDate::from_ymd_opt(2026, 1, 25).unwrap();
+        let streaks = build_stats_at(later, &data, &StatsOptions::default()).streaks;
+        assert_eq!(streaks.current_streak, 0);
+        assert_eq!(streaks.longest_streak, 4);
+
+        let mut zeros = AppData::default();
+        zeros
+            .days
+            .insert("2026-01-19".to_string(), DayCounts::new(0, 0));
+        let streaks = build_stats_at(today, &zeros, &StatsOptions::default()).streaks;
+        assert_eq!((streaks.current_streak, streaks.longest_streak), (0, 0));
+    }
+
+    #[test]
+    fn milestone_projects_from_the_recent_rate() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        let mut data = AppData::default();
+        // 100 adds long ago, then 3 a day for the last 10 days: 30 adds over
+        // the 30-day window, so a rate of 1 per day.
+        data.days
+            .insert("2025-01-01".to_string(), DayCounts::new(100, 0));
+        for offset in 0..10 {
+            let date = today - Duration::days(offset);
+            data.days.insert(date.to_string(), DayCounts::new(3, 1));
+        }
+
+        let add = milestone_at(today, &data, CountMetric::Add, 200);
+        assert_eq!(add.current, 130);
+        assert_eq!(add.daily_rate, 1.0);
+        assert_eq!(add.projected_date.as_deref(), Some("2026-05-10"));
+
+        let reached = milestone_at(today, &data, CountMetric::Add, 50);
+        assert_eq!(reached.projected_date.as_deref(), Some("2026-03-01"));
+
+        let mut idle = AppData::default();
+        idle.days
+            .insert("2025-01-01".to_string(), DayCounts::new(5, 0));
+        let stalled = milestone_at(today, &idle, CountMetric::Net, 10);
+        assert_eq!(stalled.daily_rate, 0.0);
+        assert_eq!(stalled.projected_date, None);
+    }
+
+    #[test]
+    fn clicks_per_hour_uses_time_since_midnight() {
+        let counts = crate::models::DayCounts::new(9, 3);
+        let ten_am = NaiveTime::from_hms_opt(10, 0, 0).unwrap();
+        assert_eq!(clicks_per_hour(&counts, ten_am), Some(1.2));
+
+        let just_after_midnight = NaiveTime::from_hms_opt(0, 5, 0).unwrap();
+        assert_eq!(clicks_per_hour(&counts, just_after_midnight), None);
+    }
+
+    #[test]
+    fn current_streak_counts_back_from_today() {
+        let mut data = AppData::default();
+        let today = NaiveDate::from_ymd_opt(2026, 1, 7).unwrap();
+        for (date, add, sub) in [
+            ("2026-01-03", 2, 0),
+            ("2026-01-04", 0, 1),
+            ("2026-01-05", 1, 0),
+            ("2026-01-06", 3, 1),
+        ] {
+            data.days
+                .insert(date.to_string(), crate::models::DayCounts::new(add, sub));
+        }
+
+        let options = StatsOptions::default();
+        assert_eq!(current_streak(today, &data, &options), 2);
+
+        data.days.insert(
+            "2026-01-07".to_string(),
+            crate::models::DayCounts::new(1, 0),
+        );
+        assert_eq!(current_streak(today, &data, &options), 3);
+
+        let later = NaiveDate::from_ymd_opt(2026, 1, 9).unwrap();
+        assert_eq!(current_streak(later, &data, &options), 0);
+    }
+
+    #[test]
+    fn current_streak_can_skip_weekends() {
+        let mut data = AppData::default();
+        // Thursday and Friday positive, an empty weekend, then Monday.
+        for date in ["2026-01-08", "2026-01-09", "2026-01-12"] {
+            data.days
+                .insert(date.to_string(), crate::models::DayCounts::new(1, 0));
+        }
+        let monday = NaiveDate::from_ymd_opt(2026, 1, 12).unwrap();
+
+        let strict = StatsOptions::default();
+        assert_eq!(current_streak(monday, &data, &strict), 1);
+
+        let weekdays_only = StatsOptions {
+            skip_weekends: true,
+            ..StatsOptions::default()
+        };
+        assert_eq!(current_streak(monday, &data, &weekdays_only), 3);
+
+        // Weekend activity doesn't count towards the streak either.
+        data.days.insert(
+            "2026-01-10".to_string(),
+            crate::models::DayCounts::new(5, 0),
+        );
+        assert_eq!(current_streak(monday, &data, &weekdays_only), 3);
+        assert_eq!(current_streak(monday, &data, &strict), 1);
+    }
+
+    #[test]
+    fn only_current_week_carries_today_contribution() {
+        let mut data = AppData::default();
+        let today = NaiveDate::from_ymd_opt(2026, 1, 7).unwrap();
+        data.days.insert(
+            "2026-01-05".to_string(),
+            crate::models::DayCounts::new(9, 0),
+        );
+        data.days.insert(
+            "2026-01-07".to_string(),
+            crate::models::DayCounts::new(4, 1),
+        );
+
+        let stats = build_stats_at(today, &data, &StatsOptions::default());
+        let current = stats.weekly_totals.last().unwrap();
+        assert_eq!(current.net, 12);
+        assert_eq!(current.today_contribution, Some(3));
+        assert!(
+            stats.weekly_totals[..stats.weekly_totals.len() - 1]
+                .iter()
+                .all(|week| week.today_contribution.is_none())
+        );
+    }
+
+    #[test]
+    fn stats_report_last_7_days_range() {
+        let mut data = AppData::default();
+        let today = NaiveDate::from_ymd_opt(2026, 1, 7).unwrap();
+        for (date, add, sub) in [
+            ("2025-12-30", 50, 0),
+            ("2026-01-02", 4, 0),
+            ("2026-01-03", 0, 2),
+            ("2026-01-05", 4, 0),
+            ("2026-01-06", 0, 2),
+        ] {
+            data.days
+                .insert(date.to_string(), crate::models::DayCounts::new(add, sub));
+        }
+
+        let stats = build_stats_at(today, &data, &StatsOptions::default());
+        assert_eq!(stats.max_net, 4);
+        assert_eq!(stats.max_net_date, "2026-01-02");
+        assert_eq!(stats.min_net, -2);
+        assert_eq!(stats.min_net_date, "2026-01-03");
+    }
+
+    #[test]
+    fn compare_week_reports_delta_and_proration() {
+        let mut data = AppData::default();
+        // Wednesday of 2026-W02; compare with 2026-W01.
+        let today = NaiveDate::from_ymd_opt(2026, 1, 7).unwrap();
+        data.days.insert(
+            "2025-12-30".to_string(),
+            crate::models::DayCounts::new(14, 0),
+        );
+        data.days.insert(
+            "2026-01-06".to_string(),
+            crate::models::DayCounts::new(9, 0),
+        );
+
+        let compare = compare_week_at(today, &data, 1, WeekStart::Monday);
+        assert_eq!(compare.current.week, "2026-W02");
+        assert_eq!(compare.previous.week, "2026-W01");
+        assert_eq!(compare.net_delta, -5);
+        assert_eq!(compare.percent_change, Some(-5.0 / 14.0 * 100.0));
+        assert!(compare.current_partial);
+        assert_eq!(compare.prorated_net_delta, 3.0);
+
+        let empty = compare_week_at(today, &AppData::default(), 4, WeekStart::Monday);
+        assert_eq!(empty.percent_change, None);
+    }
+
     #[test]
     fn stats_weekly_series_lengths() {
         let data = AppData::default();
         let today = NaiveDate::from_ymd_opt(2026, 1, 5).unwrap();
-        let stats = build_stats_at(today, &data);
+        let stats = build_stats_at(today, &data, &StatsOptions::default());
         assert_eq!(stats.weekly_totals.len(), 8);
         assert_eq!(stats.weekly_averages.len(), 8);
         assert_eq!(stats.last_7_days.len(), 7);
@@ -1,36 +1,972 @@
 use crate::errors::AppError;
-use crate::models::AppData;
+use crate::models::{AppData, CountMode, DayCounts};
+use chrono::NaiveDate;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::{env, path::Path, path::PathBuf};
 use tokio::fs;
-use tracing::error;
+use tokio::io::AsyncWriteExt;
+use tracing::{error, warn};
 
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorage;
+
+pub type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Where the live data is kept. `AppState` reads it once at startup and
+/// hands every write to [`Storage::persist`]; the data itself always lives in
+/// memory, so a backend only has to store and return a whole `AppData`.
+pub trait Storage: Send + Sync {
+    /// The file backing this storage. `config.toml` is kept next to it and
+    /// `/api/datastamp` reports its modification time.
+    fn path(&self) -> &Path;
+
+    /// Reads the stored data, falling back to empty data when there is none
+    /// or it can't be read. Problems are returned for `/api/status`.
+    fn load(&self) -> StorageFuture<'_, (AppData, Vec<String>)>;
+
+    /// Replaces the stored data with `data`. With `sync` set the write is on
+    /// disk before this returns (see `APP_SYNC_WRITES`).
+    fn persist<'a>(
+        &'a self,
+        data: &'a AppData,
+        sync: bool,
+    ) -> StorageFuture<'a, Result<(), AppError>>;
+
+    /// Keeps a copy of what is stored now, before a write that replaces all
+    /// of it (imports, sync and reset). Failures are only logged, since a
+    /// missing backup is no reason to refuse the write. Does nothing unless
+    /// the backend keeps backups.
+    fn back_up(&self) -> StorageFuture<'_, ()> {
+        Box::pin(async {})
+    }
+
+    /// True when nothing is ever written to disk, so there is no data
+    /// directory for `config.toml` or the event log either.
+    fn in_memory(&self) -> bool {
+        false
+    }
+}
+
+/// Keeps nothing (`APP_STORAGE=memory`): every start is empty and writes are
+/// dropped, for demos and throwaway deployments. The data still lives in
+/// `AppState` while the process runs.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    /// Always empty; there is no file.
+    path: PathBuf,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(&self) -> StorageFuture<'_, (AppData, Vec<String>)> {
+        Box::pin(async { (AppData::default(), Vec::new()) })
+    }
+
+    fn persist<'a>(
+        &'a self,
+        _data: &'a AppData,
+        _sync: bool,
+    ) -> StorageFuture<'a, Result<(), AppError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn in_memory(&self) -> bool {
+        true
+    }
+}
+
+/// The original backend: one pretty-printed JSON file, replaced atomically on
+/// every write.
+#[derive(Debug, Clone)]
+pub struct JsonStorage {
+    path: PathBuf,
+    /// Copies of the previous file kept by [`rotate_backups`] before each
+    /// whole-state write; 0 keeps none.
+    backups: usize,
+}
+
+impl JsonStorage {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, backups: 0 }
+    }
+
+    pub fn with_backups(mut self, keep: usize) -> Self {
+        self.backups = keep;
+        self
+    }
+}
+
+impl Storage for JsonStorage {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(&self) -> StorageFuture<'_, (AppData, Vec<String>)> {
+        Box::pin(load_data(&self.path))
+    }
+
+    fn persist<'a>(
+        &'a self,
+        data: &'a AppData,
+        sync: bool,
+    ) -> StorageFuture<'a, Result<(), AppError>> {
+        Box::pin(persist_data(&self.path, data, sync))
+    }
+
+    fn back_up(&self) -> StorageFuture<'_, ()> {
+        Box::pin(async move {
+            if let Err(err) = rotate_backups(&self.path, self.backups).await {
+                warn!("failed to back up {}: {err}", self.path.display());
+            }
+        })
+    }
+}
+
+/// Backups kept when `APP_BACKUPS` is unset.
+const DEFAULT_BACKUPS: usize = 5;
+
+/// `APP_DATA_PATH` when set, otherwise `state.json` in [`default_data_dir`].
+/// The relative `data/state.json` is only used when there is no home
+/// directory to put it under.
 pub fn resolve_data_path() -> Result<PathBuf, std::io::Error> {
-    if let Ok(path) = env::var("APP_DATA_PATH") {
-        return Ok(PathBuf::from(path));
+    Ok(data_path_from(
+        env::var_os("APP_DATA_PATH"),
+        default_data_dir(),
+    ))
+}
+
+/// The platform's per-user data directory for this app:
+/// `$XDG_DATA_HOME/daily-click-counter` (usually
+/// `~/.local/share/daily-click-counter`) on Linux, `~/Library/Application
+/// Support/daily-click-counter` on macOS and `%APPDATA%` on Windows.
+pub fn default_data_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "daily-click-counter").map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+fn data_path_from(env_path: Option<std::ffi::OsString>, data_dir: Option<PathBuf>) -> PathBuf {
+    match (env_path, data_dir) {
+        (Some(path), _) => PathBuf::from(path),
+        (None, Some(dir)) => dir.join("state.json"),
+        (None, None) => PathBuf::from("data/state.json"),
+    }
+}
+
+/// Picks the backend from `APP_STORAGE`: `sqlite:///var/lib/clicks.db` (or
+/// `sqlite://relative.db`) for SQLite, `json://...` for a JSON file, `memory`
+/// for [`MemoryStorage`], and the JSON file at [`resolve_data_path`] when
+/// unset.
+pub fn resolve_storage() -> Result<Arc<dyn Storage>, std::io::Error> {
+    let Some(url) = env::var("APP_STORAGE")
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+    else {
+        return Ok(Arc::new(
+            JsonStorage::new(resolve_data_path()?).with_backups(resolve_backups()),
+        ));
+    };
+    if url == "memory" {
+        return Ok(Arc::new(MemoryStorage::new()));
     }
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+    let (scheme, path) = url
+        .split_once("://")
+        .filter(|(_, path)| !path.is_empty())
+        .ok_or_else(|| {
+            invalid(format!(
+                "APP_STORAGE={url} must be memory or look like sqlite:///path/to/file.db"
+            ))
+        })?;
+    match scheme {
+        "json" => Ok(Arc::new(
+            JsonStorage::new(PathBuf::from(path)).with_backups(resolve_backups()),
+        )),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Ok(Arc::new(SqliteStorage::new(PathBuf::from(path)))),
+        #[cfg(not(feature = "sqlite"))]
+        "sqlite" => Err(invalid(
+            "APP_STORAGE=sqlite://... needs a build with the `sqlite` feature".to_string(),
+        )),
+        _ => Err(invalid(format!(
+            "APP_STORAGE scheme {scheme} is not one of json or sqlite"
+        ))),
+    }
+}
 
-    Ok(PathBuf::from("data/state.json"))
+/// How many backups of the JSON file to keep (`APP_BACKUPS`, default 5; 0
+/// turns them off).
+fn resolve_backups() -> usize {
+    env::var("APP_BACKUPS")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(DEFAULT_BACKUPS)
 }
 
-pub async fn load_data(path: &Path) -> AppData {
-    match fs::read(path).await {
-        Ok(bytes) => match serde_json::from_slice(&bytes) {
-            Ok(data) => data,
+/// Copies the current file at `path` to `<path>.1` after shifting the
+/// existing `<path>.1`..`<path>.{keep-1}` up by one, so `.1` is always the
+/// newest backup and the oldest falls off at `keep`. Does nothing when
+/// there is no file yet or `keep` is 0.
+pub async fn rotate_backups(path: &Path, keep: usize) -> std::io::Result<()> {
+    if keep == 0 || !fs::try_exists(path).await? {
+        return Ok(());
+    }
+    for n in (1..keep).rev() {
+        let older = backup_path(path, n);
+        if fs::try_exists(&older).await? {
+            fs::rename(&older, backup_path(path, n + 1)).await?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1)).await?;
+    Ok(())
+}
+
+/// `state.json.3` for the third-newest backup of `state.json`.
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "state.json".to_string());
+    path.with_file_name(format!("{name}.{n}"))
+}
+
+/// Read-only archive files listed in `APP_ARCHIVE_PATHS` (comma-separated).
+pub fn resolve_archive_paths() -> Vec<PathBuf> {
+    env::var("APP_ARCHIVE_PATHS")
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Loads and merges the archive files in order; later files win on conflicts.
+/// Also returns any problems found while reading them.
+pub async fn load_archives(paths: &[PathBuf]) -> (AppData, Vec<String>) {
+    let mut archive = AppData::default();
+    let mut warnings = Vec::new();
+    for path in paths {
+        if fs::metadata(path).await.is_err() {
+            let message = format!("archive file {} does not exist", path.display());
+            warn!("{message}");
+            warnings.push(message);
+            continue;
+        }
+        let (data, archive_warnings) = load_data(path).await;
+        archive.days.extend(data.days);
+        warnings.extend(archive_warnings);
+    }
+    (archive, warnings)
+}
+
+/// Layers the live data on top of the archive. Days in the live file take
+/// precedence over archived ones, and a zeroed live day drops its archived
+/// copy altogether: that is how [`strip_archived`] records a deletion.
+pub fn merge_archive(live: AppData, archive: &AppData) -> AppData {
+    let mut merged = live;
+    for (date, counts) in &archive.days {
+        match merged.days.entry(date.clone()) {
+            Entry::Vacant(entry) => {
+                entry.insert(counts.clone());
+            }
+            Entry::Occupied(entry) if *entry.get() == DayCounts::default() => {
+                entry.remove();
+            }
+            Entry::Occupied(_) => {}
+        }
+    }
+    merged
+}
+
+/// The part of `data` that belongs in the live file: everything except days
+/// that are still identical to their archived copy. Archived days missing
+/// from `data` were deleted, so they are written as zeroed days to keep
+/// [`merge_archive`] from bringing them back.
+pub fn strip_archived(data: &AppData, archive: &AppData) -> AppData {
+    let mut live = data.clone();
+    live.days
+        .retain(|date, counts| archive.days.get(date) != Some(counts));
+    for date in archive.days.keys() {
+        if !data.days.contains_key(date) {
+            live.days.insert(date.clone(), DayCounts::default());
+        }
+    }
+    live
+}
+
+/// Reads a data file, falling back to empty data when it is missing or
+/// unreadable. Problems are logged and also returned so they can be surfaced
+/// through `/api/status`.
+pub async fn load_data(path: &Path) -> (AppData, Vec<String>) {
+    let mut warnings = Vec::new();
+    let data = match fs::read(path).await {
+        Ok(bytes) => match serde_json::from_slice::<AppData>(&bytes) {
+            Ok(mut data) => {
+                data.normalize_counters();
+                warnings.extend(normalize_date_keys(&mut data, path));
+                data
+            }
             Err(err) => {
-                error!("failed to parse data file: {err}");
+                let message = format!("failed to parse data file {}: {err}", path.display());
+                error!("{message}");
+                warnings.push(message);
                 AppData::default()
             }
         },
         Err(err) if err.kind() == std::io::ErrorKind::NotFound => AppData::default(),
         Err(err) => {
-            error!("failed to read data file: {err}");
+            let message = format!("failed to read data file {}: {err}", path.display());
+            error!("{message}");
+            warnings.push(message);
             AppData::default()
         }
+    };
+    (data, warnings)
+}
+
+/// Drops days whose key isn't a date (a hand-edited `"2026-13-40"` or
+/// `"garbage"`) and rewrites the rest as zero-padded `YYYY-MM-DD`, which is
+/// what every lookup expects. When a rewritten key collides with one already
+/// in that form, the canonical entry wins. Returns one warning per dropped day.
+fn normalize_date_keys(data: &mut AppData, path: &Path) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let counters = std::iter::once(&mut data.days).chain(data.counters.values_mut());
+    for days in counters {
+        let mut kept = BTreeMap::new();
+        let mut rewritten = Vec::new();
+        for (key, counts) in std::mem::take(days) {
+            match NaiveDate::parse_from_str(&key, "%Y-%m-%d") {
+                Ok(date) if date.format("%Y-%m-%d").to_string() == key => {
+                    kept.insert(key, counts);
+                }
+                Ok(date) => rewritten.push((key, date.format("%Y-%m-%d").to_string(), counts)),
+                Err(_) => warnings.push(format!(
+                    "dropped day {key:?} from {}: not a YYYY-MM-DD date",
+                    path.display()
+                )),
+            }
+        }
+        for (key, canonical, counts) in rewritten {
+            match kept.entry(canonical) {
+                Entry::Vacant(entry) => {
+                    entry.insert(counts);
+                }
+                Entry::Occupied(entry) => warnings.push(format!(
+                    "dropped day {key:?} from {}: {} is already recorded",
+                    path.display(),
+                    entry.key()
+                )),
+            }
+        }
+        *days = kept;
+    }
+    for message in &warnings {
+        warn!("{message}");
     }
+    warnings
+}
+
+/// Hex-encoded SHA-256 of the serialized data, as written to disk.
+pub fn data_digest(data: &AppData) -> Result<String, AppError> {
+    let payload = serde_json::to_vec_pretty(data).map_err(AppError::internal)?;
+    let digest = Sha256::digest(&payload);
+    Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
 }
 
-pub async fn persist_data(path: &Path, data: &AppData) -> Result<(), AppError> {
+/// Writes `data` to `path` by writing a sibling temp file and renaming it
+/// over `path`, so a crash mid-write leaves either the old file or the new
+/// one, never a truncated mix. With `sync` set the temp file is flushed to
+/// disk with `sync_all` before the rename and the directory after it (on
+/// Unix, where the rename lives in the directory entry), so a write that
+/// returned `Ok` survives a power loss. That costs a disk flush per write (milliseconds on
+/// SSDs, much more on SD cards and network mounts); without it the OS may
+/// hold the data in its page cache for a few seconds, which only matters if
+/// the machine itself goes down.
+pub async fn persist_data(path: &Path, data: &AppData, sync: bool) -> Result<(), AppError> {
     let payload = serde_json::to_vec_pretty(data).map_err(AppError::internal)?;
-    fs::write(path, payload).await.map_err(AppError::internal)?;
+    let temp = temp_path_for(path);
+    let result = write_and_rename(&temp, path, payload, sync).await;
+    if result.is_err() {
+        let _ = fs::remove_file(&temp).await;
+    }
+    result.map_err(AppError::internal)
+}
+
+async fn write_and_rename(
+    temp: &Path,
+    path: &Path,
+    payload: Vec<u8>,
+    sync: bool,
+) -> std::io::Result<()> {
+    fs::write(temp, payload).await?;
+    if sync {
+        fs::OpenOptions::new()
+            .write(true)
+            .open(temp)
+            .await?
+            .sync_all()
+            .await?;
+    }
+    fs::rename(temp, path).await?;
+    #[cfg(unix)]
+    if sync {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        fs::File::open(dir).await?.sync_all().await?;
+    }
     Ok(())
 }
+
+/// `state.json.<pid>.<n>.tmp` next to `path`. The counter keeps concurrent
+/// writers in this process apart and the pid keeps other processes apart.
+fn temp_path_for(path: &Path) -> PathBuf {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "state.json".to_string());
+    path.with_file_name(format!("{name}.{}.{n}.tmp", std::process::id()))
+}
+
+/// One line of the append-only click log (`APP_EVENT_LOG=1`). `amount` is in
+/// stored steps, like the data file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClickEvent {
+    /// Position in the log. Events at or below the snapshot's `log_seq` are
+    /// already folded into it and skipped on replay.
+    pub seq: u64,
+    pub ts: String,
+    /// Absent for the default counter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counter: Option<String>,
+    pub date: String,
+    pub action: String,
+    pub amount: u64,
+    /// A `sub` in single-count mode, which takes `amount` from `add`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub single: bool,
+}
+
+impl ClickEvent {
+    /// Adds the click to `data` and moves its `log_seq` up to this event.
+    pub fn apply(&self, data: &mut AppData) {
+        let counter = self.counter.as_deref().unwrap_or(AppData::DEFAULT_COUNTER);
+        let counts = data
+            .counter_mut(counter)
+            .entry(self.date.clone())
+            .or_default();
+        let mode = if self.single {
+            CountMode::Single
+        } else {
+            CountMode::Split
+        };
+        counts.click(mode, &self.action, self.amount);
+        data.log_seq = data.log_seq.max(self.seq);
+    }
+}
+
+/// JSON Lines file of clicks not yet folded into the snapshot. A click only
+/// appends one short line here instead of rewriting the whole data file;
+/// every full write of the snapshot (a compaction) empties it again.
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    path: PathBuf,
+}
+
+impl EventLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The log kept next to a data file: `data/state.json` logs to
+    /// `data/state.log`.
+    pub fn beside(data_path: &Path) -> Self {
+        Self::new(data_path.with_extension("log"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `event` as one line. With `sync` set the line is on disk
+    /// before this returns.
+    pub async fn append(&self, event: &ClickEvent, sync: bool) -> Result<(), AppError> {
+        let mut line = serde_json::to_vec(event).map_err(AppError::internal)?;
+        line.push(b'\n');
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(AppError::internal)?;
+        file.write_all(&line).await.map_err(AppError::internal)?;
+        if sync {
+            file.sync_data().await.map_err(AppError::internal)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the logged clicks that `data` doesn't have yet. Unreadable
+    /// lines (typically one cut short by a crash) are skipped and reported.
+    pub async fn replay(&self, data: &mut AppData) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let text = match fs::read_to_string(&self.path).await {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return warnings,
+            Err(err) => {
+                let message = format!("failed to read event log {}: {err}", self.path.display());
+                error!("{message}");
+                warnings.push(message);
+                return warnings;
+            }
+        };
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<ClickEvent>(line) {
+                Ok(event) if event.seq > data.log_seq => event.apply(data),
+                Ok(_) => {}
+                Err(err) => {
+                    let message = format!(
+                        "skipped line {} of event log {}: {err}",
+                        index + 1,
+                        self.path.display()
+                    );
+                    warn!("{message}");
+                    warnings.push(message);
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Empties the log once the snapshot holds everything in it.
+    pub async fn truncate(&self) -> Result<(), AppError> {
+        match fs::OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(&self.path)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(AppError::internal(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DayCounts;
+
+    fn sample(days: &[(&str, u64, u64)]) -> AppData {
+        let mut data = AppData::default();
+        for (date, add, sub) in days {
+            data.days
+                .insert(date.to_string(), DayCounts::new(*add, *sub));
+        }
+        data
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        env::temp_dir().join(format!(
+            "web_app_{name}_{}_{nanos}.json",
+            std::process::id()
+        ))
+    }
+
+    /// Everything in `AppData` must survive a write and a fresh read through
+    /// the trait, whichever backend is behind it.
+    async fn assert_round_trips(storage: &dyn Storage) {
+        let (empty, warnings) = storage.load().await;
+        assert!(empty.days.is_empty());
+        assert!(warnings.is_empty());
+
+        let mut data = sample(&[("2026-01-01", 3, 1), ("2026-01-02", 0, 2)]);
+        data.days.get_mut("2026-01-01").unwrap().tags = vec!["focus".to_string()];
+        data.days.get_mut("2026-01-01").unwrap().record("jump", 2);
+        data.counter_mut("coffee")
+            .insert("2026-01-02".to_string(), DayCounts::new(4, 0));
+        data.week_notes
+            .insert("2026-W01".to_string(), "good start".to_string());
+        data.last_auto_add = Some("2026-01-02".to_string());
+        storage.persist(&data, false).await.unwrap();
+
+        data.days.remove("2026-01-02");
+        storage.persist(&data, true).await.unwrap();
+
+        let (loaded, warnings) = storage.load().await;
+        assert!(warnings.is_empty());
+        assert_eq!(loaded.days, data.days);
+        assert_eq!(loaded.counters, data.counters);
+        assert_eq!(loaded.week_notes, data.week_notes);
+        assert_eq!(loaded.last_auto_add, data.last_auto_add);
+    }
+
+    #[tokio::test]
+    async fn json_storage_round_trips_through_the_trait() {
+        let path = temp_path("json_storage");
+        assert_round_trips(&JsonStorage::new(path.clone())).await;
+        let _ = fs::remove_file(path).await;
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn sqlite_storage_round_trips_through_the_trait() {
+        let path = temp_path("sqlite_storage").with_extension("db");
+        assert_round_trips(&SqliteStorage::new(path.clone())).await;
+        // A second handle reads what the first one wrote.
+        let (data, _) = SqliteStorage::new(path.clone()).load().await;
+        assert_eq!(data.days["2026-01-01"].tags, vec!["focus"]);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{suffix}", path.display())).await;
+        }
+    }
+
+    #[test]
+    fn data_path_defaults_to_the_platform_data_dir() {
+        let dir = default_data_dir().expect("a home directory");
+        assert!(dir.is_absolute());
+        assert!(dir.ends_with("daily-click-counter"));
+        #[cfg(target_os = "linux")]
+        if env::var_os("XDG_DATA_HOME").is_none() {
+            let home = env::var_os("HOME").map(PathBuf::from).unwrap();
+            assert_eq!(dir, home.join(".local/share/daily-click-counter"));
+        }
+
+        assert_eq!(
+            data_path_from(None, Some(dir.clone())),
+            dir.join("state.json")
+        );
+        assert_eq!(
+            data_path_from(Some("/srv/clicks.json".into()), Some(dir)),
+            PathBuf::from("/srv/clicks.json")
+        );
+        assert_eq!(data_path_from(None, None), PathBuf::from("data/state.json"));
+    }
+
+    #[test]
+    fn digest_changes_with_data() {
+        let empty = data_digest(&AppData::default()).unwrap();
+        assert_eq!(empty.len(), 64);
+        assert_eq!(empty, data_digest(&AppData::default()).unwrap());
+
+        let changed = data_digest(&sample(&[("2026-01-01", 1, 0)])).unwrap();
+        assert_ne!(empty, changed);
+    }
+
+    #[tokio::test]
+    async fn legacy_and_nested_files_load_into_the_default_counter() {
+        let legacy = temp_path("legacy");
+        fs::write(&legacy, br#"{"days":{"2026-01-05":{"add":3,"sub":1}}}"#)
+            .await
+            .unwrap();
+        let (data, warnings) = load_data(&legacy).await;
+        assert!(warnings.is_empty());
+        assert_eq!(
+            data.counter(AppData::DEFAULT_COUNTER).unwrap()["2026-01-05"].add,
+            3
+        );
+        assert!(data.days["2026-01-05"].actions.is_empty());
+        assert!(data.counters.is_empty());
+
+        // Custom actions survive a save and only appear once used.
+        let mut data = data;
+        data.days.get_mut("2026-01-05").unwrap().record("jump", 2);
+        persist_data(&legacy, &data, false).await.unwrap();
+        let saved: serde_json::Value =
+            serde_json::from_slice(&fs::read(&legacy).await.unwrap()).unwrap();
+        assert_eq!(saved["days"]["2026-01-05"]["actions"]["jump"], 2);
+        let (data, _) = load_data(&legacy).await;
+        assert_eq!(data.days["2026-01-05"].actions["jump"], 2);
+        assert_eq!(data.days["2026-01-05"].net(), 2);
+
+        let nested = temp_path("nested");
+        fs::write(
+            &nested,
+            br#"{"counters":{"default":{"2026-01-05":{"add":2,"sub":0}},"coffee":{"2026-01-05":{"add":4,"sub":0}}}}"#,
+        )
+        .await
+        .unwrap();
+        let (data, _) = load_data(&nested).await;
+        assert_eq!(data.days["2026-01-05"].add, 2);
+        assert_eq!(data.counter("coffee").unwrap()["2026-01-05"].add, 4);
+        assert!(!data.counters.contains_key(AppData::DEFAULT_COUNTER));
+
+        let _ = fs::remove_file(legacy).await;
+        let _ = fs::remove_file(nested).await;
+    }
+
+    #[tokio::test]
+    async fn invalid_day_keys_are_dropped_and_the_rest_normalized() {
+        let path = temp_path("date_keys");
+        fs::write(
+            &path,
+            br#"{"days":{"2026-01-05":{"add":3,"sub":0},"2026-1-5":{"add":9,"sub":0},"2026-2-7":{"add":2,"sub":1},"2026-13-40":{"add":1,"sub":0},"garbage":{"add":1,"sub":0}},"counters":{"coffee":{"2026-3-1":{"add":4,"sub":0},"yesterday":{"add":1,"sub":0}}}}"#,
+        )
+        .await
+        .unwrap();
+
+        let (data, warnings) = load_data(&path).await;
+        let days: Vec<_> = data.days.keys().cloned().collect();
+        assert_eq!(days, ["2026-01-05", "2026-02-07"]);
+        assert_eq!(data.days["2026-01-05"].add, 3);
+        assert_eq!(data.days["2026-02-07"].sub, 1);
+        let coffee: Vec<_> = data.counter("coffee").unwrap().keys().cloned().collect();
+        assert_eq!(coffee, ["2026-03-01"]);
+        assert_eq!(warnings.len(), 4);
+        assert!(
+            warnings
+                .iter()
+                .any(|warning| warning.contains("\"garbage\""))
+        );
+        assert!(
+            warnings
+                .iter()
+                .any(|warning| warning.contains("2026-01-05 is already recorded"))
+        );
+
+        let _ = fs::remove_file(path).await;
+    }
+
+    #[tokio::test]
+    async fn backups_rotate_and_keep_only_the_newest() {
+        let path = temp_path("backups");
+        let storage = JsonStorage::new(path.clone()).with_backups(3);
+
+        // Nothing to back up before the first write.
+        rotate_backups(&path, 3).await.unwrap();
+        assert!(!backup_path(&path, 1).exists());
+
+        for add in 1..=5 {
+            storage.back_up().await;
+            storage
+                .persist(&sample(&[("2026-01-05", add, 0)]), false)
+                .await
+                .unwrap();
+        }
+        // Plain writes don't take one.
+        storage
+            .persist(&sample(&[("2026-01-05", 6, 0)]), false)
+            .await
+            .unwrap();
+        let adds: Vec<u64> = {
+            let mut adds = Vec::new();
+            for n in 1..=3 {
+                let (backup, _) = load_data(&backup_path(&path, n)).await;
+                adds.push(backup.days["2026-01-05"].add);
+            }
+            adds
+        };
+        // Newest first; the write of 1 fell off.
+        assert_eq!(adds, [4, 3, 2]);
+        assert!(!backup_path(&path, 4).exists());
+        let (current, _) = load_data(&path).await;
+        assert_eq!(current.days["2026-01-05"].add, 6);
+
+        let _ = fs::remove_file(&path).await;
+        for n in 1..=3 {
+            let _ = fs::remove_file(backup_path(&path, n)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn corrupt_file_falls_back_with_a_warning() {
+        let path = temp_path("corrupt");
+        fs::write(&path, b"{ not json").await.unwrap();
+
+        let (data, warnings) = load_data(&path).await;
+        assert!(data.days.is_empty());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("failed to parse data file"));
+
+        let (_, warnings) = load_data(&temp_path("never_written")).await;
+        assert!(warnings.is_empty());
+
+        let _ = fs::remove_file(path).await;
+    }
+
+    #[tokio::test]
+    async fn persist_replaces_the_file_without_leftovers() {
+        let dir = temp_path("atomic");
+        fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("state.json");
+        persist_data(&path, &sample(&[("2026-01-01", 1, 0)]), false)
+            .await
+            .unwrap();
+
+        persist_data(&path, &sample(&[("2026-01-01", 2, 1)]), true)
+            .await
+            .unwrap();
+
+        let (data, warnings) = load_data(&path).await;
+        assert!(warnings.is_empty());
+        assert_eq!(data.days["2026-01-01"], DayCounts::new(2, 1));
+        let mut entries = fs::read_dir(&dir).await.unwrap();
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+        assert_eq!(names, vec!["state.json"]);
+
+        let _ = fs::remove_dir_all(dir).await;
+    }
+
+    #[test]
+    fn temp_names_are_unique_siblings() {
+        let path = Path::new("data/state.json");
+        let first = temp_path_for(path);
+        let second = temp_path_for(path);
+        assert_ne!(first, second);
+        assert_eq!(first.parent(), path.parent());
+        assert!(first.to_string_lossy().ends_with(".tmp"));
+    }
+
+    #[test]
+    fn live_days_take_precedence_over_archive() {
+        let archive = sample(&[("2025-01-01", 5, 0), ("2025-01-02", 1, 1)]);
+        let live = sample(&[("2025-01-02", 9, 0), ("2026-01-01", 2, 0)]);
+
+        let merged = merge_archive(live, &archive);
+        assert_eq!(merged.days.len(), 3);
+        assert_eq!(merged.days["2025-01-01"].add, 5);
+        assert_eq!(merged.days["2025-01-02"].add, 9);
+    }
+
+    #[test]
+    fn strip_archived_keeps_only_live_changes() {
+        let archive = sample(&[("2025-01-01", 5, 0), ("2025-01-02", 1, 1)]);
+        let merged = sample(&[
+            ("2025-01-01", 5, 0),
+            ("2025-01-02", 2, 1),
+            ("2026-01-01", 2, 0),
+        ]);
+
+        let live = strip_archived(&merged, &archive);
+        let dates: Vec<_> = live.days.keys().cloned().collect();
+        assert_eq!(dates, vec!["2025-01-02", "2026-01-01"]);
+    }
+
+    #[test]
+    fn deleted_archived_days_stay_deleted() {
+        let archive = sample(&[("2025-01-01", 5, 0), ("2025-01-02", 1, 1)]);
+        let mut merged = merge_archive(sample(&[("2026-01-01", 2, 0)]), &archive);
+        merged.days.remove("2025-01-01");
+        merged
+            .days
+            .insert("2025-01-02".to_string(), DayCounts::default());
+
+        let live = strip_archived(&merged, &archive);
+        assert_eq!(live.days["2025-01-01"], DayCounts::default());
+        assert_eq!(live.days["2025-01-02"], DayCounts::default());
+
+        let reloaded = merge_archive(live, &archive);
+        let dates: Vec<_> = reloaded.days.keys().cloned().collect();
+        assert_eq!(dates, vec!["2026-01-01"]);
+
+        // A reset leaves nothing of the archive behind.
+        let reloaded = merge_archive(strip_archived(&AppData::default(), &archive), &archive);
+        assert!(reloaded.days.is_empty());
+    }
+
+    #[tokio::test]
+    async fn replaying_the_event_log_rebuilds_the_counts() {
+        let log = EventLog::new(temp_path("events").with_extension("log"));
+        let lines = [
+            r#"{"seq":1,"ts":"2026-01-05T09:00:00Z","date":"2026-01-05","action":"add","amount":1}"#,
+            r#"{"seq":2,"ts":"2026-01-05T09:01:00Z","date":"2026-01-05","action":"add","amount":2}"#,
+            r#"{"seq":3,"ts":"2026-01-05T09:02:00Z","date":"2026-01-05","action":"sub","amount":1}"#,
+            r#"{"seq":4,"ts":"2026-01-06T08:00:00Z","counter":"coffee","date":"2026-01-06","action":"add","amount":1}"#,
+            r#"{"seq":5,"ts":"2026-01-06T08:00:01Z","date":"2026-01-"#,
+        ];
+        fs::write(log.path(), lines.join("\n")).await.unwrap();
+
+        let mut data = AppData::default();
+        let warnings = log.replay(&mut data).await;
+        assert_eq!(data.days["2026-01-05"], DayCounts::new(3, 1));
+        assert_eq!(data.counter("coffee").unwrap()["2026-01-06"].add, 1);
+        assert_eq!(data.log_seq, 4);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("skipped line 5"));
+
+        // A snapshot already holding the first two events only gets the rest.
+        let mut snapshot = sample(&[("2026-01-05", 3, 0)]);
+        snapshot.log_seq = 2;
+        log.replay(&mut snapshot).await;
+        assert_eq!(snapshot.days["2026-01-05"], DayCounts::new(3, 1));
+
+        let event = ClickEvent {
+            seq: 6,
+            ts: "2026-01-06T09:00:00Z".to_string(),
+            counter: None,
+            date: "2026-01-06".to_string(),
+            action: "add".to_string(),
+            amount: 1,
+            single: false,
+        };
+        log.truncate().await.unwrap();
+        log.append(&event, true).await.unwrap();
+        let mut fresh = AppData::default();
+        assert!(log.replay(&mut fresh).await.is_empty());
+        assert_eq!(fresh.days["2026-01-06"].add, 1);
+
+        let _ = fs::remove_file(log.path()).await;
+    }
+
+    #[tokio::test]
+    async fn later_archives_override_earlier_ones() {
+        let first = temp_path("archive_a");
+        let second = temp_path("archive_b");
+        persist_data(
+            &first,
+            &sample(&[("2024-01-01", 1, 0), ("2024-01-02", 1, 0)]),
+            false,
+        )
+        .await
+        .unwrap();
+        persist_data(&second, &sample(&[("2024-01-02", 7, 0)]), true)
+            .await
+            .unwrap();
+
+        let missing = temp_path("archive_missing");
+        let (archive, warnings) = load_archives(&[first.clone(), missing, second.clone()]).await;
+        assert_eq!(archive.days.len(), 2);
+        assert_eq!(archive.days["2024-01-02"].add, 7);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("does not exist"));
+
+        let _ = fs::remove_file(first).await;
+        let _ = fs::remove_file(second).await;
+    }
+}
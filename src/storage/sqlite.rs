@@ -0,0 +1,218 @@
+//! SQLite backend (`--features sqlite`, `APP_STORAGE=sqlite:///path`). Days
+//! of every counter go in a `days` table so they can be queried directly;
+//! everything else in `AppData` (week notes, the auto-add marker, ...) is
+//! kept as one JSON document in `meta`, so new fields need no migration.
+
+use super::{Storage, StorageFuture};
+use crate::errors::AppError;
+use crate::models::{AppData, DayCounts};
+use rusqlite::{Connection, params};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS days (
+        counter TEXT NOT NULL,
+        date TEXT NOT NULL,
+        add_count INTEGER NOT NULL,
+        sub_count INTEGER NOT NULL,
+        tags TEXT NOT NULL DEFAULT '[]',
+        actions TEXT NOT NULL DEFAULT '{}',
+        PRIMARY KEY (counter, date)
+    );
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+";
+
+const EXTRA_KEY: &str = "extra";
+
+/// The database is opened on first use rather than up front, so startup can
+/// still tell whether it existed before (see `fresh_install`).
+pub struct SqliteStorage {
+    path: PathBuf,
+    connection: Arc<Mutex<Option<Connection>>>,
+}
+
+impl SqliteStorage {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            connection: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Runs `f` on a blocking thread with the connection, opening the
+    /// database and creating its tables the first time.
+    async fn with_connection<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> Result<T, AppError> + Send + 'static,
+    ) -> Result<T, AppError> {
+        let slot = Arc::clone(&self.connection);
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut slot = slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if slot.is_none() {
+                *slot = Some(open(&path).map_err(AppError::internal)?);
+            }
+            f(slot.as_mut().expect("opened above"))
+        })
+        .await
+        .map_err(AppError::internal)?
+    }
+}
+
+fn open(path: &Path) -> rusqlite::Result<Connection> {
+    let connection = Connection::open(path)?;
+    connection.execute_batch("PRAGMA journal_mode = WAL;")?;
+    connection.execute_batch(SCHEMA)?;
+    add_actions_column(&connection)?;
+    Ok(connection)
+}
+
+/// Databases created before custom actions have no `actions` column.
+fn add_actions_column(connection: &Connection) -> rusqlite::Result<()> {
+    let mut statement = connection.prepare("SELECT name FROM pragma_table_info('days')")?;
+    let columns = statement
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if !columns.iter().any(|column| column == "actions") {
+        connection
+            .execute_batch("ALTER TABLE days ADD COLUMN actions TEXT NOT NULL DEFAULT '{}'")?;
+    }
+    Ok(())
+}
+
+impl Storage for SqliteStorage {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(&self) -> StorageFuture<'_, (AppData, Vec<String>)> {
+        Box::pin(async move {
+            let result = self
+                .with_connection(|connection| read_data(connection).map_err(AppError::internal))
+                .await;
+            match result {
+                Ok(mut data) => {
+                    data.normalize_counters();
+                    (data, Vec::new())
+                }
+                Err(err) => {
+                    let message = format!(
+                        "failed to read database {}: {}",
+                        self.path.display(),
+                        err.message
+                    );
+                    error!("{message}");
+                    (AppData::default(), vec![message])
+                }
+            }
+        })
+    }
+
+    fn persist<'a>(
+        &'a self,
+        data: &'a AppData,
+        sync: bool,
+    ) -> StorageFuture<'a, Result<(), AppError>> {
+        let data = data.clone();
+        Box::pin(self.with_connection(move |connection| {
+            write_data(connection, &data, sync).map_err(AppError::internal)
+        }))
+    }
+}
+
+fn read_data(connection: &Connection) -> rusqlite::Result<AppData> {
+    let extra: Option<String> = connection
+        .query_row(
+            "SELECT value FROM meta WHERE key = ?1",
+            [EXTRA_KEY],
+            |row| row.get(0),
+        )
+        .map(Some)
+        .or_else(|err| match err {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            err => Err(err),
+        })?;
+    let mut data: AppData = match extra {
+        Some(extra) => serde_json::from_str(&extra).map_err(json_error)?,
+        None => AppData::default(),
+    };
+
+    let mut statement = connection.prepare(
+        "SELECT counter, date, add_count, sub_count, tags, actions FROM days ORDER BY counter, date",
+    )?;
+    let rows = statement.query_map([], |row| {
+        let counter: String = row.get(0)?;
+        let date: String = row.get(1)?;
+        let add: i64 = row.get(2)?;
+        let sub: i64 = row.get(3)?;
+        let tags: String = row.get(4)?;
+        let actions: String = row.get(5)?;
+        Ok((counter, date, add, sub, tags, actions))
+    })?;
+    for row in rows {
+        let (counter, date, add, sub, tags, actions) = row?;
+        let counts = DayCounts {
+            add: u64::try_from(add).unwrap_or_default(),
+            sub: u64::try_from(sub).unwrap_or_default(),
+            tags: serde_json::from_str(&tags).map_err(json_error)?,
+            actions: serde_json::from_str(&actions).map_err(json_error)?,
+        };
+        data.counter_mut(&counter).insert(date, counts);
+    }
+    Ok(data)
+}
+
+fn write_data(connection: &mut Connection, data: &AppData, sync: bool) -> rusqlite::Result<()> {
+    // Everything but the days, which get their own rows.
+    let mut extra = serde_json::to_value(data).map_err(json_error)?;
+    if let Value::Object(fields) = &mut extra {
+        fields.remove("days");
+        fields.remove("counters");
+    }
+    let mut counters: BTreeMap<&str, &BTreeMap<String, DayCounts>> = data
+        .counters
+        .iter()
+        .map(|(name, days)| (name.as_str(), days))
+        .collect();
+    counters.insert(AppData::DEFAULT_COUNTER, &data.days);
+
+    connection.pragma_update(None, "synchronous", if sync { "FULL" } else { "NORMAL" })?;
+    let transaction = connection.transaction()?;
+    transaction.execute("DELETE FROM days", [])?;
+    {
+        let mut insert = transaction.prepare(
+            "INSERT INTO days (counter, date, add_count, sub_count, tags, actions) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for (counter, days) in &counters {
+            for (date, counts) in *days {
+                let tags = serde_json::to_string(&counts.tags).map_err(json_error)?;
+                let actions = serde_json::to_string(&counts.actions).map_err(json_error)?;
+                insert.execute(params![
+                    counter,
+                    date,
+                    i64::try_from(counts.add).unwrap_or(i64::MAX),
+                    i64::try_from(counts.sub).unwrap_or(i64::MAX),
+                    tags,
+                    actions,
+                ])?;
+            }
+        }
+    }
+    transaction.execute(
+        "INSERT INTO meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+        params![EXTRA_KEY, extra.to_string()],
+    )?;
+    transaction.commit()
+}
+
+fn json_error(err: serde_json::Error) -> rusqlite::Error {
+    rusqlite::Error::ToSqlConversionFailure(Box::new(err))
+}
@@ -0,0 +1,152 @@
+use crate::clock::AccountingZone;
+use crate::errors::AppError;
+use crate::models::AppData;
+use crate::state::AppState;
+use crate::stats::day_for;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
+use tracing::{error, info, warn};
+
+/// Adds `amount` to today's `add` count once per day. The date of the last
+/// automatic add is stored in the data file, so restarting mid-day doesn't
+/// apply it twice. Returns whether anything changed.
+pub fn apply_auto_add(data: &mut AppData, today: NaiveDate, amount: u64) -> bool {
+    let key = today.to_string();
+    if data.last_auto_add.as_deref() == Some(key.as_str()) {
+        return false;
+    }
+
+    let entry = data.days.entry(key.clone()).or_default();
+    entry.add = entry.add.saturating_add(amount);
+    data.last_auto_add = Some(key);
+    true
+}
+
+/// Applies [`apply_auto_add`] to the shared data and persists it. If the
+/// write fails the data is put back as it was, so the add is retried at the
+/// next run instead of being marked done for a day that never got it.
+pub async fn auto_add(state: &AppState, today: NaiveDate, amount: u64) -> Result<bool, AppError> {
+    let mut data = state.data.lock().await;
+    let previous = data.clone();
+    if !apply_auto_add(&mut data, today, amount) {
+        return Ok(false);
+    }
+    if let Err(err) = state.persist(&data).await {
+        *data = previous;
+        return Err(err);
+    }
+    Ok(true)
+}
+
+/// Applies the daily auto-add on startup and then again whenever a new day
+/// starts.
+pub async fn run_auto_add(state: AppState, amount: u64) {
+    loop {
+        let today = state.today();
+        match auto_add(&state, today, amount).await {
+            Ok(true) => info!("auto-added {amount} for {today}"),
+            Ok(false) => {}
+            Err(err) => error!("failed to persist auto-add: {}", err.message),
+        }
+
+        let wait = until_next_day(
+            state.now(),
+            state.config.timezone,
+            state.config.day_offset_hours,
+        );
+        tokio::time::sleep(wait.to_std().unwrap_or_default()).await;
+    }
+}
+
+/// Writes debounced changes at most once per `interval`: the first change
+/// after a flush starts the timer, and everything that comes in before it
+/// fires goes out in one write.
+pub async fn run_persist_flusher(state: AppState, interval: std::time::Duration) {
+    loop {
+        state.flush_requested.notified().await;
+        tokio::time::sleep(interval).await;
+        if let Err(err) = state.flush().await {
+            warn!("debounced write failed, will retry: {}", err.message);
+            state.flush_requested.notify_one();
+        }
+    }
+}
+
+/// Folds the event log into the data file every `interval`. Each write
+/// through [`AppState::flush`] empties the log once the snapshot is on disk.
+pub async fn run_log_compactor(state: AppState, interval: std::time::Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        if let Err(err) = state.flush().await {
+            warn!("event log compaction failed, will retry: {}", err.message);
+        }
+    }
+}
+
+/// Time left until just after the next day starts, which is midnight in the
+/// accounting zone plus the configured day offset.
+fn until_next_day(
+    now: DateTime<FixedOffset>,
+    zone: AccountingZone,
+    day_offset_hours: u32,
+) -> Duration {
+    let tomorrow = day_for(now.naive_local(), day_offset_hours) + Duration::days(1);
+    let next_midnight = tomorrow
+        .and_hms_opt(day_offset_hours, 0, 1)
+        .and_then(|time| zone.resolve(time));
+    match next_midnight {
+        Some(next) => next - now,
+        // Local midnight can be skipped by a DST change; check again shortly.
+        None => Duration::minutes(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn auto_add_applies_once_per_day() {
+        let mut data = AppData::default();
+        let today = NaiveDate::from_ymd_opt(2026, 1, 5).unwrap();
+
+        assert!(apply_auto_add(&mut data, today, 1));
+        assert!(!apply_auto_add(&mut data, today, 1));
+        assert_eq!(data.days["2026-01-05"].add, 1);
+
+        let tomorrow = today + Duration::days(1);
+        assert!(apply_auto_add(&mut data, tomorrow, 1));
+        assert_eq!(data.days["2026-01-06"].add, 1);
+        assert_eq!(data.last_auto_add.as_deref(), Some("2026-01-06"));
+    }
+
+    #[test]
+    fn auto_add_keeps_existing_clicks() {
+        let mut data = AppData::default();
+        data.days.insert(
+            "2026-01-05".to_string(),
+            crate::models::DayCounts::new(2, 1),
+        );
+        let today = NaiveDate::from_ymd_opt(2026, 1, 5).unwrap();
+
+        apply_auto_add(&mut data, today, 3);
+        assert_eq!(data.days["2026-01-05"].add, 5);
+        assert_eq!(data.days["2026-01-05"].sub, 1);
+    }
+
+    #[test]
+    fn next_day_wait_ends_after_midnight() {
+        let zone = AccountingZone::Local;
+        let now = Local
+            .with_ymd_and_hms(2026, 1, 5, 23, 0, 0)
+            .unwrap()
+            .fixed_offset();
+        let wait = until_next_day(now, zone, 0);
+        assert!(wait > Duration::minutes(59));
+        assert!(wait <= Duration::minutes(61));
+
+        let wait = until_next_day(now, zone, 3);
+        assert!(wait > Duration::minutes(239));
+        assert!(wait <= Duration::minutes(241));
+    }
+}
@@ -1,22 +1,184 @@
+use crate::chart::escape;
+use crate::config::Config;
 use crate::models::DayCounts;
+use crate::units::format_steps;
+use std::borrow::Cow;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Where the page markup comes from: the embedded copy, or the file named by
+/// `APP_UI_TEMPLATE`. Debug builds re-read the file on every request so UI
+/// edits show up on reload; release builds use the copy read at startup.
+#[derive(Debug, Clone)]
+pub enum UiTemplate {
+    Embedded,
+    File { path: PathBuf, loaded: String },
+}
+
+impl UiTemplate {
+    /// Reads `path` if given. An unreadable file falls back to the embedded
+    /// page with a warning rather than refusing to start.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let Some(path) = path else {
+            return Self::Embedded;
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(loaded) => Self::File { path, loaded },
+            Err(err) => {
+                warn!(
+                    "failed to read UI template {}: {err}; using the built-in page",
+                    path.display()
+                );
+                Self::Embedded
+            }
+        }
+    }
+
+    pub async fn current(&self) -> Cow<'_, str> {
+        match self {
+            Self::Embedded => Cow::Borrowed(INDEX_HTML),
+            Self::File { path, loaded } if cfg!(debug_assertions) => {
+                match tokio::fs::read_to_string(path).await {
+                    Ok(fresh) => Cow::Owned(fresh),
+                    Err(err) => {
+                        warn!("failed to re-read UI template {}: {err}", path.display());
+                        Cow::Borrowed(loaded)
+                    }
+                }
+            }
+            Self::File { loaded, .. } => Cow::Borrowed(loaded),
+        }
+    }
+
+    /// The file's modification time, when edits to it show up without a
+    /// restart. The embedded page and a release build's loaded copy can only
+    /// change with the process, which [`PageStamp`] already starts from.
+    pub async fn modified(&self) -> Option<SystemTime> {
+        match self {
+            Self::File { path, .. } if cfg!(debug_assertions) => tokio::fs::metadata(path)
+                .await
+                .and_then(|meta| meta.modified())
+                .ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Everything the page at `/` is rendered from besides the config, which is
+/// fixed for the life of the process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageInputs {
+    pub version: u64,
+    pub date: String,
+    pub maintenance: Option<String>,
+    pub template: Option<SystemTime>,
+}
+
+/// The `Last-Modified` time of the page at `/`. It starts at process start
+/// and moves to the current time whenever a request sees different
+/// [`PageInputs`] than the one before, so it is never earlier than the
+/// change it reflects. Times are whole seconds, as HTTP dates are, and each
+/// change moves it forward at least a second.
+#[derive(Debug)]
+pub struct PageStamp {
+    inputs: Option<PageInputs>,
+    modified: SystemTime,
+}
 
-pub fn render_index(date: &str, counts: &DayCounts) -> String {
-    let net = counts.add as i64 - counts.sub as i64;
-    INDEX_HTML
+impl PageStamp {
+    pub fn new(started: SystemTime) -> Self {
+        Self {
+            inputs: None,
+            modified: whole_seconds(started),
+        }
+    }
+
+    pub fn update(&mut self, inputs: PageInputs, now: SystemTime) -> SystemTime {
+        if self.inputs.as_ref() != Some(&inputs) {
+            if self.inputs.is_some() {
+                self.modified = whole_seconds(now).max(self.modified + Duration::from_secs(1));
+            }
+            self.inputs = Some(inputs);
+        }
+        self.modified
+    }
+}
+
+fn whole_seconds(time: SystemTime) -> SystemTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+pub fn render_index(
+    date: &str,
+    counts: &DayCounts,
+    config: &Config,
+    maintenance: Option<&str>,
+) -> String {
+    render_page(INDEX_HTML, date, counts, config, maintenance)
+}
+
+/// Fills in the placeholders of `template`, which is [`INDEX_HTML`] or an
+/// `APP_UI_TEMPLATE` override.
+pub fn render_page(
+    template: &str,
+    date: &str,
+    counts: &DayCounts,
+    config: &Config,
+    maintenance: Option<&str>,
+) -> String {
+    let net = counts.net();
+    let scale = config.unit_scale();
+    // Offline deployments drop the Google Fonts import; the CSS already lists
+    // system fallbacks after the web fonts.
+    let font_import = if config.offline { "" } else { FONT_IMPORT };
+    let sub_confirm = if config.confirm_sub { SUB_CONFIRM } else { "" };
+    let banner = maintenance
+        .map(|message| MAINTENANCE_BANNER.replace("{{MESSAGE}}", &escape(message)))
+        .unwrap_or_default();
+    template
+        .replace("{{FONT_IMPORT}}", font_import)
+        .replace("{{SUB_CONFIRM}}", sub_confirm)
         .replace("{{DATE}}", date)
-        .replace("{{ADD}}", &counts.add.to_string())
-        .replace("{{SUB}}", &counts.sub.to_string())
-        .replace("{{NET}}", &net.to_string())
+        .replace(
+            "{{ADD}}",
+            &format_steps(i64::try_from(counts.add).unwrap_or(i64::MAX), scale),
+        )
+        .replace(
+            "{{SUB}}",
+            &format_steps(i64::try_from(counts.sub).unwrap_or(i64::MAX), scale),
+        )
+        .replace("{{NET}}", &format_steps(net, scale))
+        .replace("{{TITLE}}", &escape(config.title()))
+        .replace("{{ADD_LABEL}}", &escape(config.add_label()))
+        .replace("{{SUB_LABEL}}", &escape(config.sub_label()))
+        // Last, so placeholders inside the message are left alone.
+        .replace("{{MAINTENANCE}}", &banner)
 }
 
+const FONT_IMPORT: &str = "@import url('https://fonts.googleapis.com/css2?family=Space+Grotesk:wght@400;500;600&family=Fraunces:wght@600&display=swap');";
+
+const SUB_CONFIRM: &str = r#"
+        <label class="confirm"><input type="checkbox" name="confirm" value="true" required /> Confirm subtract</label>"#;
+
+const MAINTENANCE_BANNER: &str = r#"
+    <div class="banner" id="maintenance" role="status">
+      <span id="maintenance-message">{{MESSAGE}}</span>
+      <button type="button" id="maintenance-dismiss" aria-label="Dismiss">&times;</button>
+    </div>"#;
+
 const INDEX_HTML: &str = r#"<!DOCTYPE html>
 <html lang="en">
 <head>
   <meta charset="UTF-8" />
   <meta name="viewport" content="width=device-width, initial-scale=1.0" />
-  <title>Daily Click Counter</title>
+  <title>{{TITLE}}</title>
   <style>
-    @import url('https://fonts.googleapis.com/css2?family=Space+Grotesk:wght@400;500;600&family=Fraunces:wght@600&display=swap');
+    {{FONT_IMPORT}}
 
     :root {
       --bg-1: #f8f3e6;
@@ -81,6 +243,44 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
       gap: 16px;
     }
 
+    .banner {
+      display: flex;
+      align-items: center;
+      justify-content: space-between;
+      gap: 12px;
+      padding: 12px 18px;
+      border-radius: 14px;
+      background: #fff4d6;
+      border: 1px solid rgba(184, 134, 11, 0.35);
+    }
+
+    .banner button {
+      width: auto;
+      padding: 0 6px;
+      background: none;
+      border: none;
+      font-size: 1.3rem;
+      color: inherit;
+      cursor: pointer;
+    }
+
+    .setup form {
+      display: grid;
+      gap: 10px;
+    }
+
+    .setup label {
+      display: grid;
+      gap: 4px;
+    }
+
+    .setup input {
+      font: inherit;
+      padding: 8px 10px;
+      border-radius: 10px;
+      border: 1px solid rgba(47, 72, 88, 0.2);
+    }
+
     .stat {
       background: white;
       border-radius: 18px;
@@ -259,6 +459,15 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
       color: #2d7a4b;
     }
 
+    .confirm {
+      display: flex;
+      align-items: center;
+      gap: 8px;
+      margin-top: 10px;
+      font-size: 0.9rem;
+      color: #6b645d;
+    }
+
     .hint {
       margin: 0;
       color: #6f6a65;
@@ -289,9 +498,20 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
 <body>
   <main class="app">
     <header>
-      <h1>Daily Click Counter</h1>
+      <h1>{{TITLE}}</h1>
       <p class="subtitle">Track adds and subtracts for each day, then build stats panels later.</p>
     </header>
+{{MAINTENANCE}}
+
+    <section class="panel setup" id="setup" hidden>
+      <form id="setup-form">
+        <h2>Set up your counter</h2>
+        <label>Title <input name="title" maxlength="60" placeholder="Daily Click Counter" /></label>
+        <label>Timezone <input name="timezone" placeholder="+02:00" /></label>
+        <label>Daily goal <input name="daily_goal" type="number" min="1" /></label>
+        <button class="btn-add" type="submit">Save</button>
+      </form>
+    </section>
 
     <section class="panel">
       <div class="stat">
@@ -314,10 +534,10 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
 
     <section class="actions">
       <form id="add-form" method="post" action="/click/add">
-        <button class="btn-add" id="add-btn" type="submit">Add +1</button>
+        <button class="btn-add" id="add-btn" type="submit">{{ADD_LABEL}}</button>
       </form>
       <form id="sub-form" method="post" action="/click/sub">
-        <button class="btn-sub" id="sub-btn" type="submit">Subtract -1</button>
+        <button class="btn-sub" id="sub-btn" type="submit">{{SUB_LABEL}}</button>{{SUB_CONFIRM}}
       </form>
     </section>
 
@@ -376,6 +596,19 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
     let statsData = null;
     let activeTab = 'daily';
 
+    // Dismissing hides this message for the session; a new message shows again.
+    const bannerEl = document.getElementById('maintenance');
+    if (bannerEl) {
+      const message = document.getElementById('maintenance-message').textContent;
+      if (sessionStorage.getItem('maintenance-dismissed') === message) {
+        bannerEl.hidden = true;
+      }
+      document.getElementById('maintenance-dismiss').addEventListener('click', () => {
+        sessionStorage.setItem('maintenance-dismissed', message);
+        bannerEl.hidden = true;
+      });
+    }
+
     const setStatus = (message, type) => {
       statusEl.textContent = message;
       statusEl.dataset.type = type || '';
@@ -580,17 +813,21 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
       await Promise.all([loadToday(), loadStats()]);
     };
 
-    const send = async (action) => {
+    const errorMessage = async (res) => {
+      const body = await res.json().catch(() => null);
+      return (body && body.error && body.error.message) || 'Request failed';
+    };
+
+    const send = async (action, confirm = false) => {
       setStatus('Saving...', 'info');
       const res = await fetch('/api/v1/click', {
         method: 'POST',
         headers: { 'content-type': 'application/json' },
-        body: JSON.stringify({ action })
+        body: JSON.stringify({ action, confirm })
       });
 
       if (!res.ok) {
-        const msg = await res.text();
-        throw new Error(msg || 'Request failed');
+        throw new Error(await errorMessage(res));
       }
 
       updateUI(await res.json());
@@ -613,11 +850,187 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
 
     subForm.addEventListener('submit', (event) => {
       event.preventDefault();
-      send('sub').catch((err) => setStatus(err.message, 'error'));
+      const confirmBox = subForm.elements.confirm;
+      const confirmed = Boolean(confirmBox && confirmBox.checked);
+      send('sub', confirmed)
+        .then(() => {
+          if (confirmBox) {
+            confirmBox.checked = false;
+          }
+        })
+        .catch((err) => setStatus(err.message, 'error'));
+    });
+
+    const setupEl = document.getElementById('setup');
+    const setupForm = document.getElementById('setup-form');
+
+    const loadSetup = async () => {
+      const res = await fetch('/api/v1/setup/status');
+      if (res.ok && (await res.json()).is_fresh) {
+        setupEl.hidden = false;
+      }
+    };
+
+    setupForm.addEventListener('submit', async (event) => {
+      event.preventDefault();
+      const body = {};
+      ['title', 'timezone'].forEach((name) => {
+        const value = setupForm.elements[name].value.trim();
+        if (value) {
+          body[name] = value;
+        }
+      });
+      const goal = setupForm.elements.daily_goal.value;
+      if (goal) {
+        body.daily_goal = Number(goal);
+      }
+      const res = await fetch('/api/v1/setup', {
+        method: 'POST',
+        headers: { 'content-type': 'application/json' },
+        body: JSON.stringify(body)
+      });
+      if (!res.ok) {
+        setStatus(await errorMessage(res), 'error');
+        return;
+      }
+      setupEl.hidden = true;
+      setStatus('Setup saved. Restart the server to apply it.', 'ok');
     });
 
     refresh().catch((err) => setStatus(err.message, 'error'));
+    loadSetup().catch(() => {});
   </script>
 </body>
 </html>
 "#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_mode_drops_google_fonts_import() {
+        let counts = DayCounts::default();
+        let online = render_index("2026-01-05", &counts, &Config::default(), None);
+        assert!(online.contains("fonts.googleapis.com"));
+
+        let config = Config {
+            offline: true,
+            ..Config::default()
+        };
+        let offline = render_index("2026-01-05", &counts, &config, None);
+        assert!(!offline.contains("fonts.googleapis.com"));
+        assert!(offline.contains("\"Space Grotesk\", \"Trebuchet MS\", sans-serif"));
+    }
+
+    #[test]
+    fn confirm_sub_adds_a_required_checkbox() {
+        let counts = DayCounts::default();
+        let plain = render_index("2026-01-05", &counts, &Config::default(), None);
+        assert!(!plain.contains("name=\"confirm\""));
+        assert!(!plain.contains("{{SUB_CONFIRM}}"));
+
+        let config = Config {
+            confirm_sub: true,
+            ..Config::default()
+        };
+        let guarded = render_index("2026-01-05", &counts, &config, None);
+        assert!(guarded.contains("name=\"confirm\" value=\"true\" required"));
+    }
+
+    #[test]
+    fn button_labels_default_and_are_escaped() {
+        let counts = DayCounts::default();
+        let plain = render_index("2026-01-05", &counts, &Config::default(), None);
+        assert!(plain.contains(">Add +1</button>"));
+        assert!(plain.contains(">Subtract -1</button>"));
+
+        let config = Config {
+            add_label: Some("Did it".to_string()),
+            sub_label: Some("<Skipped>".to_string()),
+            ..Config::default()
+        };
+        let custom = render_index("2026-01-05", &counts, &config, None);
+        assert!(custom.contains(">Did it</button>"));
+        assert!(custom.contains(">&lt;Skipped&gt;</button>"));
+    }
+
+    #[test]
+    fn huge_counts_do_not_wrap_negative() {
+        let page = render_page(
+            "{{ADD}} {{SUB}} {{NET}}",
+            "2026-01-05",
+            &DayCounts::new(u64::MAX, u64::MAX),
+            &Config::default(),
+            None,
+        );
+        assert_eq!(page, format!("{0} {0} 0", i64::MAX));
+    }
+
+    #[test]
+    fn maintenance_banner_is_escaped_and_optional() {
+        let counts = DayCounts::default();
+        let plain = render_index("2026-01-05", &counts, &Config::default(), None);
+        assert!(!plain.contains("id=\"maintenance\""));
+        assert!(!plain.contains("{{MAINTENANCE}}"));
+
+        let banner = render_index(
+            "2026-01-05",
+            &counts,
+            &Config::default(),
+            Some("Migrating <data> {{DATE}}"),
+        );
+        assert!(banner.contains("Migrating &lt;data&gt; {{DATE}}"));
+    }
+
+    #[tokio::test]
+    async fn template_file_overrides_the_embedded_page() {
+        let path =
+            std::env::temp_dir().join(format!("web_app_ui_template_{}.html", std::process::id()));
+        std::fs::write(&path, "<p>{{DATE}}: {{ADD}} - {{SUB}} = {{NET}}</p>").unwrap();
+        let template = UiTemplate::load(Some(path.clone()));
+
+        let page = render_page(
+            &template.current().await,
+            "2026-01-05",
+            &DayCounts::new(3, 1),
+            &Config::default(),
+            None,
+        );
+        assert_eq!(page, "<p>2026-01-05: 3 - 1 = 2</p>");
+
+        // Debug builds pick up edits without a restart.
+        std::fs::write(&path, "<p>edited {{NET}}</p>").unwrap();
+        if cfg!(debug_assertions) {
+            assert_eq!(&*template.current().await, "<p>edited {{NET}}</p>");
+        }
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(UiTemplate::load(Some(path)), UiTemplate::Embedded));
+    }
+
+    #[test]
+    fn page_stamp_moves_forward_on_every_change() {
+        let start = UNIX_EPOCH + Duration::from_millis(1_000_500);
+        let inputs = |version| PageInputs {
+            version,
+            date: "2026-01-05".to_string(),
+            maintenance: None,
+            template: None,
+        };
+        let mut stamp = PageStamp::new(start);
+        let first = stamp.update(inputs(0), start + Duration::from_secs(5));
+        assert_eq!(first, UNIX_EPOCH + Duration::from_secs(1_000));
+        assert_eq!(
+            stamp.update(inputs(0), start + Duration::from_secs(9)),
+            first
+        );
+
+        // Two changes within the same second still get distinct times.
+        let later = start + Duration::from_secs(20);
+        let second = stamp.update(inputs(1), later);
+        assert_eq!(second, UNIX_EPOCH + Duration::from_secs(1_020));
+        let third = stamp.update(inputs(2), later);
+        assert_eq!(third, second + Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,160 @@
+//! Fractional counting. Counts are always stored as integers; with
+//! `APP_UNIT_SCALE=n` one stored step is `1/n` of a unit, so `0.5` hours at a
+//! scale of 2 is stored as `1`. Amounts are converted to stored steps when a
+//! click comes in and count fields are divided by the scale on the way out.
+//! The data file, imports and sync always carry the stored integers, and with
+//! the default scale of 1 responses are exactly what they were without it.
+
+use axum::{
+    Json,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Response fields that hold counts (or per-count averages and rates) and
+/// are therefore divided by the scale.
+const COUNT_FIELDS: &[&str] = &[
+    "add",
+    "sub",
+    "net",
+    "add_count",
+    "sub_count",
+    "clicks_per_hour",
+    "today_contribution",
+    "min_net",
+    "max_net",
+    "best_net",
+    "worst_net",
+    "net_delta",
+    "prorated_net_delta",
+    "avg_add",
+    "avg_sub",
+    "avg_net",
+    "stddev_net",
+    "median_net",
+    "total_net",
+    "current",
+    "target",
+    "daily_rate",
+    "lo",
+    "hi",
+    "min",
+    "max",
+];
+
+/// Converts a client amount into stored steps. Returns `None` unless the
+/// amount is positive and a whole multiple of `1/scale`.
+pub fn to_steps(amount: f64, scale: u64) -> Option<u64> {
+    if !amount.is_finite() || amount <= 0.0 {
+        return None;
+    }
+    let steps = amount * scale as f64;
+    let rounded = steps.round();
+    if (steps - rounded).abs() > 1e-6 || rounded < 1.0 || rounded > u64::MAX as f64 {
+        return None;
+    }
+    Some(rounded as u64)
+}
+
+/// Formats stored steps for display, e.g. `3` at scale 2 becomes `1.5`.
+pub fn format_steps(steps: i64, scale: u64) -> String {
+    if scale == 1 {
+        steps.to_string()
+    } else {
+        (steps as f64 / scale as f64).to_string()
+    }
+}
+
+/// A JSON response whose count fields are divided by the unit scale.
+pub struct Scaled<T>(pub T, pub u64);
+
+impl<T: Serialize> IntoResponse for Scaled<T> {
+    fn into_response(self) -> Response {
+        let Scaled(body, scale) = self;
+        if scale == 1 {
+            return Json(body).into_response();
+        }
+        match scaled_value(&body, scale) {
+            Ok(value) => Json(value).into_response(),
+            Err(err) => crate::errors::AppError::internal(err).into_response(),
+        }
+    }
+}
+
+/// `body` as JSON with its count fields divided by the unit scale, for
+/// payloads that don't go out as a plain response.
+pub fn scaled_value<T: Serialize>(body: &T, scale: u64) -> serde_json::Result<Value> {
+    let mut value = serde_json::to_value(body)?;
+    if scale != 1 {
+        unscale(&mut value, scale);
+    }
+    Ok(value)
+}
+
+fn unscale(value: &mut Value, scale: u64) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                match field {
+                    Value::Object(counts) if key == "actions" => {
+                        // Custom action names map straight to counts.
+                        for count in counts.values_mut() {
+                            if let Some(number) = count.as_f64() {
+                                *count = Value::from(number / scale as f64);
+                            }
+                        }
+                    }
+                    _ => match field.as_f64() {
+                        Some(number) if COUNT_FIELDS.contains(&key.as_str()) => {
+                            *field = Value::from(number / scale as f64);
+                        }
+                        _ => unscale(field, scale),
+                    },
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| unscale(item, scale)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts_must_be_whole_steps() {
+        assert_eq!(to_steps(1.0, 1), Some(1));
+        assert_eq!(to_steps(0.5, 2), Some(1));
+        assert_eq!(to_steps(1.5, 2), Some(3));
+        assert_eq!(to_steps(0.1, 10), Some(1));
+        assert_eq!(to_steps(0.5, 1), None);
+        assert_eq!(to_steps(0.3, 2), None);
+        assert_eq!(to_steps(0.0, 2), None);
+        assert_eq!(to_steps(-1.0, 2), None);
+        assert_eq!(to_steps(f64::NAN, 2), None);
+    }
+
+    #[test]
+    fn count_fields_are_divided_and_others_left_alone() {
+        let mut value = serde_json::json!({
+            "date": "2026-01-05",
+            "net": 3,
+            "days_counted": 7,
+            "last_7_days": [{ "add_count": 4, "sub_count": 1 }],
+            "percent_change": null,
+            "actions": { "jump": 5 },
+        });
+        unscale(&mut value, 2);
+        assert_eq!(value["net"], 1.5);
+        assert_eq!(value["days_counted"], 7);
+        assert_eq!(value["last_7_days"][0]["add_count"], 2.0);
+        assert!(value["percent_change"].is_null());
+        assert_eq!(value["actions"]["jump"], 2.5);
+
+        assert_eq!(format_steps(3, 2), "1.5");
+        assert_eq!(format_steps(4, 2), "2");
+        assert_eq!(format_steps(-3, 1), "-3");
+    }
+}
@@ -0,0 +1,2308 @@
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use chrono::{Local, NaiveDate, TimeZone, Utc};
+use futures::future::join_all;
+use http_body_util::BodyExt;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tower::ServiceExt;
+use web_app::clock::{AccountingZone, FixedClock};
+use web_app::models::{AppData, CountMode, DayCounts};
+use web_app::{AppState, Config, MemoryStorage, Storage};
+
+fn unique_data_path() -> PathBuf {
+    // Tests run in parallel and can read the clock in the same tick.
+    static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let n = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let mut path = std::env::temp_dir();
+    path.push(format!(
+        "web_app_api_{}_{}_{n}.json",
+        std::process::id(),
+        nanos
+    ));
+    path
+}
+
+fn test_app() -> Router {
+    test_app_with(AppData::default())
+}
+
+fn test_app_with(data: AppData) -> Router {
+    let state = AppState::new(unique_data_path(), data, Config::default());
+    web_app::router(state)
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+    (status, body)
+}
+
+fn get(uri: &str) -> Request<Body> {
+    Request::get(uri).body(Body::empty()).unwrap()
+}
+
+fn post_json(uri: &str, body: Value) -> Request<Body> {
+    Request::post(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn put_json(uri: &str, body: Value) -> Request<Body> {
+    Request::put(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_clicks_are_not_lost() {
+    let path = unique_data_path();
+    let app = web_app::router(AppState::new(
+        path.clone(),
+        AppData::default(),
+        Config::default(),
+    ));
+
+    let requests = (0..100).map(|_| {
+        let app = app.clone();
+        tokio::spawn(async move {
+            send(
+                &app,
+                post_json("/api/click", serde_json::json!({ "action": "add" })),
+            )
+            .await
+        })
+    });
+    for result in join_all(requests).await {
+        let (status, _) = result.unwrap();
+        assert!(status.is_success());
+    }
+
+    let (status, today) = send(&app, get("/api/today")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(today["add_count"], 100);
+    assert_eq!(today["sub_count"], 0);
+    assert_eq!(today["net"], 100);
+
+    // Writes go out after the lock is released, possibly out of order; the
+    // file must still end up with the newest snapshot.
+    let (saved, _) = web_app::load_data(&path).await;
+    let date = today["date"].as_str().unwrap();
+    assert_eq!(saved.days[date].add, 100);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn debounced_writes_keep_every_rapid_click() {
+    let path = unique_data_path();
+    let config = Config {
+        persist_debounce_ms: Some(100),
+        ..Config::default()
+    };
+    let state = AppState::new(path.clone(), AppData::default(), config);
+    tokio::spawn(web_app::tasks::run_persist_flusher(
+        state.clone(),
+        std::time::Duration::from_millis(100),
+    ));
+    let app = web_app::router(state.clone());
+
+    let clicks = (0..50).map(|_| {
+        let app = app.clone();
+        tokio::spawn(async move {
+            send(
+                &app,
+                post_json("/api/click", serde_json::json!({ "action": "add" })),
+            )
+            .await
+        })
+    });
+    for result in join_all(clicks).await {
+        assert!(result.unwrap().0.is_success());
+    }
+
+    let saved_adds = || async {
+        let (data, _) = web_app::load_data(&path).await;
+        data.days.values().map(|counts| counts.add).sum::<u64>()
+    };
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(3);
+    while saved_adds().await != 50 {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "clicks never reached the file"
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(state.metrics.persist_latency.summary().count < 50);
+
+    // Shutdown flushes whatever the timer hasn't written yet.
+    let (status, _) = send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+    assert!(status.is_success());
+    state.flush().await.unwrap();
+    assert_eq!(saved_adds().await, 51);
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn single_count_mode_floors_at_zero() {
+    let path = unique_data_path();
+    let log = web_app::EventLog::beside(&path);
+    let config = Config {
+        count_mode: CountMode::Single,
+        event_log: true,
+        ..Config::default()
+    };
+    let state = AppState::new(path.clone(), AppData::default(), config).with_event_log(log.clone());
+    let app = web_app::router(state.clone());
+    let click = |action: &str, amount: u64| {
+        post_json(
+            "/api/click",
+            serde_json::json!({ "action": action, "amount": amount }),
+        )
+    };
+
+    let (_, body) = send(&app, click("add", 2)).await;
+    assert_eq!(body["net"], 2);
+    let (status, body) = send(&app, click("sub", 5)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["add_count"], 0);
+    assert_eq!(body["sub_count"], 0);
+    assert_eq!(body["net"], 0);
+
+    // Undo gives back only what the floored click took.
+    let (_, body) = send(
+        &app,
+        Request::post("/api/undo").body(Body::empty()).unwrap(),
+    )
+    .await;
+    assert_eq!(body["net"], 2);
+    let (_, body) = send(&app, click("sub", 1)).await;
+    assert_eq!(body["net"], 1);
+
+    // A restart replays the logged click the same way.
+    let (mut replayed, _) = web_app::load_data(&path).await;
+    assert!(log.replay(&mut replayed).await.is_empty());
+    let counts = replayed.days.values().next().unwrap();
+    assert_eq!((counts.add, counts.sub), (1, 0));
+
+    let (_, config) = send(&app, get("/api/config")).await;
+    assert_eq!(config["count_mode"], "single");
+
+    let _ = std::fs::remove_file(path);
+    let _ = std::fs::remove_file(log.path());
+}
+
+#[tokio::test]
+async fn event_log_takes_clicks_until_compaction() {
+    let path = unique_data_path();
+    let log = web_app::EventLog::beside(&path);
+    let config = Config {
+        event_log: true,
+        ..Config::default()
+    };
+    let state = AppState::new(path.clone(), AppData::default(), config).with_event_log(log.clone());
+    let app = web_app::router(state.clone());
+
+    for action in ["add", "add", "sub"] {
+        let (status, _) = send(
+            &app,
+            post_json("/api/click", serde_json::json!({ "action": action })),
+        )
+        .await;
+        assert!(status.is_success());
+    }
+    assert!(!path.exists());
+    let logged = std::fs::read_to_string(log.path()).unwrap();
+    assert_eq!(logged.lines().count(), 3);
+
+    // What a restart before compaction would see.
+    let mut replayed = AppData::default();
+    assert!(log.replay(&mut replayed).await.is_empty());
+    assert_eq!(
+        replayed.days.values().map(|counts| counts.add).sum::<u64>(),
+        2
+    );
+
+    state.flush().await.unwrap();
+    assert_eq!(std::fs::read_to_string(log.path()).unwrap(), "");
+    let (saved, _) = web_app::load_data(&path).await;
+    assert_eq!(saved.days.values().map(|counts| counts.sub).sum::<u64>(), 1);
+    assert_eq!(saved.log_seq, 3);
+
+    let _ = std::fs::remove_file(path);
+    let _ = std::fs::remove_file(log.path());
+}
+
+#[tokio::test]
+async fn cors_preflight_allows_listed_origins() {
+    let config = Config {
+        cors_origins: vec!["https://spa.example".to_string()],
+        ..Config::default()
+    };
+    let app = web_app::router(AppState::new(
+        unique_data_path(),
+        AppData::default(),
+        config,
+    ));
+    let preflight = |origin: &str| {
+        Request::options("/api/click")
+            .header("origin", origin)
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "content-type")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(preflight("https://spa.example"))
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    let headers = response.headers();
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://spa.example"
+    );
+    assert!(
+        headers["access-control-allow-methods"]
+            .to_str()
+            .unwrap()
+            .contains("POST")
+    );
+    assert!(
+        headers["access-control-allow-headers"]
+            .to_str()
+            .unwrap()
+            .contains("content-type")
+    );
+
+    let response = app
+        .clone()
+        .oneshot(preflight("https://elsewhere.example"))
+        .await
+        .unwrap();
+    assert!(
+        !response
+            .headers()
+            .contains_key("access-control-allow-origin")
+    );
+
+    let response = app
+        .oneshot(
+            Request::get("/api/today")
+                .header("origin", "https://spa.example")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["access-control-allow-origin"],
+        "https://spa.example"
+    );
+}
+
+#[tokio::test]
+async fn api_token_guards_writes_only() {
+    let config = Config {
+        api_token: Some("s3cret".to_string()),
+        ..Config::default()
+    };
+    let app = web_app::router(AppState::new(
+        unique_data_path(),
+        AppData::default(),
+        config,
+    ));
+    let click = |authorization: Option<&str>| {
+        let mut request = Request::post("/api/click").header("content-type", "application/json");
+        if let Some(value) = authorization {
+            request = request.header("authorization", value);
+        }
+        request.body(Body::from(r#"{"action":"add"}"#)).unwrap()
+    };
+
+    for authorization in [None, Some("Bearer wrong"), Some("Basic s3cret")] {
+        let response = app.clone().oneshot(click(authorization)).await.unwrap();
+        assert_eq!(
+            response.status(),
+            StatusCode::UNAUTHORIZED,
+            "{authorization:?}"
+        );
+        assert_eq!(response.headers()["www-authenticate"], "Bearer");
+    }
+    let response = app
+        .clone()
+        .oneshot(Request::post("/click/add").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let (status, body) = send(&app, click(Some("Bearer s3cret"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["add_count"], 1);
+
+    // Reads and previews need no token.
+    let (status, _) = send(&app, get("/api/today")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, get("/")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(
+        &app,
+        post_json(
+            "/api/v1/click/preview",
+            serde_json::json!({ "action": "add" }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn websocket_clients_get_each_click_pushed() {
+    use futures::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let app = test_app();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(axum::serve(listener, app.clone().into_make_service()).into_future());
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/api/ws"))
+        .await
+        .unwrap();
+    for (action, expected_net) in [("add", 1), ("add", 2), ("sub", 1)] {
+        let (status, _) = send(
+            &app,
+            post_json("/api/click", serde_json::json!({ "action": action })),
+        )
+        .await;
+        assert!(status.is_success());
+
+        let message = tokio::time::timeout(std::time::Duration::from_secs(3), socket.next())
+            .await
+            .expect("no update pushed")
+            .unwrap()
+            .unwrap();
+        let Message::Text(text) = message else {
+            panic!("expected a text frame, got {message:?}");
+        };
+        let update: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(update["net"], expected_net);
+    }
+
+    socket.close(None).await.unwrap();
+    // The server keeps working after the client went away.
+    let (status, _) = send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+    assert!(status.is_success());
+}
+
+#[tokio::test]
+async fn stats_stream_sends_stats_on_connect_and_after_clicks() {
+    let state = AppState::new(unique_data_path(), AppData::default(), Config::default());
+    let app = web_app::router(state.clone());
+    let response = app.clone().oneshot(get("/api/stats/stream")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let mut body = response.into_body();
+    let mut next_event = async || {
+        let frame = tokio::time::timeout(std::time::Duration::from_secs(3), body.frame())
+            .await
+            .expect("no event sent")
+            .unwrap()
+            .unwrap();
+        let text = String::from_utf8(frame.into_data().unwrap().to_vec()).unwrap();
+        let data = text
+            .strip_prefix("event: stats\ndata: ")
+            .and_then(|rest| rest.strip_suffix("\n\n"))
+            .unwrap_or_else(|| panic!("unexpected frame {text:?}"));
+        serde_json::from_str::<Value>(data).unwrap()
+    };
+
+    let initial = next_event().await;
+    assert_eq!(initial["last_7_days"][6]["net"], 0);
+
+    let (status, _) = send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+    assert!(status.is_success());
+    let refreshed = next_event().await;
+    assert_eq!(refreshed["last_7_days"][6]["net"], 1);
+
+    // Shutting down ends the stream instead of holding the server open.
+    state.shutdown.send_replace(true);
+    let end = tokio::time::timeout(std::time::Duration::from_secs(3), body.frame())
+        .await
+        .expect("stream kept running");
+    assert!(end.is_none());
+}
+
+#[tokio::test]
+async fn rapid_clicks_from_one_client_get_429() {
+    let config = Config {
+        click_rate_limit: Some(3),
+        trust_proxy: true,
+        ..Config::default()
+    };
+    let app = web_app::router(AppState::new(
+        unique_data_path(),
+        AppData::default(),
+        config,
+    ));
+    let click_from = |ip: &str| {
+        Request::post("/api/click")
+            .header("content-type", "application/json")
+            .header("x-forwarded-for", ip)
+            .body(Body::from(r#"{"action":"add"}"#))
+            .unwrap()
+    };
+
+    let mut statuses = Vec::new();
+    for _ in 0..5 {
+        let response = app
+            .clone()
+            .oneshot(click_from("203.0.113.7"))
+            .await
+            .unwrap();
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            assert!(response.headers().contains_key("retry-after"));
+        }
+        statuses.push(response.status());
+    }
+    assert_eq!(&statuses[..3], [StatusCode::OK; 3]);
+    assert_eq!(&statuses[3..], [StatusCode::TOO_MANY_REQUESTS; 2]);
+
+    let form = Request::post("/click/add")
+        .header("x-forwarded-for", "203.0.113.7")
+        .body(Body::empty())
+        .unwrap();
+    let (status, _) = send(&app, form).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let (status, _) = send(&app, click_from("198.51.100.2")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["add_count"], 4);
+}
+
+#[tokio::test]
+async fn healthz_answers_while_the_data_lock_is_held() {
+    let state = AppState::new(unique_data_path(), AppData::default(), Config::default());
+    let app = web_app::router(state.clone());
+    let _guard = state.data.lock().await;
+
+    let (status, body) = send(&app, get("/healthz")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, serde_json::json!({ "status": "ok" }));
+}
+
+#[tokio::test]
+async fn stats_can_be_anchored_at_an_instant() {
+    let mut data = AppData::default();
+    data.days
+        .insert("2026-01-05".to_string(), DayCounts::new(3, 1));
+    let app = test_app_with(data);
+
+    let (status, stats) = send(&app, get("/api/stats?at=2026-01-05T12:00:00Z")).await;
+    assert_eq!(status, StatusCode::OK);
+    let last = &stats["last_7_days"][6];
+    assert_eq!(last["date"], "2026-01-05");
+    assert_eq!(last["net"], 2);
+
+    let (status, _) = send(&app, get("/api/stats?at=yesterday")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn stats_and_today_answer_304_until_a_click() {
+    let app = test_app();
+    let conditional = |uri: &str, etag: &str| {
+        Request::get(uri)
+            .header("if-none-match", etag)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    for uri in ["/api/stats", "/api/today"] {
+        let response = app.clone().oneshot(get(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+        let response = app.clone().oneshot(conditional(uri, &etag)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{uri}");
+        assert_eq!(response.headers()["etag"], etag.as_str());
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(body.is_empty());
+
+        let (status, _) = send(
+            &app,
+            post_json("/api/click", serde_json::json!({ "action": "add" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let response = app.clone().oneshot(conditional(uri, &etag)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{uri}");
+        assert_ne!(response.headers()["etag"], etag.as_str());
+    }
+
+    let response = app.clone().oneshot(get("/api/today?now=1")).await.unwrap();
+    assert!(response.headers().get("etag").is_none());
+}
+
+#[tokio::test]
+async fn index_answers_304_when_not_modified_since() {
+    let app = test_app();
+    let conditional = |since: &str| {
+        Request::get("/")
+            .header("if-modified-since", since)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(get("/")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let last_modified = response.headers()["last-modified"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(last_modified.ends_with(" GMT"), "{last_modified}");
+
+    let recent = (Utc::now() + chrono::Duration::minutes(1))
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string();
+    for since in [last_modified.as_str(), recent.as_str()] {
+        let response = app.clone().oneshot(conditional(since)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{since}");
+        assert_eq!(response.headers()["last-modified"], last_modified.as_str());
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(body.is_empty());
+    }
+
+    for since in ["Sat, 01 Jan 2000 00:00:00 GMT", "yesterday"] {
+        let response = app.clone().oneshot(conditional(since)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{since}");
+    }
+
+    // The page shows today's counts, so a click modifies it.
+    let (status, _) = send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let response = app
+        .clone()
+        .oneshot(conditional(&last_modified))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()["last-modified"], last_modified.as_str());
+}
+
+#[tokio::test]
+async fn openapi_document_describes_the_click_endpoint() {
+    let app = test_app();
+    for uri in ["/api/openapi.json", "/api/v1/openapi.json"] {
+        let response = app.clone().oneshot(get(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/json");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let doc: Value = serde_json::from_slice(&body).expect("valid JSON");
+
+        assert!(doc["openapi"].as_str().unwrap().starts_with("3."));
+        let click = &doc["paths"]["/click"]["post"];
+        assert_eq!(
+            click["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/ClickRequest"
+        );
+        for schema in ["ClickRequest", "DailyCountsResponse", "StatsResponse"] {
+            assert!(
+                doc["components"]["schemas"][schema].is_object(),
+                "{uri} is missing {schema}"
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn stats_weeks_parameter_sets_the_weekly_window() {
+    let app = test_app();
+
+    for (uri, expected) in [
+        ("/api/stats", 8),
+        ("/api/stats?weeks=12", 12),
+        ("/api/v1/stats?weeks=3", 3),
+        ("/api/stats?weeks=0", 1),
+        ("/api/stats?weeks=100", 52),
+    ] {
+        let (status, stats) = send(&app, get(uri)).await;
+        assert_eq!(status, StatusCode::OK, "{uri}");
+        assert_eq!(
+            stats["weekly_totals"].as_array().unwrap().len(),
+            expected,
+            "{uri}"
+        );
+        assert_eq!(
+            stats["weekly_averages"].as_array().unwrap().len(),
+            expected,
+            "{uri}"
+        );
+    }
+
+    let (status, _) = send(&app, get("/api/stats?weeks=many")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn today_reports_clicks_per_hour() {
+    let now = Local
+        .with_ymd_and_hms(2026, 1, 5, 10, 0, 0)
+        .unwrap()
+        .with_timezone(&Utc);
+    let mut data = AppData::default();
+    data.days
+        .insert("2026-01-05".to_string(), DayCounts::new(4, 1));
+    let state = AppState::new(unique_data_path(), data, Config::default())
+        .with_clock(Arc::new(FixedClock(now)));
+    let app = web_app::router(state);
+
+    let (status, today) = send(&app, get("/api/today")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(today["date"], "2026-01-05");
+    assert_eq!(today["clicks_per_hour"], 0.5);
+    assert!(today.get("server_time").is_none());
+
+    let (status, today) = send(&app, get("/api/today?now=1")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(today["server_time"], now.with_timezone(&Local).to_rfc3339());
+}
+
+#[tokio::test]
+async fn app_timezone_decides_the_day_key() {
+    // 03:30 UTC on Jan 6 is still Jan 5 in New York.
+    let now = Utc.with_ymd_and_hms(2026, 1, 6, 3, 30, 0).unwrap();
+    let app_in = |zone: &str| {
+        let config = Config {
+            timezone: AccountingZone::parse(zone).unwrap(),
+            ..Config::default()
+        };
+        let state = AppState::new(unique_data_path(), AppData::default(), config)
+            .with_clock(Arc::new(FixedClock(now)));
+        web_app::router(state)
+    };
+
+    let (_, today) = send(&app_in("UTC"), get("/api/today")).await;
+    assert_eq!(today["date"], "2026-01-06");
+
+    let app = app_in("America/New_York");
+    let (_, body) = send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+    assert_eq!(body["date"], "2026-01-05");
+    let (_, config) = send(&app, get("/api/config")).await;
+    assert_eq!(config["accounting_timezone"], "America/New_York");
+    assert_eq!(config["accounting_utc_offset"], "-05:00");
+}
+
+#[tokio::test]
+async fn metrics_expose_data_gauges() {
+    let app = test_app();
+    send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+
+    let response = app.clone().oneshot(get("/metrics")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8(bytes.to_vec()).unwrap();
+    assert!(body.contains("# TYPE clickcounter_today_net gauge"));
+    assert!(body.contains("clickcounter_today_net 1\n"));
+    assert!(body.contains("clickcounter_week_net 1\n"));
+    assert!(body.contains("clickcounter_current_streak_days 1\n"));
+    assert!(body.contains("clickcounter_persist_duration_seconds_count 1\n"));
+    assert!(body.contains("# TYPE clickcounter_clicks_total counter"));
+    assert!(body.contains("clickcounter_clicks_total{action=\"add\"} 1\n"));
+    assert!(body.contains("clickcounter_clicks_total{action=\"sub\"} 0\n"));
+
+    send(
+        &app,
+        post_json(
+            "/api/click",
+            serde_json::json!({ "action": "add", "amount": 2 }),
+        ),
+    )
+    .await;
+    let response = app.clone().oneshot(get("/metrics")).await.unwrap();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let body = String::from_utf8(bytes.to_vec()).unwrap();
+    assert!(body.contains("clickcounter_clicks_total{action=\"add\"} 3\n"));
+    assert!(body.contains("clickcounter_today_net 3\n"));
+}
+
+#[tokio::test]
+async fn confirm_sub_requires_explicit_confirmation() {
+    let config = Config {
+        confirm_sub: true,
+        ..Config::default()
+    };
+    let state = AppState::new(unique_data_path(), AppData::default(), config);
+    let app = web_app::router(state);
+
+    for uri in ["/api/click", "/api/click/preview"] {
+        let (status, _) = send(&app, post_json(uri, serde_json::json!({ "action": "sub" }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+    }
+    let (status, preview) = send(
+        &app,
+        post_json(
+            "/api/click/preview",
+            serde_json::json!({ "action": "sub", "confirm": true }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(preview["sub_count"], 1);
+
+    let response = app
+        .clone()
+        .oneshot(Request::post("/click/sub").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let (status, body) = send(
+        &app,
+        post_json(
+            "/api/click",
+            serde_json::json!({ "action": "sub", "confirm": true }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["sub_count"], 1);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::post("/click/sub")
+                .header("content-type", "application/x-www-form-urlencoded")
+                .body(Body::from("confirm=true"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["sub_count"], 2);
+
+    let (status, _) = send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn datastamp_changes_after_a_click() {
+    let app = test_app();
+
+    let (status, before) = send(&app, get("/api/datastamp")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(before["version"], 0);
+    assert!(before["modified"].is_null());
+
+    send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+
+    let (_, after) = send(&app, get("/api/datastamp")).await;
+    assert_eq!(after["version"], 1);
+    assert_ne!(after["sha256"], before["sha256"]);
+    assert!(after["modified"].is_string());
+}
+
+#[tokio::test]
+async fn day_tags_are_validated_and_surface_in_stats() {
+    let app = test_app();
+
+    let (status, body) = send(
+        &app,
+        post_json(
+            "/api/day/2026-01-05/tags",
+            serde_json::json!({ "tags": ["\u{1F600}", " focus ", "focus"] }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["tags"], serde_json::json!(["\u{1F600}", "focus"]));
+
+    let (status, _) = send(
+        &app,
+        post_json(
+            "/api/day/2026-01-05/tags",
+            serde_json::json!({ "tags": ["a", "b", "c", "d", "e", "f"] }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send(
+        &app,
+        post_json(
+            "/api/day/2026-1-5/tags",
+            serde_json::json!({ "tags": ["ok"] }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_, stats) = send(&app, get("/api/stats?at=2026-01-06T12:00:00Z")).await;
+    let day = &stats["last_7_days"][5];
+    assert_eq!(day["date"], "2026-01-05");
+    assert_eq!(day["tags"], serde_json::json!(["\u{1F600}", "focus"]));
+}
+
+#[tokio::test]
+async fn grafana_query_returns_daily_datapoints() {
+    let mut data = AppData::default();
+    data.days
+        .insert("2026-01-06".to_string(), DayCounts::new(5, 2));
+    let app = test_app_with(data);
+
+    let (status, _) = send(&app, get("/grafana/")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, targets) = send(&app, post_json("/grafana/search", serde_json::json!({}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(targets, serde_json::json!(["net", "add", "sub"]));
+
+    let from = Local.with_ymd_and_hms(2026, 1, 5, 12, 0, 0).unwrap();
+    let to = Local.with_ymd_and_hms(2026, 1, 7, 12, 0, 0).unwrap();
+    let query = serde_json::json!({
+        "range": { "from": from.to_rfc3339(), "to": to.to_rfc3339() },
+        "targets": [{ "target": "net" }, { "target": "add" }],
+    });
+    let (status, series) = send(&app, post_json("/grafana/query", query)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(series[0]["target"], "net");
+    assert_eq!(series[0]["datapoints"].as_array().unwrap().len(), 3);
+    assert_eq!(series[0]["datapoints"][1][0], 3.0);
+    assert_eq!(series[1]["datapoints"][1][0], 5.0);
+
+    let query = serde_json::json!({
+        "range": { "from": from.to_rfc3339(), "to": to.to_rfc3339() },
+        "targets": [{ "target": "bogus" }],
+    });
+    let (status, _) = send(&app, post_json("/grafana/query", query)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn failed_write_leaves_counts_unchanged() {
+    let data_path = unique_data_path().join("missing-dir").join("state.json");
+    let config = Config {
+        sync_writes: true,
+        ..Config::default()
+    };
+    let app = web_app::router(AppState::new(data_path, AppData::default(), config));
+    let etag = |response: &axum::response::Response| response.headers()["etag"].clone();
+    let before = etag(&app.clone().oneshot(get("/api/today")).await.unwrap());
+
+    let (status, _) = send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let response = app.clone().oneshot(get("/api/today")).await.unwrap();
+    // The click was visible while its write was in flight, so validators
+    // from before it must not be reused.
+    assert_ne!(etag(&response), before);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let today: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(today["add_count"], 0);
+
+    // Every other write is taken back too.
+    let imported = serde_json::json!({ "days": { "2026-01-05": { "add": 3, "sub": 0 } } });
+    for request in [
+        post_json("/api/import", imported.clone()),
+        post_json(
+            "/api/sync",
+            serde_json::json!({ "data": imported, "version": 1 }),
+        ),
+        post_json(
+            "/api/day/2026-01-05/tags",
+            serde_json::json!({ "tags": ["focus"] }),
+        ),
+        put_json(
+            "/api/week/2026-W02/note",
+            serde_json::json!({ "note": "busy" }),
+        ),
+    ] {
+        let uri = request.uri().to_string();
+        let (status, _) = send(&app, request).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR, "{uri}");
+    }
+    let (status, _) = send(
+        &app,
+        Request::post("/api/import.csv")
+            .header("content-type", "text/csv")
+            .body(Body::from("2026-01-05,3,0,3\n"))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (_, week) = send(&app, get("/api/week/2026-W02")).await;
+    assert!(week["note"].is_null());
+    let (_, history) = send(&app, get("/api/history")).await;
+    assert_eq!(history["total"], 0);
+}
+
+#[tokio::test]
+async fn failed_auto_add_is_not_marked_done() {
+    let data_path = unique_data_path().join("missing-dir").join("state.json");
+    let state = AppState::new(data_path, AppData::default(), Config::default());
+    let today = NaiveDate::from_ymd_opt(2026, 1, 5).unwrap();
+
+    assert!(web_app::tasks::auto_add(&state, today, 2).await.is_err());
+    let data = state.data.lock().await;
+    assert!(data.last_auto_add.is_none());
+    assert!(data.days.is_empty());
+}
+
+#[tokio::test]
+async fn click_can_include_the_updated_stats() {
+    let app = test_app();
+    let add = serde_json::json!({ "action": "add" });
+
+    let (status, body) = send(&app, post_json("/api/click", add.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["add_count"], 1);
+    assert!(body.get("stats").is_none());
+
+    let (status, body) = send(&app, post_json("/api/v1/click?include=stats", add.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["today"]["add_count"], 2);
+    let last_7_days = body["stats"]["last_7_days"].as_array().unwrap();
+    assert_eq!(last_7_days.len(), 7);
+    assert_eq!(last_7_days[6]["date"], body["today"]["date"]);
+    assert_eq!(last_7_days[6]["add_count"], 2);
+    assert_eq!(body["stats"]["streaks"]["current_streak"], 1);
+
+    let (status, _) = send(&app, post_json("/api/click?include=chart", add)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["add_count"], 2);
+}
+
+#[tokio::test]
+async fn backups_are_only_taken_before_whole_state_writes() {
+    use web_app::storage::backup_path;
+
+    let path = unique_data_path();
+    let storage = Arc::new(web_app::JsonStorage::new(path.clone()).with_backups(3));
+    let state =
+        AppState::new(path.clone(), AppData::default(), Config::default()).with_storage(storage);
+    let app = web_app::router(state);
+
+    for _ in 0..2 {
+        let (status, _) = send(
+            &app,
+            post_json("/api/click", serde_json::json!({ "action": "add" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    assert!(!backup_path(&path, 1).exists());
+
+    let imported = serde_json::json!({ "days": { "2026-01-05": { "add": 3, "sub": 0 } } });
+    let (status, _) = send(&app, post_json("/api/import", imported)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (backup, _) = web_app::load_data(&backup_path(&path, 1)).await;
+    let today = Local::now().date_naive().to_string();
+    assert_eq!(backup.days[&today].add, 2);
+    assert!(!backup_path(&path, 2).exists());
+
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(backup_path(&path, 1));
+}
+
+#[tokio::test]
+async fn memory_storage_counts_without_touching_disk() {
+    let dir = unique_data_path();
+    let config = Config {
+        sync_writes: true,
+        ..Config::default()
+    };
+    let storage = Arc::new(MemoryStorage::new());
+    let state = AppState::new(dir.join("state.json"), AppData::default(), config)
+        .with_storage(storage.clone());
+    let app = web_app::router(state);
+
+    for _ in 0..2 {
+        let (status, _) = send(
+            &app,
+            post_json("/api/click", serde_json::json!({ "action": "add" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["add_count"], 2);
+
+    assert!(!dir.exists());
+    let (stored, _) = storage.load().await;
+    assert!(stored.days.is_empty());
+}
+
+#[tokio::test]
+async fn whoami_reports_forwarded_client_behind_a_trusted_proxy() {
+    let config = Config {
+        trust_proxy: true,
+        ..Config::default()
+    };
+    let app = web_app::router(AppState::new(
+        unique_data_path(),
+        AppData::default(),
+        config,
+    ));
+
+    let request = Request::get("/api/whoami")
+        .header("x-forwarded-for", "203.0.113.7")
+        .header("user-agent", "counter-test/1.0")
+        .body(Body::empty())
+        .unwrap();
+    let (status, body) = send(&app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["client_ip"], "203.0.113.7");
+    assert_eq!(body["user_agent"], "counter-test/1.0");
+    assert!(body["utc_offset"].is_string());
+
+    let (_, body) = send(&test_app(), get("/api/whoami")).await;
+    assert!(body["client_ip"].is_null());
+}
+
+#[tokio::test]
+async fn click_amount_counts_several_at_once() {
+    let app = test_app();
+
+    let (status, body) = send(
+        &app,
+        post_json(
+            "/api/v1/click",
+            serde_json::json!({ "action": "add", "amount": 5 }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["add_count"], 5);
+    assert_eq!(body["sub_count"], 0);
+
+    for amount in [0.0, -2.0, 1.5] {
+        let (status, _) = send(
+            &app,
+            post_json(
+                "/api/click",
+                serde_json::json!({ "action": "sub", "amount": amount }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{amount}");
+    }
+
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["add_count"], 5);
+    assert_eq!(today["sub_count"], 0);
+}
+
+#[tokio::test]
+async fn click_on_backfills_past_days_only() {
+    let app = test_app();
+    let yesterday = (Local::now().date_naive() - chrono::Duration::days(1)).to_string();
+    let tomorrow = (Local::now().date_naive() + chrono::Duration::days(1)).to_string();
+
+    let (status, body) = send(
+        &app,
+        post_json(
+            &format!("/api/click/{yesterday}"),
+            serde_json::json!({ "action": "add", "amount": 2 }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["date"], yesterday);
+    assert_eq!(body["add_count"], 2);
+
+    let (_, stats) = send(&app, get("/api/stats")).await;
+    assert_eq!(stats["last_7_days"][5]["add_count"], 2);
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["add_count"], 0);
+
+    for date in [tomorrow.as_str(), "2026-02-30", "yesterday"] {
+        let (status, _) = send(
+            &app,
+            post_json(
+                &format!("/api/click/{date}"),
+                serde_json::json!({ "action": "add" }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{date}");
+    }
+}
+
+#[tokio::test]
+async fn get_day_returns_any_days_counts() {
+    let mut data = AppData::default();
+    data.days
+        .insert("2026-01-05".to_string(), DayCounts::new(4, 1));
+    let app = test_app_with(data);
+
+    let (status, day) = send(&app, get("/api/day/2026-01-05")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(day["date"], "2026-01-05");
+    assert_eq!(day["add_count"], 4);
+    assert_eq!(day["sub_count"], 1);
+    assert_eq!(day["net"], 3);
+    assert!(day["clicks_per_hour"].is_null());
+
+    let (status, day) = send(&app, get("/api/v1/day/2019-07-01")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(day["date"], "2019-07-01");
+    assert_eq!(day["add_count"], 0);
+    assert_eq!(day["net"], 0);
+
+    let (_, day) = send(&app, get("/api/day/2026-01-05?counter=coffee")).await;
+    assert_eq!(day["add_count"], 0);
+
+    for date in ["2026-1-5", "2026-02-30", "today"] {
+        let (status, _) = send(&app, get(&format!("/api/day/{date}"))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{date}");
+    }
+}
+
+#[tokio::test]
+async fn delete_day_clears_counts_idempotently() {
+    let app = test_app();
+    send(
+        &app,
+        post_json(
+            "/api/click",
+            serde_json::json!({ "action": "add", "amount": 3 }),
+        ),
+    )
+    .await;
+    let (_, today) = send(&app, get("/api/today")).await;
+    let date = today["date"].as_str().unwrap().to_string();
+    assert_eq!(today["add_count"], 3);
+
+    for _ in 0..2 {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::delete(format!("/api/day/{date}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["add_count"], 0);
+        assert_eq!(body["net"], 0);
+    }
+
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["add_count"], 0);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::delete("/api/day/2026-1-5")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn put_day_overwrites_or_creates_exact_totals() {
+    let mut data = AppData::default();
+    let mut tagged = DayCounts::new(7, 2);
+    tagged.tags = vec!["focus".to_string()];
+    data.days.insert("2026-01-05".to_string(), tagged);
+    let app = test_app_with(data);
+
+    let (status, body) = send(
+        &app,
+        put_json(
+            "/api/day/2026-01-05",
+            serde_json::json!({ "add": 3, "sub": 4 }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["date"], "2026-01-05");
+    assert_eq!(body["add_count"], 3);
+    assert_eq!(body["sub_count"], 4);
+    assert_eq!(body["net"], -1);
+
+    let (status, body) = send(
+        &app,
+        put_json(
+            "/api/v1/day/2026-01-03",
+            serde_json::json!({ "add": 5, "sub": 0 }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["net"], 5);
+
+    let (_, range) = send(&app, get("/api/range?from=2026-01-03&to=2026-01-05")).await;
+    assert_eq!(range[0]["add_count"], 5);
+    assert_eq!(range[1]["add_count"], 0);
+    assert_eq!(range[2]["add_count"], 3);
+    assert_eq!(range[2]["tags"], serde_json::json!([]));
+
+    let (status, _) = send(
+        &app,
+        put_json(
+            "/api/day/2999-01-01",
+            serde_json::json!({ "add": 1, "sub": 0 }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = send(
+        &app,
+        put_json(
+            "/api/day/2999-01-01?allow_future=true",
+            serde_json::json!({ "add": 1, "sub": 0 }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["add_count"], 1);
+
+    for (uri, body) in [
+        (
+            "/api/day/2026-1-5",
+            serde_json::json!({ "add": 1, "sub": 0 }),
+        ),
+        (
+            "/api/day/2026-01-05",
+            serde_json::json!({ "add": -1, "sub": 0 }),
+        ),
+        ("/api/day/2026-01-05", serde_json::json!({ "add": 1 })),
+    ] {
+        let (status, _) = send(&app, put_json(uri, body)).await;
+        assert!(status.is_client_error(), "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn undo_reverses_only_the_last_click() {
+    let app = test_app();
+    let undo = || Request::post("/api/undo").body(Body::empty()).unwrap();
+
+    let (status, _) = send(&app, undo()).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+    send(
+        &app,
+        post_json(
+            "/api/click",
+            serde_json::json!({ "action": "add", "amount": 4 }),
+        ),
+    )
+    .await;
+
+    let (status, body) = send(&app, undo()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["add_count"], 1);
+
+    let (status, _) = send(&app, undo()).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["add_count"], 1);
+}
+
+#[tokio::test]
+async fn custom_actions_are_counted_apart_from_net() {
+    let app = test_app();
+    let click = |action: &str| post_json("/api/click", serde_json::json!({ "action": action }));
+
+    send(&app, click("add")).await;
+    send(&app, click("jump")).await;
+    let (status, body) = send(&app, click("jump")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["actions"]["jump"], 2);
+    assert_eq!(body["add_count"], 1);
+    assert_eq!(body["net"], 1);
+
+    let (_, body) = send(
+        &app,
+        Request::post("/api/undo").body(Body::empty()).unwrap(),
+    )
+    .await;
+    assert_eq!(body["actions"]["jump"], 1);
+
+    let (status, _) = send(&app, click("Jump!")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // A day without custom actions looks exactly as it did before them.
+    let (_, other) = send(&app, get("/api/today?counter=coffee")).await;
+    assert!(other.get("actions").is_none());
+}
+
+#[tokio::test]
+async fn idempotency_key_counts_a_retried_click_once() {
+    let app = test_app();
+    let keyed = |uri: &str, action: &str, key: &str| {
+        let mut request = post_json(uri, serde_json::json!({ "action": action }));
+        request
+            .headers_mut()
+            .insert("idempotency-key", key.parse().unwrap());
+        request
+    };
+    let click = |key: &str| keyed("/api/click", "add", key);
+
+    let (status, first) = send(&app, click("tap-1")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, retry) = send(&app, click("tap-1")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(retry, first);
+    assert_eq!(retry["add_count"], 1);
+
+    let (_, other) = send(&app, click("tap-2")).await;
+    assert_eq!(other["add_count"], 2);
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["add_count"], 2);
+
+    let (status, _) = send(&app, click(" ")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // A retry may switch API versions, but not the request itself.
+    let (status, retry) = send(&app, keyed("/api/v1/click", "add", "tap-1")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(retry, first);
+    for (uri, action) in [("/api/click", "sub"), ("/api/click?counter=coffee", "add")] {
+        let (status, body) = send(&app, keyed(uri, action, "tap-1")).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{uri} {action}");
+        assert_eq!(body["error"]["code"], "unprocessable");
+    }
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["add_count"], 2);
+    assert_eq!(today["sub_count"], 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_retries_of_a_key_count_once() {
+    let app = test_app();
+    let requests = (0..20).map(|n| {
+        let app = app.clone();
+        tokio::spawn(async move {
+            let mut request = post_json("/api/click", serde_json::json!({ "action": "add" }));
+            let key = if n % 2 == 0 { "tap-even" } else { "tap-odd" };
+            request
+                .headers_mut()
+                .insert("idempotency-key", key.parse().unwrap());
+            send(&app, request).await
+        })
+    });
+    let mut answers = std::collections::HashSet::new();
+    for result in join_all(requests).await {
+        let (status, body) = result.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        answers.insert(body["add_count"].as_u64().unwrap());
+    }
+    // Every retry got its key's first answer: one for each key.
+    assert_eq!(answers, [1, 2].into());
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["add_count"], 2);
+}
+
+#[tokio::test]
+async fn large_responses_are_gzipped_on_request() {
+    use std::io::Read;
+
+    let app = test_app();
+    let gzip_get = |uri: &str| {
+        Request::get(uri)
+            .header("accept-encoding", "gzip")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(gzip_get("/api/stats")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-encoding"], "gzip");
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let mut json = String::new();
+    flate2::read::GzDecoder::new(&bytes[..])
+        .read_to_string(&mut json)
+        .unwrap();
+    let stats: Value = serde_json::from_str(&json).unwrap();
+    assert!(stats["last_7_days"].is_array());
+
+    // Too small to bother with.
+    let response = app.clone().oneshot(gzip_get("/api/today")).await.unwrap();
+    assert!(response.headers().get("content-encoding").is_none());
+
+    // Nothing changes for clients that don't ask.
+    let response = app.clone().oneshot(get("/api/stats")).await.unwrap();
+    assert!(response.headers().get("content-encoding").is_none());
+}
+
+#[tokio::test]
+async fn requests_are_logged_except_quiet_probes() {
+    #[derive(Clone, Default)]
+    struct Captured(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let app = test_app();
+    let (status, _) = send(&app, get("/healthz")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, get("/api/today")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = logs
+        .lines()
+        .filter(|line| line.contains("finished processing request"))
+        .collect();
+    assert_eq!(lines.len(), 1, "{logs}");
+    assert!(lines[0].contains("GET"));
+    assert!(lines[0].contains("/api/today"));
+    assert!(lines[0].contains("status=200"));
+    assert!(lines[0].contains("latency="));
+}
+
+#[tokio::test]
+async fn daily_target_reports_progress() {
+    let config = Config {
+        daily_goal: Some(4),
+        ..Config::default()
+    };
+    let app = web_app::router(AppState::new(
+        unique_data_path(),
+        AppData::default(),
+        config,
+    ));
+
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["target"], 4);
+    assert_eq!(today["progress"], 0.0);
+    let (_, today) = send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+    assert_eq!(today["progress"], 0.25);
+
+    let (status, body) = send(
+        &app,
+        put_json("/api/target", serde_json::json!({ "target": 0 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["target"], 0);
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["target"], 0);
+    assert!(today["progress"].is_null());
+    let (_, stats) = send(&app, get("/api/stats")).await;
+    assert_eq!(stats["target_met"].as_array().unwrap().len(), 7);
+
+    send(
+        &app,
+        put_json("/api/target", serde_json::json!({ "target": null })),
+    )
+    .await;
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert!(today["target"].is_null());
+    assert!(today["progress"].is_null());
+    let (_, target) = send(&app, get("/api/target")).await;
+    assert!(target["target"].is_null());
+    let (_, stats) = send(&app, get("/api/stats")).await;
+    assert_eq!(stats["target_met"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn named_counters_are_independent() {
+    let app = test_app();
+    let click = |uri: &str| post_json(uri, serde_json::json!({ "action": "add" }));
+
+    send(&app, click("/api/click?counter=coffee")).await;
+    send(&app, click("/api/click?counter=coffee")).await;
+    send(&app, click("/api/click?counter=pushups")).await;
+    send(&app, click("/api/click")).await;
+
+    let (_, coffee) = send(&app, get("/api/today?counter=coffee")).await;
+    assert_eq!(coffee["add_count"], 2);
+    let (_, pushups) = send(&app, get("/api/today?counter=pushups")).await;
+    assert_eq!(pushups["add_count"], 1);
+    let (_, default) = send(&app, get("/api/today")).await;
+    assert_eq!(default["add_count"], 1);
+    let (_, explicit) = send(&app, get("/api/today?counter=default")).await;
+    assert_eq!(explicit["add_count"], 1);
+    let (_, unknown) = send(&app, get("/api/today?counter=tea")).await;
+    assert_eq!(unknown["add_count"], 0);
+
+    let (status, _) = send(&app, click("/api/click?counter=Bad%20Name")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn unit_scale_accepts_fractional_amounts() {
+    let config = Config {
+        unit_scale: Some(2),
+        ..Config::default()
+    };
+    let app = web_app::router(AppState::new(
+        unique_data_path(),
+        AppData::default(),
+        config,
+    ));
+
+    let (status, today) = send(
+        &app,
+        post_json(
+            "/api/click",
+            serde_json::json!({ "action": "add", "amount": 0.5 }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(today["add_count"], 0.5);
+
+    let (status, _) = send(
+        &app,
+        post_json(
+            "/api/click",
+            serde_json::json!({ "action": "add", "amount": 0.3 }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["add_count"], 1.5);
+    assert_eq!(today["net"], 1.5);
+
+    let (_, stats) = send(&app, get("/api/stats")).await;
+    assert_eq!(stats["last_7_days"][6]["add_count"], 1.5);
+}
+
+#[tokio::test]
+async fn click_preview_does_not_change_counts() {
+    let mut data = AppData::default();
+    data.days
+        .insert("2026-01-05".to_string(), DayCounts::new(4, 1));
+    let app = test_app_with(data);
+
+    let (status, preview) = send(
+        &app,
+        post_json(
+            "/api/click/preview",
+            serde_json::json!({ "action": "add", "date": "2026-01-05" }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(preview["add_count"], 5);
+    assert_eq!(preview["net"], 4);
+
+    let (status, _) = send(
+        &app,
+        post_json(
+            "/api/click/preview",
+            serde_json::json!({ "action": "Reset!" }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_, preview) = send(
+        &app,
+        post_json(
+            "/api/click/preview",
+            serde_json::json!({ "action": "sub", "date": "2026-01-05" }),
+        ),
+    )
+    .await;
+    assert_eq!(preview["net"], 2);
+
+    let (_, preview) = send(
+        &app,
+        post_json(
+            "/api/click/preview?counter=coffee",
+            serde_json::json!({ "action": "add", "date": "2026-01-05" }),
+        ),
+    )
+    .await;
+    assert_eq!(preview["add_count"], 1);
+
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["add_count"], 0);
+}
+
+#[tokio::test]
+async fn dry_run_click_leaves_counts_unchanged() {
+    let path = unique_data_path();
+    let state = AppState::new(path.clone(), AppData::default(), Config::default());
+    let app = web_app::router(state);
+    let add = || serde_json::json!({ "action": "add", "amount": 2 });
+
+    let (status, body) = send(&app, post_json("/api/click", add())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["add_count"], 2);
+    assert!(body.get("dry_run").is_none());
+    let saved = std::fs::read_to_string(&path).unwrap();
+
+    let (status, body) = send(&app, post_json("/api/click?dry_run=true", add())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["add_count"], 4);
+    assert_eq!(body["net"], 4);
+    assert_eq!(body["dry_run"], true);
+
+    let (status, body) = send(&app, post_json("/api/click?dry_run=1&include=stats", add())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["today"]["net"], 4);
+    assert_eq!(body["today"]["dry_run"], true);
+    assert_eq!(body["stats"]["last_7_days"][6]["net"], 4);
+
+    let (status, _) = send(&app, post_json("/api/click?dry_run=maybe", add())).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_, today) = send(&app, get("/api/today")).await;
+    assert_eq!(today["add_count"], 2);
+    let (_, stats) = send(&app, get("/api/stats")).await;
+    assert_eq!(stats["last_7_days"][6]["net"], 2);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), saved);
+
+    // Undo still reverses the last real click.
+    let (status, body) = send(&app, post_json("/api/undo", serde_json::json!({}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["add_count"], 0);
+}
+
+#[tokio::test]
+async fn display_timezone_only_changes_formatting() {
+    let now = Local
+        .with_ymd_and_hms(2026, 1, 5, 22, 0, 0)
+        .unwrap()
+        .with_timezone(&Utc);
+    let config = Config {
+        display_timezone: web_app::config::parse_utc_offset("+14:00"),
+        ..Config::default()
+    };
+    let state = AppState::new(unique_data_path(), AppData::default(), config)
+        .with_clock(Arc::new(FixedClock(now)));
+    let app = web_app::router(state);
+
+    let (_, today) = send(&app, get("/api/today?now=1")).await;
+    assert_eq!(today["date"], "2026-01-05");
+    let server_time = today["server_time"].as_str().unwrap();
+    assert!(server_time.ends_with("+14:00"));
+    assert_eq!(
+        chrono::DateTime::parse_from_rfc3339(server_time).unwrap(),
+        now
+    );
+
+    let (_, config) = send(&app, get("/api/config")).await;
+    assert_eq!(config["display_utc_offset"], "+14:00");
+}
+
+#[tokio::test]
+async fn json_import_sums_or_replaces() {
+    let mut data = AppData::default();
+    data.days
+        .insert("2026-01-05".to_string(), DayCounts::new(2, 1));
+    data.days
+        .insert("2026-01-06".to_string(), DayCounts::new(1, 0));
+    let app = test_app_with(data);
+    let payload = serde_json::json!({ "days": { "2026-01-05": { "add": 3, "sub": 0 } } });
+
+    let (status, body) = send(&app, post_json("/api/import?mode=sum", payload.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["mode"], "merge");
+    assert_eq!(body["days_imported"], 1);
+    assert_eq!(body["total_days"], 2);
+    let (_, stats) = send(&app, get("/api/stats?at=2026-01-05T12:00:00Z")).await;
+    assert_eq!(stats["last_7_days"][6]["add_count"], 5);
+    assert_eq!(stats["last_7_days"][6]["sub_count"], 1);
+
+    let (status, body) = send(&app, post_json("/api/import?mode=replace", payload)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["total_days"], 1);
+
+    let bad = serde_json::json!({ "days": { "2026-1-5": { "add": 1, "sub": 0 } } });
+    let (status, _) = send(&app, post_json("/api/import", bad)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn csv_export_lists_every_day_in_order() {
+    async fn export(app: &Router) -> (axum::http::HeaderMap, String) {
+        let response = app.clone().oneshot(get("/api/export.csv")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers().clone();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (headers, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    let (headers, csv) = export(&test_app()).await;
+    assert_eq!(
+        headers["content-disposition"],
+        "attachment; filename=\"clicks.csv\""
+    );
+    assert_eq!(csv, "date,add,sub,net\n");
+
+    let mut data = AppData::default();
+    data.days
+        .insert("2026-01-06".to_string(), DayCounts::new(0, 2));
+    data.days
+        .insert("2025-12-31".to_string(), DayCounts::new(5, 1));
+    let (headers, csv) = export(&test_app_with(data)).await;
+    assert!(
+        headers["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/csv")
+    );
+    assert_eq!(
+        csv,
+        "date,add,sub,net\n2025-12-31,5,1,4\n2026-01-06,0,2,-2\n"
+    );
+}
+
+#[tokio::test]
+async fn csv_round_trips_with_locale_options() {
+    let mut data = AppData::default();
+    data.days
+        .insert("2026-01-05".to_string(), DayCounts::new(4, 1));
+    let app = test_app_with(data);
+
+    let response = app
+        .clone()
+        .oneshot(get(
+            "/api/export.csv?delimiter=%3B&date_format=%25d.%25m.%25Y",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/csv; charset=utf-8"
+    );
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let csv = String::from_utf8(bytes.to_vec()).unwrap();
+    assert_eq!(csv, "date;add;sub;net\n05.01.2026;4;1;3\n");
+
+    let request = Request::post("/api/import.csv?delimiter=%3B&date_format=%25d.%25m.%25Y")
+        .header("content-type", "text/csv")
+        .body(Body::from("06.01.2026;2;0;2\n"))
+        .unwrap();
+    let (status, body) = send(&app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["days_imported"], 1);
+    assert_eq!(body["total_days"], 2);
+
+    let (status, _) = send(&app, get("/api/export.csv?delimiter=ab")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn setup_runs_once_on_a_fresh_install() {
+    let dir = unique_data_path();
+    let data_path = dir.join("state.json");
+    let state =
+        AppState::new(data_path, AppData::default(), Config::default()).with_fresh_install(true);
+    let app = web_app::router(state);
+
+    let (_, status) = send(&app, get("/api/setup/status")).await;
+    assert_eq!(status["is_fresh"], true);
+
+    let (status, _) = send(
+        &app,
+        post_json(
+            "/api/setup",
+            serde_json::json!({ "timezone": "Mars/Olympus" }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let setup = serde_json::json!({ "title": "Push-ups", "timezone": "+02:00", "daily_goal": 50 });
+    let (status, body) = send(&app, post_json("/api/setup", setup.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["restart_required"], true);
+    let written = std::fs::read_to_string(dir.join("config.toml")).unwrap();
+    assert!(written.contains("title = \"Push-ups\""));
+
+    let (_, status) = send(&app, get("/api/setup/status")).await;
+    assert_eq!(status["is_fresh"], false);
+    let (status, _) = send(&app, post_json("/api/setup", setup)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn milestone_validates_its_query() {
+    let app = test_app();
+
+    let (status, body) = send(&app, get("/api/milestone?target=10&metric=net")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["current"], 0);
+    assert!(body["projected_date"].is_null());
+
+    let (status, _) = send(&app, get("/api/milestone?metric=add")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = send(&app, get("/api/milestone?target=10&metric=streak")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn range_returns_every_day_between_the_dates() {
+    let mut data = AppData::default();
+    data.days
+        .insert("2026-01-05".to_string(), DayCounts::new(3, 1));
+    data.days
+        .insert("2026-01-08".to_string(), DayCounts::new(5, 0));
+    let app = test_app_with(data);
+
+    let (status, body) = send(&app, get("/api/range?from=2026-01-04&to=2026-01-08")).await;
+    assert_eq!(status, StatusCode::OK);
+    let days = body.as_array().unwrap();
+    assert_eq!(days.len(), 5);
+    assert_eq!(days[0]["date"], "2026-01-04");
+    assert_eq!(days[0]["net"], 0);
+    assert_eq!(days[1]["net"], 2);
+    assert_eq!(days[2]["add_count"], 0);
+    assert_eq!(days[4]["date"], "2026-01-08");
+    assert_eq!(days[4]["add_count"], 5);
+
+    let (status, body) = send(&app, get("/api/v1/range?from=2025-01-01&to=2026-01-01")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 366);
+
+    for query in [
+        "from=2026-01-08&to=2026-01-04",
+        "from=2026-1-4&to=2026-01-08",
+        "from=2026-01-04",
+        "from=2024-01-01&to=2026-01-01",
+    ] {
+        let (status, _) = send(&app, get(&format!("/api/range?{query}"))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{query}");
+    }
+}
+
+#[tokio::test]
+async fn days_lists_recorded_dates_in_order() {
+    let app = test_app_with(AppData::default());
+    let (status, body) = send(&app, get("/api/days")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, serde_json::json!([]));
+
+    let mut data = AppData::default();
+    for date in ["2026-01-08", "2025-12-31", "2026-01-05"] {
+        data.days.insert(date.to_string(), DayCounts::new(1, 0));
+    }
+    let app = test_app_with(data);
+
+    let (status, body) = send(&app, get("/api/days")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        serde_json::json!(["2025-12-31", "2026-01-05", "2026-01-08"])
+    );
+
+    let (_, body) = send(&app, get("/api/days?from=2026-01-01")).await;
+    assert_eq!(body, serde_json::json!(["2026-01-05", "2026-01-08"]));
+    let (_, body) = send(&app, get("/api/v1/days?from=2026-01-05&to=2026-01-07")).await;
+    assert_eq!(body, serde_json::json!(["2026-01-05"]));
+
+    for query in ["from=2026-01-08&to=2026-01-04", "to=2026-1-4"] {
+        let (status, _) = send(&app, get(&format!("/api/days?{query}"))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{query}");
+    }
+}
+
+#[tokio::test]
+async fn history_pages_through_recorded_days() {
+    let mut data = AppData::default();
+    let first = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    for (n, date) in first.iter_days().step_by(2).take(600).enumerate() {
+        data.days
+            .insert(date.to_string(), DayCounts::new(n as u64, 0));
+    }
+    let app = test_app_with(data);
+
+    let (status, body) = send(&app, get("/api/history?offset=1&limit=2")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["total"], 600);
+    assert_eq!(body["has_more"], true);
+    assert_eq!(body["next_offset"], 3);
+    let days = body["days"].as_array().unwrap();
+    assert_eq!(days.len(), 2);
+    assert_eq!(days[0]["date"], "2024-01-03");
+    assert_eq!(days[0]["add_count"], 1);
+    assert_eq!(days[1]["date"], "2024-01-05");
+
+    let (_, body) = send(&app, get("/api/history")).await;
+    assert_eq!(body["limit"], 100);
+    assert_eq!(body["days"].as_array().unwrap().len(), 100);
+
+    let (_, body) = send(&app, get("/api/v1/history?offset=50&limit=10000")).await;
+    assert_eq!(body["limit"], 500);
+    assert_eq!(body["days"].as_array().unwrap().len(), 500);
+    assert_eq!(body["has_more"], true);
+    assert_eq!(body["next_offset"], 550);
+
+    let (_, body) = send(&app, get("/api/history?offset=550&limit=500")).await;
+    assert_eq!(body["days"].as_array().unwrap().len(), 50);
+    assert_eq!(body["has_more"], false);
+    assert!(body["next_offset"].is_null());
+
+    let (_, body) = send(&app, get("/api/history?offset=600")).await;
+    assert_eq!(body["days"], serde_json::json!([]));
+    assert_eq!(body["has_more"], false);
+}
+
+#[tokio::test]
+async fn heatmap_returns_a_whole_year() {
+    let now = Utc.with_ymd_and_hms(2028, 6, 1, 12, 0, 0).unwrap();
+    let config = Config {
+        timezone: AccountingZone::parse("UTC").unwrap(),
+        ..Config::default()
+    };
+    let mut data = AppData::default();
+    data.days
+        .insert("2028-02-29".to_string(), DayCounts::new(2, 0));
+    let state =
+        AppState::new(unique_data_path(), data, config).with_clock(Arc::new(FixedClock(now)));
+    let app = web_app::router(state);
+
+    let (status, body) = send(&app, get("/api/heatmap")).await;
+    assert_eq!(status, StatusCode::OK);
+    let days = body.as_array().unwrap();
+    assert_eq!(days.len(), 366);
+    assert_eq!(days[59]["date"], "2028-02-29");
+    assert_eq!(days[59]["net"], 2);
+
+    let (status, body) = send(&app, get("/api/heatmap?year=2027")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 365);
+
+    for query in ["year=12", "year=20000", "year=abc"] {
+        let (status, _) = send(&app, get(&format!("/api/heatmap?{query}"))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{query}");
+    }
+}
+
+#[tokio::test]
+async fn reset_needs_confirmation_and_clears_everything() {
+    let path = unique_data_path();
+    let mut data = AppData::default();
+    data.days
+        .insert("2026-01-05".to_string(), DayCounts::new(3, 1));
+    data.days
+        .insert("2026-01-06".to_string(), DayCounts::new(1, 0));
+    data.counter_mut("coffee")
+        .insert("2026-01-06".to_string(), DayCounts::new(2, 0));
+    let app = web_app::router(AppState::new(path.clone(), data, Config::default()));
+
+    for body in [
+        serde_json::json!({}),
+        serde_json::json!({ "confirm": "reset" }),
+        serde_json::json!({ "confirm": "yes" }),
+    ] {
+        let (status, _) = send(&app, post_json("/api/reset", body.clone())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    }
+    let (_, range) = send(&app, get("/api/range?from=2026-01-05&to=2026-01-06")).await;
+    assert_eq!(range[0]["add_count"], 3);
+    assert!(!path.exists());
+
+    let (status, body) = send(
+        &app,
+        post_json("/api/reset", serde_json::json!({ "confirm": "RESET" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["days_removed"], 3);
+
+    let (_, range) = send(&app, get("/api/range?from=2026-01-05&to=2026-01-06")).await;
+    assert_eq!(range[0]["add_count"], 0);
+    let (saved, _) = web_app::load_data(&path).await;
+    assert!(saved.days.is_empty());
+    assert!(saved.counters.is_empty());
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn histogram_buckets_recorded_days() {
+    let mut data = AppData::default();
+    data.days
+        .insert("2026-01-05".to_string(), DayCounts::new(3, 1));
+    data.days
+        .insert("2026-01-06".to_string(), DayCounts::new(12, 0));
+    let app = test_app_with(data);
+
+    let (status, body) = send(&app, get("/api/histogram?bins=4")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["metric"], "net");
+    assert_eq!(body["min"], 2);
+    assert_eq!(body["max"], 12);
+    let bins = body["bins"].as_array().unwrap();
+    assert!(bins.len() <= 4);
+    let total: u64 = bins.iter().map(|bin| bin["count"].as_u64().unwrap()).sum();
+    assert_eq!(total, 2);
+
+    let (_, body) = send(&app, get("/api/histogram?from=2026-01-06&metric=add")).await;
+    assert_eq!(body["min"], 12);
+
+    for query in [
+        "bins=0",
+        "bins=1000",
+        "metric=streak",
+        "from=2026-01-07&to=2026-01-01",
+    ] {
+        let (status, _) = send(&app, get(&format!("/api/histogram?{query}"))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{query}");
+    }
+}
+
+#[tokio::test]
+async fn maintenance_message_can_gate_writes() {
+    let app = test_app();
+
+    let (status, body) = send(
+        &app,
+        post_json(
+            "/api/maintenance/message",
+            serde_json::json!({ "message": " Migrating ", "read_only": true }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["message"], "Migrating");
+
+    let (_, config) = send(&app, get("/api/config")).await;
+    assert_eq!(config["maintenance_message"], "Migrating");
+    assert_eq!(config["maintenance_read_only"], true);
+
+    let response = app.clone().oneshot(get("/")).await.unwrap();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    assert!(String::from_utf8_lossy(&bytes).contains("Migrating"));
+
+    let (status, _) = send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    let (status, _) = send(&app, get("/api/today")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = send(
+        &app,
+        post_json(
+            "/api/maintenance/message",
+            serde_json::json!({ "message": "", "read_only": true }),
+        ),
+    )
+    .await;
+    assert!(body["message"].is_null());
+    assert_eq!(body["read_only"], false);
+    let (status, _) = send(
+        &app,
+        post_json("/api/click", serde_json::json!({ "action": "add" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn errors_follow_the_accept_header() {
+    let app = test_app();
+    let bad_click = |accept: Option<&str>| {
+        let mut request = Request::post("/api/click").header("content-type", "application/json");
+        if let Some(accept) = accept {
+            request = request.header("accept", accept);
+        }
+        request.body(Body::from(r#"{"action":""}"#)).unwrap()
+    };
+
+    for accept in [None, Some("application/json"), Some("*/*")] {
+        let (status, body) = send(&app, bad_click(accept)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], "bad_request", "{accept:?}");
+        assert_eq!(
+            body["error"]["message"],
+            "action must be 'add', 'sub' or a name of up to 32 lowercase letters, digits, '-' or '_'"
+        );
+    }
+
+    let response = app
+        .clone()
+        .oneshot(bad_click(Some("text/html,application/xhtml+xml,*/*;q=0.8")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(
+        response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html")
+    );
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let page = String::from_utf8(bytes.to_vec()).unwrap();
+    assert!(page.contains("<p>action must be 'add', 'sub' or a name of up to 32 lowercase letters, digits, '-' or '_'</p>"));
+
+    let response = app
+        .clone()
+        .oneshot(bad_click(Some("text/plain")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(
+        response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain")
+    );
+
+    // q-values decide; JSON wins ties.
+    for accept in [
+        "application/json, text/html;q=0.1",
+        "text/plain, application/json",
+        "text/*;q=0.5, application/json",
+    ] {
+        let (status, body) = send(&app, bad_click(Some(accept))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], "bad_request", "{accept}");
+    }
+}
+
+#[tokio::test]
+async fn negotiated_errors_keep_their_headers() {
+    let config = Config {
+        api_token: Some("s3cret".to_string()),
+        click_rate_limit: Some(1),
+        trust_proxy: true,
+        ..Config::default()
+    };
+    let app = web_app::router(AppState::new(
+        unique_data_path(),
+        AppData::default(),
+        config,
+    ));
+    let request = |accept: &str, token: Option<&str>| {
+        let mut request = Request::post("/click/add")
+            .header("accept", accept)
+            .header("x-forwarded-for", "203.0.113.9");
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {token}"));
+        }
+        request.body(Body::empty()).unwrap()
+    };
+
+    let (status, _) = send(&app, request("application/json", Some("s3cret"))).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    for (accept, content_type) in [("text/html", "text/html"), ("text/plain", "text/plain")] {
+        let response = app.clone().oneshot(request(accept, None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()["www-authenticate"], "Bearer");
+        assert!(
+            response.headers()["content-type"]
+                .to_str()
+                .unwrap()
+                .starts_with(content_type)
+        );
+
+        let response = app
+            .clone()
+            .oneshot(request(accept, Some("s3cret")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key("retry-after"));
+        assert!(
+            response.headers()["content-type"]
+                .to_str()
+                .unwrap()
+                .starts_with(content_type)
+        );
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(
+            String::from_utf8(bytes.to_vec())
+                .unwrap()
+                .contains("too many clicks")
+        );
+    }
+}
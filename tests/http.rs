@@ -18,6 +18,7 @@ struct DailyCountsResponse {
 
 struct TestServer {
     base_url: String,
+    data_path: String,
     child: Child,
 }
 
@@ -79,10 +80,10 @@ async fn wait_until_ready(base_url: &str) {
     let client = Client::new();
     let deadline = Instant::now() + Duration::from_secs(3);
     loop {
-        if let Ok(resp) = client.get(format!("{base_url}/api/v1/today")).send().await {
-            if resp.status().is_success() {
-                return;
-            }
+        if let Ok(resp) = client.get(format!("{base_url}/api/v1/today")).send().await
+            && resp.status().is_success()
+        {
+            return;
         }
         if Instant::now() > deadline {
             panic!("server did not become ready");
@@ -92,24 +93,31 @@ async fn wait_until_ready(base_url: &str) {
 }
 
 async fn spawn_server() -> TestServer {
+    spawn_server_with(&[]).await
+}
+
+async fn spawn_server_with(envs: &[(&str, &str)]) -> TestServer {
     let port = pick_free_port();
     let data_path = unique_data_path();
     let child = Command::new(env!("CARGO_BIN_EXE_web_app"))
         .env("PORT", port.to_string())
-        .env("APP_DATA_PATH", data_path)
+        .env("APP_DATA_PATH", &data_path)
+        .env("APP_BACKUPS", "0")
         .env("RUST_LOG", "info")
+        .envs(envs.iter().copied())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
         .spawn()
         .expect("failed to spawn server");
 
-    #[cfg(unix)]
-    cleanup::register(child.id());
-
     let base_url = format!("http://127.0.0.1:{port}");
     wait_until_ready(&base_url).await;
 
-    TestServer { base_url, child }
+    TestServer {
+        base_url,
+        data_path,
+        child,
+    }
 }
 
 async fn shared_server() -> Arc<TestServer> {
@@ -118,6 +126,8 @@ async fn shared_server() -> Arc<TestServer> {
         return Arc::clone(server);
     }
     let server = Arc::new(spawn_server().await);
+    #[cfg(unix)]
+    cleanup::register(server.child.id());
     *guard = Some(Arc::clone(&server));
     server
 }
@@ -197,3 +207,50 @@ async fn http_click_sub_updates_today() {
     assert_eq!(today.net, before.net - 1);
     assert!(!today.date.is_empty());
 }
+
+#[cfg(unix)]
+#[tokio::test]
+async fn sigterm_writes_pending_clicks_before_exiting() {
+    // A debounce far longer than the test means only the shutdown flush can
+    // get the clicks into the file.
+    let mut server = spawn_server_with(&[("APP_PERSIST_DEBOUNCE_MS", "600000")]).await;
+    let client = Client::new();
+    for _ in 0..3 {
+        let response = client
+            .post(format!("{}/api/v1/click", server.base_url))
+            .json(&serde_json::json!({ "action": "add" }))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+    }
+    assert!(std::fs::metadata(&server.data_path).is_err());
+
+    unsafe {
+        libc::kill(server.child.id() as i32, libc::SIGTERM);
+    }
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let status = loop {
+        if let Some(status) = server.child.try_wait().unwrap() {
+            break status;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "server did not exit after SIGTERM"
+        );
+        sleep(Duration::from_millis(50)).await;
+    };
+    assert!(status.success());
+
+    let saved: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&server.data_path).unwrap()).unwrap();
+    let adds: u64 = saved["days"]
+        .as_object()
+        .unwrap()
+        .values()
+        .map(|counts| counts["add"].as_u64().unwrap())
+        .sum();
+    assert_eq!(adds, 3);
+
+    let _ = std::fs::remove_file(&server.data_path);
+}